@@ -0,0 +1,294 @@
+// A borrowed view of a job, for reading a few keys out of a large number of XML plists.
+//
+// plist's deserializer copies every string it reads, so LaunchdRef reads the XML itself. Its
+// strings point into the buffer, unless they contain an escape such as `&amp;`, and the values of
+// the keys it doesn't keep are skipped without being decoded. Converting it to a Launchd parses the
+// buffer again, with every key.
+use crate::xml::Parser;
+use crate::{sorted_entries, Error, KeyPath, Launchd};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// The string-valued keys of a job, borrowed from an XML plist.
+///
+/// Usage:
+/// ```
+/// use launchd::{Error, Launchd, LaunchdRef};
+/// use std::convert::TryFrom;
+///
+/// fn example() -> Result<(), Error> {
+///     let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <plist version="1.0">
+/// <dict>
+///     <key>Label</key>
+///     <string>com.example.backup</string>
+///     <key>ProgramArguments</key>
+///     <array>
+///         <string>/usr/local/bin/backup</string>
+///         <string>--quiet</string>
+///     </array>
+///     <key>UserName</key>
+///     <string>backup</string>
+///     <key>StartInterval</key>
+///     <integer>3600</integer>
+/// </dict>
+/// </plist>"#;
+///
+///     let launchd = LaunchdRef::from_xml(xml)?;
+///     assert_eq!(launchd.label(), "com.example.backup");
+///     assert_eq!(launchd.user_name(), Some("backup"));
+///
+///     let launchd = Launchd::try_from(launchd)?;
+///     assert_eq!(launchd.start_interval(), Some(3600));
+///     Ok(())
+/// }
+/// example().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchdRef<'a> {
+    xml: &'a str,
+    label: Cow<'a, str>,
+    program: Option<Cow<'a, str>>,
+    program_arguments: Option<Vec<Cow<'a, str>>>,
+    bundle_program: Option<Cow<'a, str>>,
+    user_name: Option<Cow<'a, str>>,
+    group_name: Option<Cow<'a, str>>,
+    root_directory: Option<Cow<'a, str>>,
+    working_directory: Option<Cow<'a, str>>,
+    environment_variables: Option<HashMap<Cow<'a, str>, Cow<'a, str>>>,
+    standard_in_path: Option<Cow<'a, str>>,
+    standard_out_path: Option<Cow<'a, str>>,
+    standard_error_path: Option<Cow<'a, str>>,
+    watch_paths: Option<Vec<Cow<'a, str>>>,
+    queue_directories: Option<Vec<Cow<'a, str>>>,
+}
+
+impl<'a> LaunchdRef<'a> {
+    /// Reads the keys of a job from an XML plist. The other keys are skipped, so a value of the
+    /// wrong type in one of them is only found by converting it to a `Launchd`.
+    pub fn from_xml(xml: &'a str) -> Result<Self, Error> {
+        let mut parser = Parser::new(xml);
+        parser.open_root()?;
+
+        let mut label = None;
+        let mut program = None;
+        let mut program_arguments = None;
+        let mut bundle_program = None;
+        let mut user_name = None;
+        let mut group_name = None;
+        let mut root_directory = None;
+        let mut working_directory = None;
+        let mut environment_variables = None;
+        let mut standard_in_path = None;
+        let mut standard_out_path = None;
+        let mut standard_error_path = None;
+        let mut watch_paths = None;
+        let mut queue_directories = None;
+        while let Some(key) = parser.key()? {
+            match key.as_ref() {
+                "Label" => label = Some(parser.string(&key)?),
+                "Program" => program = Some(parser.string(&key)?),
+                "ProgramArguments" => program_arguments = Some(parser.strings(&key)?),
+                "BundleProgram" => bundle_program = Some(parser.string(&key)?),
+                "UserName" => user_name = Some(parser.string(&key)?),
+                "GroupName" => group_name = Some(parser.string(&key)?),
+                "RootDirectory" => root_directory = Some(parser.string(&key)?),
+                "WorkingDirectory" => working_directory = Some(parser.string(&key)?),
+                "EnvironmentVariables" => environment_variables = Some(parser.dictionary(&key)?),
+                "StandardInPath" => standard_in_path = Some(parser.string(&key)?),
+                "StandardOutPath" => standard_out_path = Some(parser.string(&key)?),
+                "StandardErrorPath" => standard_error_path = Some(parser.string(&key)?),
+                "WatchPaths" => watch_paths = Some(parser.strings(&key)?),
+                "QueueDirectories" => queue_directories = Some(parser.strings(&key)?),
+                _ => parser.skip()?,
+            }
+        }
+
+        Ok(LaunchdRef {
+            xml,
+            label: label.ok_or_else(|| Error::KeyPathNotFound(KeyPath::new().key("Label")))?,
+            program,
+            program_arguments,
+            bundle_program,
+            user_name,
+            group_name,
+            root_directory,
+            working_directory,
+            environment_variables,
+            standard_in_path,
+            standard_out_path,
+            standard_error_path,
+            watch_paths,
+            queue_directories,
+        })
+    }
+
+    pub fn from_xml_bytes(xml: &'a [u8]) -> Result<Self, Error> {
+        let xml = std::str::from_utf8(xml).map_err(|error| Error::InvalidXml(error.to_string()))?;
+        Self::from_xml(xml)
+    }
+
+    /// Parses the whole plist, including the keys that `LaunchdRef` skips.
+    pub fn to_launchd(&self) -> Result<Launchd, Error> {
+        Launchd::from_bytes(self.xml.as_bytes())
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn program(&self) -> Option<&str> {
+        self.program.as_deref()
+    }
+
+    pub fn program_arguments(&self) -> Option<&[Cow<'a, str>]> {
+        self.program_arguments.as_deref()
+    }
+
+    pub fn bundle_program(&self) -> Option<&str> {
+        self.bundle_program.as_deref()
+    }
+
+    pub fn user_name(&self) -> Option<&str> {
+        self.user_name.as_deref()
+    }
+
+    pub fn group_name(&self) -> Option<&str> {
+        self.group_name.as_deref()
+    }
+
+    pub fn root_directory(&self) -> Option<&str> {
+        self.root_directory.as_deref()
+    }
+
+    pub fn working_directory(&self) -> Option<&str> {
+        self.working_directory.as_deref()
+    }
+
+    pub fn environment_variables(&self) -> Option<&HashMap<Cow<'a, str>, Cow<'a, str>>> {
+        self.environment_variables.as_ref()
+    }
+
+    pub fn iter_environment_variables(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        sorted_entries(self.environment_variables.as_ref())
+            .map(|(name, value)| (name.as_ref(), value.as_ref()))
+    }
+
+    pub fn standard_in_path(&self) -> Option<&str> {
+        self.standard_in_path.as_deref()
+    }
+
+    pub fn standard_out_path(&self) -> Option<&str> {
+        self.standard_out_path.as_deref()
+    }
+
+    pub fn standard_error_path(&self) -> Option<&str> {
+        self.standard_error_path.as_deref()
+    }
+
+    pub fn watch_paths(&self) -> Option<&[Cow<'a, str>]> {
+        self.watch_paths.as_deref()
+    }
+
+    pub fn queue_directories(&self) -> Option<&[Cow<'a, str>]> {
+        self.queue_directories.as_deref()
+    }
+}
+
+impl TryFrom<LaunchdRef<'_>> for Launchd {
+    type Error = Error;
+
+    fn try_from(launchd: LaunchdRef<'_>) -> Result<Self, Error> {
+        launchd.to_launchd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::corpus;
+
+    #[test]
+    fn strings_are_borrowed_from_the_buffer() {
+        for file in corpus() {
+            let xml = std::fs::read_to_string(&file).unwrap();
+            let borrowed = LaunchdRef::from_xml(&xml).unwrap();
+            let launchd = Launchd::from_file(&file).unwrap();
+
+            assert!(matches!(borrowed.label, Cow::Borrowed(_)), "{:?}", file);
+            assert_eq!(borrowed.label(), launchd.label(), "{:?}", file);
+            assert_eq!(
+                borrowed.program(),
+                launchd.program().and_then(|program| program.to_str()),
+                "{:?}",
+                file
+            );
+            assert_eq!(
+                borrowed
+                    .program_arguments()
+                    .map(|arguments| arguments.iter().map(AsRef::as_ref).collect::<Vec<&str>>()),
+                launchd
+                    .program_arguments()
+                    .map(|arguments| arguments.iter().map(String::as_str).collect()),
+                "{:?}",
+                file
+            );
+            assert!(borrowed
+                .iter_environment_variables()
+                .eq(launchd.iter_environment_variables()));
+            assert_eq!(Launchd::try_from(borrowed).unwrap(), launchd, "{:?}", file);
+        }
+    }
+
+    #[test]
+    fn escapes_are_decoded() {
+        let xml = r#"<plist version="1.0"><dict>
+            <key>Label</key><string>com.example.agent</string>
+            <key>Program</key><string>/Applications/Tom &amp; Jerry.app/run&#x2e;sh</string>
+            <key>UserName</key><string><![CDATA[nobody]]></string>
+            <key>ProgramArguments</key><array><string/></array>
+            <key>KeepAlive</key><dict><key>SuccessfulExit</key><false/></dict>
+        </dict></plist>"#;
+        let launchd = LaunchdRef::from_xml(xml).unwrap();
+        assert_eq!(
+            launchd.program,
+            Some(Cow::Owned(
+                "/Applications/Tom & Jerry.app/run.sh".to_string()
+            ))
+        );
+        assert_eq!(launchd.user_name, Some(Cow::Borrowed("nobody")));
+        assert_eq!(launchd.program_arguments(), Some(&[Cow::Borrowed("")][..]));
+    }
+
+    #[test]
+    fn invalid_plists_are_an_error() {
+        let plist = |body: &str| format!("<plist><dict>{}</dict></plist>", body);
+        assert!(matches!(
+            LaunchdRef::from_xml(&plist("<key>Program</key><string>/bin/true</string>")),
+            Err(Error::KeyPathNotFound(path)) if path.to_string() == "Label"
+        ));
+        assert!(matches!(
+            LaunchdRef::from_xml(&plist("<key>Label</key><integer>1</integer>")),
+            Err(Error::InvalidValueAt(path, _)) if path.to_string() == "Label"
+        ));
+        assert!(matches!(
+            LaunchdRef::from_xml(&plist(
+                "<key>Label</key><string>a</string><key>WatchPaths</key><array><string>/tmp</string><true/></array>"
+            )),
+            Err(Error::InvalidValueAt(path, _)) if path.to_string() == "WatchPaths[1]"
+        ));
+        assert!(matches!(
+            LaunchdRef::from_xml(&plist("<key>Label</key><string>a</dict>")),
+            Err(Error::InvalidXml(_))
+        ));
+        assert!(matches!(
+            LaunchdRef::from_xml(&plist("<key>Label</key><string>&nbsp;</string>")),
+            Err(Error::InvalidXml(_))
+        ));
+        assert!(matches!(
+            LaunchdRef::from_xml_bytes(b"<plist><dict>\xff</dict></plist>"),
+            Err(Error::InvalidXml(_))
+        ));
+    }
+}
@@ -0,0 +1,278 @@
+// Apple's canonical XML layout for plists, as written by `plutil -convert xml1`.
+//
+// plutil writes through CFPropertyListCreateData, which indents with tabs, writes empty
+// collections as `<dict/>` and `<array/>`, sorts the keys of every dictionary by their UTF-16 code
+// units, and wraps base64 data at 76 columns (counting a tab as 8). The plist crate's writer
+// doesn't do any of that, so this is a separate, small writer over plist::Value.
+use crate::value::BASE64;
+use crate::{Error, KeyPath, Launchd};
+use plist::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+"#;
+const FOOTER: &str = "</plist>\n";
+
+const MAX_LINE_LENGTH: usize = 76;
+
+impl Launchd {
+    /// Writes this job in the layout of `plutil -convert xml1`, so reformatting a plist that was
+    /// written by Apple's tools doesn't change it. The keys are always sorted, whatever the
+    /// [`KeyOrder`](crate::KeyOrder) of the job.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut buffer = Vec::new();
+    ///     Launchd::new("LABEL", "/bin/true")?
+    ///         .with_run_at_load(true)
+    ///         .to_writer_canonical(&mut buffer)?;
+    ///
+    ///     let xml = String::from_utf8(buffer).unwrap();
+    ///     assert!(xml.ends_with(concat!(
+    ///         "<dict>\n",
+    ///         "\t<key>Label</key>\n",
+    ///         "\t<string>LABEL</string>\n",
+    ///         "\t<key>Program</key>\n",
+    ///         "\t<string>/bin/true</string>\n",
+    ///         "\t<key>RunAtLoad</key>\n",
+    ///         "\t<true/>\n",
+    ///         "</dict>\n",
+    ///         "</plist>\n",
+    ///     )));
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn to_writer_canonical<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let mut xml = String::from(HEADER);
+        append_value(&mut xml, &self.to_plist_value()?, &KeyPath::new(), 0)?;
+        xml.push_str(FOOTER);
+        writer.write_all(xml.as_bytes()).map_err(Error::Io)
+    }
+
+    pub fn to_file_canonical<P: AsRef<Path>>(&self, file: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(file).map_err(Error::Io)?);
+        self.to_writer_canonical(&mut writer)?;
+        writer.flush().map_err(Error::Io)
+    }
+}
+
+fn append_value(
+    xml: &mut String,
+    value: &Value,
+    path: &KeyPath,
+    indent: usize,
+) -> Result<(), Error> {
+    append_indent(xml, indent);
+    match value {
+        Value::Dictionary(dictionary) if dictionary.is_empty() => xml.push_str("<dict/>\n"),
+        Value::Dictionary(dictionary) => {
+            xml.push_str("<dict>\n");
+            let mut entries: Vec<_> = dictionary.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            for (key, value) in entries {
+                append_indent(xml, indent + 1);
+                append_element(xml, "key", key);
+                append_value(xml, value, &path.clone().key(key), indent + 1)?;
+            }
+            append_indent(xml, indent);
+            xml.push_str("</dict>\n");
+        }
+        Value::Array(array) if array.is_empty() => xml.push_str("<array/>\n"),
+        Value::Array(array) => {
+            xml.push_str("<array>\n");
+            for (i, value) in array.iter().enumerate() {
+                append_value(xml, value, &path.clone().index(i), indent + 1)?;
+            }
+            append_indent(xml, indent);
+            xml.push_str("</array>\n");
+        }
+        Value::Boolean(true) => xml.push_str("<true/>\n"),
+        Value::Boolean(false) => xml.push_str("<false/>\n"),
+        Value::Data(data) => {
+            xml.push_str("<data>\n");
+            append_base64(xml, data, indent);
+            append_indent(xml, indent);
+            xml.push_str("</data>\n");
+        }
+        Value::Date(date) => append_element(xml, "date", &date.to_xml_format()),
+        Value::Integer(integer) => append_element(xml, "integer", &integer.to_string()),
+        Value::Real(real) => append_element(xml, "real", &format_real(*real)),
+        Value::String(string) => append_element(xml, "string", string),
+        // CoreFoundation writes a UID as a dictionary, as XML has no element for it.
+        Value::Uid(uid) => {
+            xml.push_str("<dict>\n");
+            append_indent(xml, indent + 1);
+            append_element(xml, "key", "CF$UID");
+            append_indent(xml, indent + 1);
+            append_element(xml, "integer", &uid.get().to_string());
+            append_indent(xml, indent);
+            xml.push_str("</dict>\n");
+        }
+        // Value is non-exhaustive, but every variant of plist 1.x is handled above.
+        _ => return Err(Error::UnsupportedValue(path.clone())),
+    }
+    Ok(())
+}
+
+fn append_indent(xml: &mut String, indent: usize) {
+    xml.extend(std::iter::repeat_n('\t', indent));
+}
+
+fn append_element(xml: &mut String, name: &str, text: &str) {
+    xml.push('<');
+    xml.push_str(name);
+    xml.push('>');
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            c => xml.push(c),
+        }
+    }
+    xml.push_str("</");
+    xml.push_str(name);
+    xml.push_str(">\n");
+}
+
+// CoreFoundation prints reals with `%.17g`, and infinity and NaN as words.
+fn format_real(real: f64) -> String {
+    if real.is_nan() {
+        return "nan".to_string();
+    }
+    if real.is_infinite() {
+        return if real > 0.0 { "+infinity" } else { "-infinity" }.to_string();
+    }
+    // %g picks plain or exponent notation from the exponent after rounding to 17 digits, and then
+    // removes trailing zeroes.
+    let scientific = format!("{:.16e}", real);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let trim = |digits: &str| {
+        if digits.contains('.') {
+            digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        } else {
+            digits.to_string()
+        }
+    };
+    if (-4..17).contains(&exponent) {
+        trim(&format!("{:.*}", (16 - exponent) as usize, real))
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+    }
+}
+
+// Base64 in lines of at most 76 columns, where every tab of the indentation counts as 8 columns.
+// Like CoreFoundation, the length is checked after each input byte, so a line can be one column
+// longer, and the indentation is capped at 8 tabs.
+fn append_base64(xml: &mut String, data: &[u8], indent: usize) {
+    let indent = indent.min(8);
+    let max_length = MAX_LINE_LENGTH - 8 * indent;
+    let mut line = String::new();
+    let encode = |index: u8| BASE64[usize::from(index)] as char;
+
+    for (i, byte) in data.iter().enumerate() {
+        match i % 3 {
+            0 => line.push(encode(byte >> 2)),
+            1 => line.push(encode(((data[i - 1] << 4) & 0x30) | (byte >> 4))),
+            _ => {
+                line.push(encode(((data[i - 1] << 2) & 0x3c) | (byte >> 6)));
+                line.push(encode(byte & 0x3f));
+            }
+        }
+        if line.len() >= max_length {
+            append_indent(xml, indent);
+            xml.push_str(&line);
+            xml.push('\n');
+            line.clear();
+        }
+    }
+    match data.len() % 3 {
+        1 => {
+            line.push(encode((data[data.len() - 1] << 4) & 0x30));
+            line.push_str("==");
+        }
+        2 => {
+            line.push(encode((data[data.len() - 1] << 2) & 0x3c));
+            line.push('=');
+        }
+        _ => {}
+    }
+    if !line.is_empty() {
+        append_indent(xml, indent);
+        xml.push_str(&line);
+        xml.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_case;
+    use crate::KeyOrder;
+
+    fn canonical(launchd: &Launchd) -> String {
+        let mut buffer = Vec::new();
+        launchd.to_writer_canonical(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn canonical_plist_is_unchanged() {
+        let source = std::fs::read_to_string(test_case!("canonical-1.plist")).unwrap();
+        let launchd = Launchd::from_bytes(source.as_bytes()).unwrap();
+        assert_eq!(canonical(&launchd), source);
+    }
+
+    #[test]
+    fn formatting_is_idempotent_and_ignores_key_order() {
+        let launchd = Launchd::from_file(test_case!("keepalive-1.plist")).unwrap();
+        let formatted = canonical(&launchd);
+        assert!(
+            formatted.find("<key>EnvironmentVariables</key>")
+                < formatted.find("<key>KeepAlive</key>")
+        );
+        assert!(formatted.contains("\t\t<key>AGENT_MODE</key>\n\t\t<string>daemon</string>\n"));
+        assert!(!formatted.contains(" />"));
+
+        let reformatted = Launchd::from_bytes(formatted.as_bytes()).unwrap();
+        assert_eq!(canonical(&reformatted), formatted);
+        assert_eq!(
+            canonical(&reformatted.with_key_order(KeyOrder::Original)),
+            formatted
+        );
+    }
+
+    #[test]
+    fn reals_and_data_are_written_like_core_foundation() {
+        let cases = [
+            (0.0, "0"),
+            (-1.5, "-1.5"),
+            (0.1, "0.10000000000000001"),
+            (1e16, "10000000000000000"),
+            (1e17, "1e+17"),
+            (0.0001, "0.0001"),
+            (0.00001, "1.0000000000000001e-05"),
+            (f64::INFINITY, "+infinity"),
+        ];
+        for (real, expected) in cases.iter() {
+            assert_eq!(format_real(*real), *expected);
+        }
+
+        let mut xml = String::new();
+        append_base64(&mut xml, &[0; 12], 9);
+        assert_eq!(xml, "\t\t\t\t\t\t\t\tAAAAAAAAAAAA\n\t\t\t\t\t\t\t\tAAAA\n");
+    }
+}
@@ -0,0 +1,320 @@
+// Conversion of StartCalendarIntervals to crontab(5) lines.
+//
+// launchd fires a dictionary when every field matches, except for Day and Weekday: like crontab(5),
+// a job with both restricted fires when either matches. This means every set of CalendarIntervals
+// can be expressed exactly in crontab(5) syntax, as long as the values lie within launchd's bounds.
+use crate::{CalendarInterval, Error, Launchd};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const MINUTES: u64 = (1 << 60) - 1;
+const HOURS: u64 = (1 << 24) - 1;
+const DAYS: u64 = ((1 << 32) - 1) & !1;
+const WEEKDAYS: u64 = (1 << 7) - 1;
+const MONTHS: u64 = ((1 << 13) - 1) & !1;
+
+impl CalendarInterval {
+    /// Converts a set of `CalendarInterval`s to five-field crontab(5) lines that fire at exactly
+    /// the same times. Intervals that only differ in one field are merged into lists, ranges and
+    /// steps, so the result is usually much smaller than the input.
+    ///
+    /// Returns an error if an interval contains a value launchd doesn't accept, as there is no
+    /// crontab equivalent for an interval that never fires because of it.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut intervals = Vec::new();
+    ///     for weekday in 1..=5 {
+    ///         for hour in 9..=17 {
+    ///             intervals.push(CalendarInterval::default().with_weekday(weekday)?.with_hour(hour)?.with_minute(30)?);
+    ///         }
+    ///     }
+    ///     assert_eq!(CalendarInterval::to_cron_lines(&intervals)?, vec!["30 9-17 * * 1-5"]);
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn to_cron_lines(intervals: &[CalendarInterval]) -> Result<Vec<String>, Error> {
+        let mut lines = intervals
+            .iter()
+            .map(CronLine::try_from_interval)
+            .collect::<Result<Vec<_>, _>>()?;
+        loop {
+            let count = lines.len();
+            for field in 0..4 {
+                lines = merge_on(lines, field);
+            }
+            if lines.len() == count {
+                break;
+            }
+        }
+        Ok(lines.iter().map(CronLine::to_string).collect())
+    }
+}
+
+impl Launchd {
+    /// Converts the `StartCalendarInterval`s of this job to crontab(5) lines.
+    /// See [`CalendarInterval::to_cron_lines`].
+    pub fn start_calendar_intervals_to_cron(&self) -> Result<Vec<String>, Error> {
+        CalendarInterval::to_cron_lines(
+            self.start_calendar_intervals.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+// A crontab(5) line, with every field represented as a bitset.
+// An empty `days` or `weekdays` set means that field is "*".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CronLine {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    weekdays: u64,
+    months: u64,
+}
+
+impl CronLine {
+    fn try_from_interval(ci: &CalendarInterval) -> Result<Self, Error> {
+        let weekday = match ci.weekday {
+            Some(7) => Some(0),
+            weekday => weekday,
+        };
+        let mut line = CronLine {
+            minutes: field_bits(ci.minute, 0..=59, MINUTES, "Minute")?,
+            hours: field_bits(ci.hour, 0..=23, HOURS, "Hour")?,
+            days: field_bits(ci.day, 1..=31, 0, "Day")?,
+            weekdays: field_bits(weekday, 0..=6, 0, "Weekday")?,
+            months: field_bits(ci.month, 1..=12, MONTHS, "Month")?,
+        };
+        line.normalize();
+        Ok(line)
+    }
+
+    // If every day of the month, or every day of the week is allowed, the line fires every day.
+    fn normalize(&mut self) {
+        if self.days == DAYS || self.weekdays == WEEKDAYS {
+            self.days = 0;
+            self.weekdays = 0;
+        }
+    }
+
+    // Returns the fields other than `field`, which are equal for lines that can be merged on it.
+    fn key(&self, field: usize) -> [u64; 4] {
+        let days = [self.days, self.weekdays];
+        let mut key = [
+            self.minutes,
+            self.hours,
+            days[0] << 7 | days[1],
+            self.months,
+        ];
+        key[field] = 0;
+        key
+    }
+
+    fn merge(&mut self, other: &CronLine, field: usize) {
+        match field {
+            0 => self.minutes |= other.minutes,
+            1 => self.hours |= other.hours,
+            2 => {
+                // Unrestricted days can't be restricted by a union.
+                if (self.days | self.weekdays) == 0 || (other.days | other.weekdays) == 0 {
+                    self.days = 0;
+                    self.weekdays = 0;
+                } else {
+                    self.days |= other.days;
+                    self.weekdays |= other.weekdays;
+                    self.normalize();
+                }
+            }
+            _ => self.months |= other.months,
+        }
+    }
+}
+
+impl std::fmt::Display for CronLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Vixie cron and cronie treat a day field starting with "*" (e.g. "*/2") as unrestricted,
+        // which would turn the OR of Day and Weekday into an AND. So those never use "*/n".
+        write!(
+            f,
+            "{} {} {} {} {}",
+            render_field(self.minutes, 0, 59, true),
+            render_field(self.hours, 0, 23, true),
+            render_field(self.days, 1, 31, false),
+            render_field(self.months, 1, 12, true),
+            render_field(self.weekdays, 0, 6, false),
+        )
+    }
+}
+
+fn field_bits(
+    value: Option<u8>,
+    bounds: std::ops::RangeInclusive<u8>,
+    all: u64,
+    name: &str,
+) -> Result<u64, Error> {
+    match value {
+        None => Ok(all),
+        Some(value) if bounds.contains(&value) => Ok(1 << value),
+        Some(value) => Err(Error::CronUnrepresentable(format!(
+            "{} {} lies outside of {:?}",
+            name, value, bounds
+        ))),
+    }
+}
+
+// Merges every group of lines that only differ in `field`, keeping the order of first occurrence.
+fn merge_on(lines: Vec<CronLine>, field: usize) -> Vec<CronLine> {
+    let mut merged: Vec<CronLine> = Vec::with_capacity(lines.len());
+    let mut index: HashMap<[u64; 4], usize> = HashMap::new();
+    for line in lines {
+        match index.get(&line.key(field)) {
+            Some(&i) => merged[i].merge(&line, field),
+            None => {
+                index.insert(line.key(field), merged.len());
+                merged.push(line);
+            }
+        }
+    }
+    merged
+}
+
+// Renders a set of values as "*", "*/step", "first-last/step", or a list of values and ranges.
+fn render_field(bits: u64, min: u8, max: u8, allow_star: bool) -> String {
+    let values: Vec<u8> = (min..=max).filter(|v| bits & (1 << v) != 0).collect();
+    if values.is_empty() || values.len() == usize::from(max - min + 1) {
+        return "*".to_string();
+    }
+
+    let first = values[0];
+    let last = values[values.len() - 1];
+    if values.len() >= 3 {
+        let step = values[1] - first;
+        if step > 1 && values.windows(2).all(|w| w[1] - w[0] == step) {
+            return if allow_star && first == min && last + step > max {
+                format!("*/{}", step)
+            } else {
+                format!("{}-{}/{}", first, last, step)
+            };
+        }
+    }
+
+    let mut result = String::new();
+    let mut start = 0;
+    while start < values.len() {
+        let mut end = start;
+        while end + 1 < values.len() && values[end + 1] == values[end] + 1 {
+            end += 1;
+        }
+        if !result.is_empty() {
+            result.push(',');
+        }
+        match end - start {
+            0 => write!(result, "{}", values[start]),
+            1 => write!(result, "{},{}", values[start], values[end]),
+            _ => write!(result, "{}-{}", values[start], values[end]),
+        }
+        .expect("Writing to a String can't fail");
+        start = end + 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ci(
+        minute: Option<u8>,
+        hour: Option<u8>,
+        day: Option<u8>,
+        weekday: Option<u8>,
+    ) -> CalendarInterval {
+        CalendarInterval {
+            minute,
+            hour,
+            day,
+            weekday,
+            month: None,
+        }
+    }
+
+    #[test]
+    fn every_minute() {
+        let lines = CalendarInterval::to_cron_lines(&[CalendarInterval::default()]).unwrap();
+        assert_eq!(lines, vec!["* * * * *"]);
+    }
+
+    #[test]
+    fn collapse_steps_and_ranges() {
+        let mut intervals = Vec::new();
+        for weekday in 1..=5 {
+            for hour in 9..=17 {
+                for minute in (0..60).step_by(5) {
+                    intervals.push(ci(Some(minute), Some(hour), None, Some(weekday)));
+                }
+            }
+        }
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["*/5 9-17 * * 1-5"]);
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        let intervals = [
+            ci(Some(0), Some(0), None, Some(0)),
+            ci(Some(0), Some(0), None, Some(7)),
+        ];
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["0 0 * * 0"]);
+    }
+
+    #[test]
+    fn day_and_weekday_merge_with_or_semantics() {
+        let intervals = [
+            ci(Some(0), Some(8), Some(15), None),
+            ci(Some(0), Some(8), None, Some(1)),
+        ];
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["0 8 15 * 1"]);
+
+        let intervals = [
+            ci(Some(0), Some(8), Some(15), None),
+            ci(Some(0), Some(8), None, None),
+        ];
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["0 8 * * *"]);
+    }
+
+    #[test]
+    fn day_steps_never_start_with_a_star() {
+        let intervals: Vec<_> = (1..=31)
+            .step_by(2)
+            .map(|day| ci(Some(0), Some(0), Some(day), None))
+            .collect();
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["0 0 1-31/2 * *"]);
+    }
+
+    #[test]
+    fn unmergeable_intervals_stay_separate() {
+        let intervals = [
+            ci(Some(0), Some(9), None, None),
+            ci(Some(30), Some(17), None, None),
+        ];
+        let lines = CalendarInterval::to_cron_lines(&intervals).unwrap();
+        assert_eq!(lines, vec!["0 9 * * *", "30 17 * * *"]);
+    }
+
+    #[test]
+    fn out_of_bounds_values_are_reported() {
+        let intervals = [ci(Some(75), None, None, None)];
+        assert!(matches!(
+            CalendarInterval::to_cron_lines(&intervals),
+            Err(Error::CronUnrepresentable(_))
+        ));
+    }
+}
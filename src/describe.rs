@@ -0,0 +1,415 @@
+// English descriptions of StartInterval and StartCalendarIntervals, such as "every weekday at 09:30".
+use crate::{CalendarInterval, Launchd};
+use std::collections::{BTreeMap, BTreeSet};
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+impl CalendarInterval {
+    /// Describes when launchd fires this interval in English, e.g. "every Monday at 09:30" or
+    /// "at minute 0 of every hour in January".
+    pub fn describe(&self) -> String {
+        Self::describe_all(std::slice::from_ref(self))
+    }
+
+    /// Describes when launchd fires a set of `CalendarInterval`s in English. Intervals that
+    /// only differ in their weekday or time are described together.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut intervals = Vec::new();
+    ///     for weekday in 1..=5 {
+    ///         intervals.push(CalendarInterval::default().with_weekday(weekday)?.with_hour(9)?.with_minute(30)?);
+    ///     }
+    ///     assert_eq!(CalendarInterval::describe_all(&intervals), "every weekday at 09:30");
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn describe_all(intervals: &[CalendarInterval]) -> String {
+        if intervals.is_empty() {
+            return "never".to_string();
+        }
+
+        // Day and Weekday are combined with OR, so intervals that only differ in their weekday
+        // fire on the union of those weekdays. An interval without either fires every day.
+        let mut by_weekday: BTreeMap<_, Option<Weekdays>> = BTreeMap::new();
+        for ci in intervals {
+            let weekday = ci
+                .weekday
+                .map(|weekday| if weekday == 7 { 0 } else { weekday });
+            let weekdays = by_weekday
+                .entry((ci.day, ci.month, ci.hour, ci.minute))
+                .or_insert_with(|| Some(BTreeSet::new()));
+            match (ci.day, weekday) {
+                (None, None) => *weekdays = None,
+                (_, Some(weekday)) => {
+                    if let Some(weekdays) = weekdays {
+                        weekdays.insert(weekday);
+                    }
+                }
+                (Some(_), None) => {}
+            }
+        }
+
+        // Then group the times at which each set of days fires.
+        let mut by_days: Vec<(DaySpec, Vec<Time>)> = Vec::new();
+        for ((day, month, hour, minute), weekdays) in by_weekday {
+            let days = DaySpec::new(day, weekdays.unwrap_or_default(), month);
+            match by_days.iter_mut().find(|(other, _)| *other == days) {
+                Some((_, times)) => times.push((hour, minute)),
+                None => by_days.push((days, vec![(hour, minute)])),
+            }
+        }
+
+        by_days
+            .iter()
+            .flat_map(|(days, times)| describe_times(days, times))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+impl Launchd {
+    /// Describes when launchd starts this job because of its `StartInterval` and
+    /// `StartCalendarInterval`s in English, e.g. "every 300 seconds".
+    /// Returns `None` if neither is set.
+    pub fn describe_schedule(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        match self.start_interval {
+            Some(1) => parts.push("every second".to_string()),
+            Some(seconds) => parts.push(format!("every {} seconds", seconds)),
+            None => {}
+        }
+        match self.start_calendar_intervals.as_deref() {
+            Some(intervals) if !intervals.is_empty() => {
+                parts.push(CalendarInterval::describe_all(intervals))
+            }
+            _ => {}
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("; "))
+        }
+    }
+}
+
+type Weekdays = BTreeSet<u8>;
+// An (Hour, Minute) pair
+type Time = (Option<u8>, Option<u8>);
+
+#[derive(Debug, PartialEq)]
+struct DaySpec {
+    day: Option<u8>,
+    weekdays: Weekdays,
+    month: Option<u8>,
+}
+
+impl DaySpec {
+    fn new(day: Option<u8>, weekdays: Weekdays, month: Option<u8>) -> Self {
+        if weekdays.len() == 7 {
+            // Every day of the week, which makes the day of the month irrelevant.
+            DaySpec {
+                day: None,
+                weekdays: Weekdays::new(),
+                month,
+            }
+        } else {
+            DaySpec {
+                day,
+                weekdays,
+                month,
+            }
+        }
+    }
+
+    // The days as the subject of a description, e.g. "every Monday" in "every Monday at 09:30".
+    fn subject(&self) -> String {
+        let weekdays = match weekday_names(&self.weekdays, false) {
+            Some(names) => format!("every {}", names),
+            None => String::new(),
+        };
+        match self.day {
+            Some(day) if weekdays.is_empty() => format!("on {}", self.date(day)),
+            Some(day) => format!("on {} and {}", self.date(day), weekdays),
+            None if weekdays.is_empty() => format!("every day{}", self.in_month()),
+            None => format!("{}{}", weekdays, self.in_month()),
+        }
+    }
+
+    // The days as a suffix of a description, e.g. " on Mondays" in "every minute on Mondays".
+    fn suffix(&self) -> String {
+        let weekdays = weekday_names(&self.weekdays, true).map(|names| format!("on {}", names));
+        match (self.day, weekdays) {
+            (Some(day), None) => format!(" on {}", self.date(day)),
+            (Some(day), Some(weekdays)) => format!(" on {} and {}", self.date(day), weekdays),
+            (None, Some(weekdays)) => format!(" {}{}", weekdays, self.in_month()),
+            (None, None) => self.in_month(),
+        }
+    }
+
+    fn date(&self, day: u8) -> String {
+        match self.month {
+            Some(month) => format!("{} {}", month_name(month), day),
+            None => format!("day {} of every month", day),
+        }
+    }
+
+    fn in_month(&self) -> String {
+        match self.month {
+            Some(month) => format!(" in {}", month_name(month)),
+            None => String::new(),
+        }
+    }
+}
+
+// Describes a set of days with the times at which they fire. The times are described together
+// if they form a grid of hours and minutes, and separately otherwise.
+fn describe_times(days: &DaySpec, times: &[Time]) -> Vec<String> {
+    let hours: BTreeSet<Option<u8>> = times.iter().map(|(hour, _)| *hour).collect();
+    let minutes: BTreeSet<Option<u8>> = times.iter().map(|(_, minute)| *minute).collect();
+    let is_grid = hours.len() * minutes.len() == times.len()
+        && (hours.len() == 1 || !hours.contains(&None))
+        && (minutes.len() == 1 || !minutes.contains(&None));
+
+    // A few clock times read better than a grid, e.g. "at 09:00 and 17:00".
+    let is_fixed = times
+        .iter()
+        .all(|(hour, minute)| hour.is_some() && minute.is_some());
+    if is_fixed && (!is_grid || times.len() <= 4) {
+        let times: Vec<String> = times
+            .iter()
+            .filter_map(|(hour, minute)| Some(clock((*hour)?, (*minute)?)))
+            .collect();
+        return vec![format!("{} at {}", days.subject(), join(&times))];
+    }
+    if !is_grid {
+        return times
+            .iter()
+            .flat_map(|time| describe_times(days, std::slice::from_ref(time)))
+            .collect();
+    }
+
+    let minutes: Vec<u8> = minutes.into_iter().flatten().collect();
+    let hours: Vec<u8> = hours.into_iter().flatten().collect();
+    let minutes = match minutes.as_slice() {
+        [] => "every minute".to_string(),
+        [minute] => format!("at minute {} of every hour", minute),
+        _ => match step(&minutes) {
+            Some(step) if minutes[0] == 0 && 60 - minutes[minutes.len() - 1] == step => {
+                format!("every {} minutes", step)
+            }
+            _ => {
+                let minutes: Vec<String> = minutes.iter().map(u8::to_string).collect();
+                format!("at minutes {} of every hour", join(&minutes))
+            }
+        },
+    };
+    let hours = match hours.as_slice() {
+        [] => String::new(),
+        [first, .., last] if usize::from(last - first) + 1 == hours.len() => {
+            format!(" from {} to {}", clock(*first, 0), clock(*last, 59))
+        }
+        [hour] => format!(" from {} to {}", clock(*hour, 0), clock(*hour, 59)),
+        _ => {
+            let hours: Vec<String> = hours.iter().map(u8::to_string).collect();
+            format!(" during hours {}", join(&hours))
+        }
+    };
+    vec![format!("{}{}{}", minutes, hours, days.suffix())]
+}
+
+fn weekday_names(weekdays: &Weekdays, plural: bool) -> Option<String> {
+    let suffix = if plural { "s" } else { "" };
+    let weekdays: Vec<u8> = weekdays.iter().copied().collect();
+    match weekdays.as_slice() {
+        [] => None,
+        [1, 2, 3, 4, 5] => Some(format!("weekday{}", suffix)),
+        [0, 6] => Some(format!("weekend day{}", suffix)),
+        _ => {
+            let names: Vec<String> = weekdays
+                .iter()
+                .map(|weekday| match WEEKDAYS.get(usize::from(*weekday)) {
+                    Some(name) => format!("{}{}", name, suffix),
+                    None => format!("weekday {}", weekday),
+                })
+                .collect();
+            Some(join(&names))
+        }
+    }
+}
+
+fn month_name(month: u8) -> String {
+    match MONTHS.get(usize::from(month).wrapping_sub(1)) {
+        Some(name) => name.to_string(),
+        None => format!("month {}", month),
+    }
+}
+
+fn clock(hour: u8, minute: u8) -> String {
+    format!("{:02}:{:02}", hour, minute)
+}
+
+fn step(values: &[u8]) -> Option<u8> {
+    let step = values.get(1)? - values[0];
+    if step > 1 && values.windows(2).all(|w| w[1] - w[0] == step) {
+        Some(step)
+    } else {
+        None
+    }
+}
+
+// Joins words as "a", "a and b", or "a, b and c".
+fn join(words: &[String]) -> String {
+    match words {
+        [] => String::new(),
+        [word] => word.clone(),
+        [init @ .., last] => format!("{} and {}", init.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ci(
+        minute: Option<u8>,
+        hour: Option<u8>,
+        day: Option<u8>,
+        weekday: Option<u8>,
+        month: Option<u8>,
+    ) -> CalendarInterval {
+        CalendarInterval {
+            minute,
+            hour,
+            day,
+            weekday,
+            month,
+        }
+    }
+
+    #[test]
+    fn describe_single_intervals() {
+        let cases = [
+            (ci(None, None, None, None, None), "every minute"),
+            (
+                ci(Some(30), Some(9), None, Some(1), None),
+                "every Monday at 09:30",
+            ),
+            (
+                ci(Some(30), Some(9), None, Some(7), None),
+                "every Sunday at 09:30",
+            ),
+            (
+                ci(Some(0), None, None, None, Some(1)),
+                "at minute 0 of every hour in January",
+            ),
+            (
+                ci(None, Some(9), None, None, None),
+                "every minute from 09:00 to 09:59",
+            ),
+            (
+                ci(Some(0), Some(0), Some(1), None, None),
+                "on day 1 of every month at 00:00",
+            ),
+            (
+                ci(Some(0), Some(0), Some(25), None, Some(12)),
+                "on December 25 at 00:00",
+            ),
+            (
+                ci(Some(0), Some(8), Some(15), Some(5), None),
+                "on day 15 of every month and every Friday at 08:00",
+            ),
+            (
+                ci(Some(5), None, None, Some(6), Some(3)),
+                "at minute 5 of every hour on Saturdays in March",
+            ),
+        ];
+        for (interval, description) in cases.iter() {
+            assert_eq!(interval.describe(), *description);
+        }
+    }
+
+    #[test]
+    fn describe_grouped_intervals() {
+        let twice_a_day = [
+            ci(Some(0), Some(17), None, None, None),
+            ci(Some(0), Some(9), None, None, None),
+        ];
+        assert_eq!(
+            CalendarInterval::describe_all(&twice_a_day),
+            "every day at 09:00 and 17:00"
+        );
+
+        let weekend = [
+            ci(Some(0), Some(10), None, Some(0), None),
+            ci(Some(0), Some(10), None, Some(6), None),
+        ];
+        assert_eq!(
+            CalendarInterval::describe_all(&weekend),
+            "every weekend day at 10:00"
+        );
+
+        let mut office_hours = Vec::new();
+        for weekday in 1..=5 {
+            for hour in 9..=17 {
+                for minute in (0..60).step_by(5) {
+                    office_hours.push(ci(Some(minute), Some(hour), None, Some(weekday), None));
+                }
+            }
+        }
+        assert_eq!(
+            CalendarInterval::describe_all(&office_hours),
+            "every 5 minutes from 09:00 to 17:59 on weekdays"
+        );
+
+        let unrelated = [
+            ci(Some(0), None, None, None, None),
+            ci(Some(30), Some(12), None, None, Some(6)),
+        ];
+        assert_eq!(
+            CalendarInterval::describe_all(&unrelated),
+            "at minute 0 of every hour; every day in June at 12:30"
+        );
+        assert_eq!(CalendarInterval::describe_all(&[]), "never");
+    }
+
+    #[test]
+    fn describe_launchd_schedule() {
+        let launchd = Launchd::new("LABEL", "/bin/true").unwrap();
+        assert_eq!(launchd.describe_schedule(), None);
+
+        let launchd = launchd
+            .with_start_interval(300)
+            .with_start_calendar_intervals(vec![ci(Some(0), Some(3), None, None, None)]);
+        assert_eq!(
+            launchd.describe_schedule().as_deref(),
+            Some("every 300 seconds; every day at 03:00")
+        );
+    }
+}
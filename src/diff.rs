@@ -0,0 +1,377 @@
+// Structural diff between two jobs, for reviewing a change to a job without reading an XML diff.
+//
+// Jobs are compared as plists, so the diff covers typed fields, LaunchEvents and unknown keys
+// alike. Two keys have more than one spelling for the same job, and are normalized first: the
+// array form of Sockets is merged into one dictionary of sockets by name, and the
+// StartCalendarIntervals are compared as sets, as their order doesn't matter to launchd.
+use crate::value::format_date;
+use crate::{Error, KeyPath, Launchd, Value};
+use plist::Dictionary;
+use std::fmt;
+
+/// A single difference between two jobs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(KeyPath, Value),
+    Removed(KeyPath, Value),
+    Changed(KeyPath, Value, Value),
+    /// An element added to an array that is compared as a set, such as `StartCalendarInterval`.
+    /// The path is that of the array, as the element has no fixed position.
+    AddedToSet(KeyPath, Value),
+    /// An element removed from an array that is compared as a set.
+    RemovedFromSet(KeyPath, Value),
+}
+
+impl Change {
+    pub fn path(&self) -> &KeyPath {
+        match self {
+            Change::Added(path, _)
+            | Change::Removed(path, _)
+            | Change::Changed(path, _, _)
+            | Change::AddedToSet(path, _)
+            | Change::RemovedFromSet(path, _) => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(path, value) => write!(f, "{} added: {}", path, Rendered(value)),
+            Change::Removed(path, _) => write!(f, "{} removed", path),
+            Change::Changed(path, old, new) => {
+                write!(f, "{} {} → {}", path, Rendered(old), Rendered(new))
+            }
+            Change::AddedToSet(path, value) => write!(f, "{} {} added", path, Rendered(value)),
+            Change::RemovedFromSet(path, value) => {
+                write!(f, "{} {} removed", path, Rendered(value))
+            }
+        }
+    }
+}
+
+/// The differences between two jobs, sorted by key path.
+///
+/// It is displayed on a single line, with the changes separated by semicolons, or with one change
+/// per line in the alternate form (`{:#}`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl IntoIterator for Diff {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if f.alternate() { "\n" } else { "; " };
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(separator)?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the changes that turn `old` into `new`.
+///
+/// Usage:
+/// ```
+/// use launchd::{Error, Launchd};
+/// use std::collections::HashMap;
+///
+/// fn example() -> Result<(), Error> {
+///     let env = |name: &str| HashMap::from([(name.to_string(), "1".to_string())]);
+///     let old = Launchd::new("LABEL", "/bin/true")?
+///         .with_start_interval(300)
+///         .with_environment_variables(env("FOO"));
+///     let new = Launchd::new("LABEL", "/bin/true")?
+///         .with_start_interval(600)
+///         .with_environment_variables(env("BAR"));
+///
+///     let diff = launchd::diff(&old, &new)?;
+///     assert_eq!(
+///         diff.to_string(),
+///         "EnvironmentVariables.BAR added: \"1\"; EnvironmentVariables.FOO removed; StartInterval 300 → 600"
+///     );
+///     Ok(())
+/// }
+/// example().unwrap();
+/// ```
+pub fn diff(old: &Launchd, new: &Launchd) -> Result<Diff, Error> {
+    let (old, new) = (
+        normalize(old.to_plist_value()?),
+        normalize(new.to_plist_value()?),
+    );
+    let mut changes = Vec::new();
+    for key in union(&old, &new) {
+        let path = KeyPath::new().key(&key);
+        match (old.get(&key), new.get(&key)) {
+            (Some(plist::Value::Array(old)), Some(plist::Value::Array(new)))
+                if key == "StartCalendarInterval" =>
+            {
+                diff_sets(&path, old, new, &mut changes)
+            }
+            (old, new) => diff_options(path, old, new, &mut changes),
+        }
+    }
+    changes.sort_by(|left, right| left.path().cmp(right.path()));
+    Ok(Diff { changes })
+}
+
+impl Launchd {
+    /// Returns the changes that turn this job into `new`, see [`diff`](crate::diff()).
+    pub fn diff(&self, new: &Launchd) -> Result<Diff, Error> {
+        diff(self, new)
+    }
+}
+
+// Merges the array form of Sockets, and wraps a single StartCalendarInterval in an array.
+fn normalize(value: plist::Value) -> Dictionary {
+    let mut dictionary = match value {
+        plist::Value::Dictionary(dictionary) => dictionary,
+        _ => unreachable!("Launchd always serializes to a dictionary"),
+    };
+    if let Some(plist::Value::Array(array)) = dictionary.get("Sockets") {
+        let mut sockets = Dictionary::new();
+        for socket in array.iter().filter_map(plist::Value::as_dictionary) {
+            for (name, options) in socket {
+                sockets.insert(name.clone(), options.clone());
+            }
+        }
+        dictionary.insert("Sockets".to_string(), sockets.into());
+    }
+    if let Some(interval @ plist::Value::Dictionary(_)) = dictionary.get("StartCalendarInterval") {
+        let intervals = vec![interval.clone()];
+        dictionary.insert("StartCalendarInterval".to_string(), intervals.into());
+    }
+    dictionary
+}
+
+// The keys of both dictionaries, those of `old` first.
+fn union(old: &Dictionary, new: &Dictionary) -> Vec<String> {
+    old.keys()
+        .chain(new.keys().filter(|key| !old.contains_key(key)))
+        .cloned()
+        .collect()
+}
+
+fn diff_options(
+    path: KeyPath,
+    old: Option<&plist::Value>,
+    new: Option<&plist::Value>,
+    changes: &mut Vec<Change>,
+) {
+    match (old, new) {
+        (Some(old), Some(new)) => diff_values(path, old, new, changes),
+        (Some(old), None) => changes.push(Change::Removed(path, old.clone().into())),
+        (None, Some(new)) => changes.push(Change::Added(path, new.clone().into())),
+        (None, None) => {}
+    }
+}
+
+fn diff_values(path: KeyPath, old: &plist::Value, new: &plist::Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (plist::Value::Dictionary(old), plist::Value::Dictionary(new)) => {
+            for key in union(old, new) {
+                let path = path.clone().key(&key);
+                diff_options(path, old.get(&key), new.get(&key), changes);
+            }
+        }
+        (plist::Value::Array(old), plist::Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let path = path.clone().index(index);
+                diff_options(path, old.get(index), new.get(index), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed(
+            path,
+            old.clone().into(),
+            new.clone().into(),
+        )),
+        _ => {}
+    }
+}
+
+// Compares arrays whose order doesn't matter: an element is removed if `new` doesn't contain it,
+// and added if `old` doesn't. The changes refer to the array rather than to an index, as the index
+// of an element differs between `old` and `new`.
+fn diff_sets(
+    path: &KeyPath,
+    old: &[plist::Value],
+    new: &[plist::Value],
+    changes: &mut Vec<Change>,
+) {
+    for value in old.iter().filter(|value| !new.contains(value)) {
+        changes.push(Change::RemovedFromSet(path.clone(), value.clone().into()));
+    }
+    for value in new.iter().filter(|value| !old.contains(value)) {
+        changes.push(Change::AddedToSet(path.clone(), value.clone().into()));
+    }
+}
+
+// A compact, single-line rendering of a value.
+struct Rendered<'a>(&'a Value);
+
+impl fmt::Display for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::String(string) => write!(f, "{:?}", string),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Real(real) => write!(f, "{}", real),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::Date(date) => f.write_str(&format_date(*date)),
+            Value::Data(data) => write!(f, "<{} bytes>", data.len()),
+            Value::Array(array) => {
+                f.write_str("[")?;
+                for (i, value) in array.iter().enumerate() {
+                    let separator = if i > 0 { ", " } else { "" };
+                    write!(f, "{}{}", separator, Rendered(value))?;
+                }
+                f.write_str("]")
+            }
+            Value::Dictionary(dictionary) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in dictionary.iter().enumerate() {
+                    let separator = if i > 0 { ", " } else { "" };
+                    write!(f, "{}{}: {}", separator, key, Rendered(value))?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_case;
+    use crate::{
+        CalendarInterval, KeepAliveOptions, KeepAliveType, Socket, SocketOptions, Sockets,
+    };
+    use std::collections::HashMap;
+
+    fn paths(diff: &Diff) -> Vec<String> {
+        diff.changes()
+            .iter()
+            .map(|change| change.path().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn identical_jobs_have_no_changes() {
+        for file in crate::tests::corpus() {
+            let old = Launchd::from_file(&file).unwrap();
+            let new = Launchd::from_file(&file)
+                .unwrap()
+                .with_key_order(crate::KeyOrder::Sorted);
+            assert!(diff(&old, &new).unwrap().is_empty(), "{:?}", file);
+        }
+    }
+
+    #[test]
+    fn nested_keys_are_reported_by_path() {
+        let old = Launchd::from_file(test_case!("keepalive-1.plist")).unwrap();
+        let mut new = Launchd::from_file(test_case!("keepalive-1.plist"))
+            .unwrap()
+            .with_unknown_key("AssociatedBundleIdentifiers", "com.example".into())
+            .unwrap();
+        new.remove_environment_variable("LANG");
+        new.insert_environment_variable("DEBUG", "1");
+        new.set_keep_alive(KeepAliveType::Options(
+            KeepAliveOptions::new()
+                .with_successful_exit(true)
+                .with_path_state(HashMap::from([(
+                    "/var/run/agent.enabled".to_string(),
+                    true,
+                )])),
+        ));
+
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(
+            format!("{:#}", diff),
+            [
+                "AssociatedBundleIdentifiers added: \"com.example\"",
+                "EnvironmentVariables.DEBUG added: \"1\"",
+                "EnvironmentVariables.LANG removed",
+                "KeepAlive.PathState[\"/var/run/agent.disabled\"] removed",
+                "KeepAlive.SuccessfulExit false → true",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn calendar_intervals_are_compared_as_sets() {
+        let at = |hour| CalendarInterval::default().with_hour(hour).unwrap();
+        let old = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(vec![at(1), at(2), at(3)]);
+        let new = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(vec![at(3), at(4), at(1)]);
+
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(
+            diff.to_string(),
+            "StartCalendarInterval {Hour: 2} removed; StartCalendarInterval {Hour: 4} added"
+        );
+    }
+
+    #[test]
+    fn sockets_are_compared_by_name() {
+        let listeners = Socket::new("Listeners", SocketOptions::new().with_service_name("ssh"));
+        let old = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_socket(listeners.into())
+            .with_socket(Socket::new("Control", SocketOptions::new()).into());
+        let new = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_socket(Sockets::Dictionary(HashMap::from([
+                ("Control".to_string(), SocketOptions::new()),
+                (
+                    "Listeners".to_string(),
+                    SocketOptions::new().with_service_name("2222"),
+                ),
+            ])));
+
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(paths(&diff), vec!["Sockets.Listeners.SockServiceName"]);
+    }
+
+    #[test]
+    fn launch_events_are_compared_by_stream_and_event() {
+        let old = Launchd::from_file(test_case!("launchevents-1.plist")).unwrap();
+        let mut new = Launchd::from_file(test_case!("launchevents-1.plist")).unwrap();
+        assert!(new.remove_launch_event(
+            "com.apple.notifyd.matching",
+            "com.apple.phone.idslaunchnotification"
+        ));
+
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(
+            paths(&diff),
+            vec![
+                r#"LaunchEvents["com.apple.notifyd.matching"]["com.apple.phone.idslaunchnotification"]"#
+            ]
+        );
+    }
+}
@@ -0,0 +1,444 @@
+// In-place editing of a job, e.g. for migrating a plist that was read from disk.
+//
+// Every optional key has a `set_*` setter, a `clear_*` method that removes the key, and a `take_*`
+// method that removes the key and returns its value. This includes the deprecated keys, which have
+// no `with_*` builder. The dictionaries that are usually edited key by key can be edited through
+// their HashMaps as well.
+use crate::{
+    CalendarInterval, Error, InetdCompatibility, KeepAliveType, LaunchEvents, Launchd,
+    LoadSessionType, MachServiceEntry, ProcessType, ResourceLimits, SocketOptions, Sockets,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn path_to_string<P: AsRef<Path>>(path: P) -> Result<String, Error> {
+    Ok(path
+        .as_ref()
+        .to_str()
+        .ok_or(Error::PathConversion)?
+        .to_owned())
+}
+
+// Generates the `set_*` (optional), `clear_*` and `take_*` methods of an optional field.
+macro_rules! optional_fields {
+    ($($field:ident: $type:ty => $(set $set:ident,)? clear $clear:ident, take $take:ident;)*) => {
+        impl Launchd {
+            $(
+                $(
+                    pub fn $set(&mut self, value: $type) -> &mut Self {
+                        self.$field = Some(value);
+                        self
+                    }
+                )?
+
+                pub fn $clear(&mut self) -> &mut Self {
+                    self.$field = None;
+                    self
+                }
+
+                pub fn $take(&mut self) -> Option<$type> {
+                    self.$field.take()
+                }
+            )*
+        }
+    };
+}
+
+optional_fields! {
+    disabled: bool => set set_disabled, clear clear_disabled, take take_disabled;
+    user_name: String => clear clear_user_name, take take_user_name;
+    group_name: String => clear clear_group_name, take take_group_name;
+    limit_load_to_hosts: Vec<String> =>
+        set set_limit_load_to_hosts, clear clear_limit_load_to_hosts, take take_limit_load_to_hosts;
+    limit_load_from_hosts: Vec<String> =>
+        set set_limit_load_from_hosts,
+        clear clear_limit_load_from_hosts,
+        take take_limit_load_from_hosts;
+    limit_load_to_session_type: LoadSessionType =>
+        set set_limit_load_to_session_type,
+        clear clear_limit_load_to_session_type,
+        take take_limit_load_to_session_type;
+    limit_load_to_hardware: HashMap<String, Vec<String>> =>
+        set set_limit_load_to_hardware,
+        clear clear_limit_load_to_hardware,
+        take take_limit_load_to_hardware;
+    limit_load_from_hardware: HashMap<String, Vec<String>> =>
+        set set_limit_load_from_hardware,
+        clear clear_limit_load_from_hardware,
+        take take_limit_load_from_hardware;
+    program: String => clear clear_program, take take_program;
+    bundle_program: String => clear clear_bundle_program, take take_bundle_program;
+    program_arguments: Vec<String> =>
+        set set_program_arguments, clear clear_program_arguments, take take_program_arguments;
+    enable_globbing: bool =>
+        set set_enable_globbing, clear clear_enable_globbing, take take_enable_globbing;
+    enable_transactions: bool =>
+        set set_enable_transactions, clear clear_enable_transactions, take take_enable_transactions;
+    enable_pressured_exit: bool =>
+        set set_enable_pressured_exit,
+        clear clear_enable_pressured_exit,
+        take take_enable_pressured_exit;
+    on_demand: bool => set set_on_demand, clear clear_on_demand, take take_on_demand;
+    service_ipc: bool => set set_service_ipc, clear clear_service_ipc, take take_service_ipc;
+    keep_alive: KeepAliveType => set set_keep_alive, clear clear_keep_alive, take take_keep_alive;
+    run_at_load: bool => set set_run_at_load, clear clear_run_at_load, take take_run_at_load;
+    root_directory: String => clear clear_root_directory, take take_root_directory;
+    working_directory: String => clear clear_working_directory, take take_working_directory;
+    environment_variables: HashMap<String, String> =>
+        set set_environment_variables,
+        clear clear_environment_variables,
+        take take_environment_variables;
+    umask: u16 => set set_umask, clear clear_umask, take take_umask;
+    time_out: u32 => set set_timeout, clear clear_timeout, take take_timeout;
+    exit_time_out: u32 => set set_exit_timeout, clear clear_exit_timeout, take take_exit_timeout;
+    throttle_interval: u32 =>
+        set set_throttle_interval, clear clear_throttle_interval, take take_throttle_interval;
+    init_groups: bool => set set_init_groups, clear clear_init_groups, take take_init_groups;
+    watch_paths: Vec<String> => set set_watch_paths, clear clear_watch_paths, take take_watch_paths;
+    queue_directories: Vec<String> =>
+        set set_queue_directories, clear clear_queue_directories, take take_queue_directories;
+    start_on_mount: bool =>
+        set set_start_on_mount, clear clear_start_on_mount, take take_start_on_mount;
+    start_interval: u32 =>
+        set set_start_interval, clear clear_start_interval, take take_start_interval;
+    start_calendar_intervals: Vec<CalendarInterval> =>
+        set set_start_calendar_intervals,
+        clear clear_start_calendar_intervals,
+        take take_start_calendar_intervals;
+    standard_in_path: String => clear clear_standard_in_path, take take_standard_in_path;
+    standard_out_path: String => clear clear_standard_out_path, take take_standard_out_path;
+    standard_error_path: String => clear clear_standard_error_path, take take_standard_error_path;
+    debug: bool => set set_debug, clear clear_debug, take take_debug;
+    wait_for_debugger: bool =>
+        set set_wait_for_debugger, clear clear_wait_for_debugger, take take_wait_for_debugger;
+    soft_resource_limits: ResourceLimits =>
+        set set_soft_resource_limits,
+        clear clear_soft_resource_limits,
+        take take_soft_resource_limits;
+    hard_resource_limits: ResourceLimits =>
+        set set_hard_resource_limits,
+        clear clear_hard_resource_limits,
+        take take_hard_resource_limits;
+    nice: i32 => set set_nice, clear clear_nice, take take_nice;
+    process_type: ProcessType =>
+        set set_process_type, clear clear_process_type, take take_process_type;
+    abandon_process_group: bool =>
+        set set_abandon_process_group,
+        clear clear_abandon_process_group,
+        take take_abandon_process_group;
+    low_priority_io: bool =>
+        set set_low_priority_io, clear clear_low_priority_io, take take_low_priority_io;
+    low_priority_background_io: bool =>
+        set set_low_priority_background_io,
+        clear clear_low_priority_background_io,
+        take take_low_priority_background_io;
+    materialize_dataless_files: bool =>
+        set set_materialize_dataless_files,
+        clear clear_materialize_dataless_files,
+        take take_materialize_dataless_files;
+    launch_only_once: bool =>
+        set set_launch_only_once, clear clear_launch_only_once, take take_launch_only_once;
+    mach_services: HashMap<String, MachServiceEntry> =>
+        set set_mach_services, clear clear_mach_services, take take_mach_services;
+    sockets: Sockets => set set_sockets, clear clear_sockets, take take_sockets;
+    launch_events: LaunchEvents =>
+        set set_launch_events, clear clear_launch_events, take take_launch_events;
+    hopefully_exits_last: bool =>
+        set set_hopefully_exits_last,
+        clear clear_hopefully_exits_last,
+        take take_hopefully_exits_last;
+    hopefully_exits_first: bool =>
+        set set_hopefully_exits_first,
+        clear clear_hopefully_exits_first,
+        take take_hopefully_exits_first;
+    session_create: bool =>
+        set set_session_create, clear clear_session_create, take take_session_create;
+    legacy_timers: bool =>
+        set set_legacy_timers, clear clear_legacy_timers, take take_legacy_timers;
+}
+
+// Setters that convert their argument like the matching `with_*` builder.
+impl Launchd {
+    pub fn set_label<S: AsRef<str>>(&mut self, label: S) -> &mut Self {
+        self.label = label.as_ref().to_string();
+        self
+    }
+
+    pub fn set_user_name<S: AsRef<str>>(&mut self, user_name: S) -> &mut Self {
+        self.user_name = Some(user_name.as_ref().to_string());
+        self
+    }
+
+    pub fn set_group_name<S: AsRef<str>>(&mut self, group_name: S) -> &mut Self {
+        self.group_name = Some(group_name.as_ref().to_string());
+        self
+    }
+
+    pub fn set_inetd_compatibility(&mut self, wait: bool) -> &mut Self {
+        self.inetd_compatibility = Some(HashMap::from([(InetdCompatibility::Wait, wait)]));
+        self
+    }
+
+    pub fn clear_inetd_compatibility(&mut self) -> &mut Self {
+        self.inetd_compatibility = None;
+        self
+    }
+
+    /// Removes `inetdCompatibility` and returns its `Wait` flag.
+    pub fn take_inetd_compatibility(&mut self) -> Option<bool> {
+        self.inetd_compatibility
+            .take()
+            .and_then(|inetd| inetd.get(&InetdCompatibility::Wait).copied())
+    }
+
+    pub fn set_program<P: AsRef<Path>>(&mut self, program: P) -> Result<&mut Self, Error> {
+        self.program = Some(path_to_string(program)?);
+        Ok(self)
+    }
+
+    pub fn set_bundle_program<S: AsRef<str>>(&mut self, bundle: S) -> &mut Self {
+        self.bundle_program = Some(bundle.as_ref().to_string());
+        self
+    }
+
+    pub fn set_root_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        self.root_directory = Some(path_to_string(path)?);
+        Ok(self)
+    }
+
+    pub fn set_working_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        self.working_directory = Some(path_to_string(path)?);
+        Ok(self)
+    }
+
+    pub fn set_standard_in_path<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        self.standard_in_path = Some(path_to_string(path)?);
+        Ok(self)
+    }
+
+    pub fn set_standard_out_path<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        self.standard_out_path = Some(path_to_string(path)?);
+        Ok(self)
+    }
+
+    pub fn set_standard_error_path<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        self.standard_error_path = Some(path_to_string(path)?);
+        Ok(self)
+    }
+}
+
+// Editing the dictionaries key by key. The `*_mut` methods add an empty dictionary if the key isn't
+// set, which is written as an empty dictionary; the `remove_*` methods remove the key once its
+// dictionary is empty.
+impl Launchd {
+    /// Returns `EnvironmentVariables` for editing, adding it if it isn't set.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut launchd = Launchd::new("LABEL", "/bin/true")?;
+    ///     launchd
+    ///         .environment_variables_mut()
+    ///         .entry("PATH".to_string())
+    ///         .or_insert_with(|| "/usr/bin:/bin".to_string())
+    ///         .push_str(":/usr/local/bin");
+    ///
+    ///     assert_eq!(
+    ///         launchd.environment_variables().unwrap()["PATH"],
+    ///         "/usr/bin:/bin:/usr/local/bin"
+    ///     );
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn environment_variables_mut(&mut self) -> &mut HashMap<String, String> {
+        self.environment_variables.get_or_insert_with(HashMap::new)
+    }
+
+    pub fn insert_environment_variable<K: AsRef<str>, V: AsRef<str>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> Option<String> {
+        self.environment_variables_mut()
+            .insert(name.as_ref().to_string(), value.as_ref().to_string())
+    }
+
+    pub fn remove_environment_variable<K: AsRef<str>>(&mut self, name: K) -> Option<String> {
+        let variables = self.environment_variables.as_mut()?;
+        let removed = variables.remove(name.as_ref());
+        if variables.is_empty() {
+            self.environment_variables = None;
+        }
+        removed
+    }
+
+    /// Returns `MachServices` for editing, adding it if it isn't set.
+    pub fn mach_services_mut(&mut self) -> &mut HashMap<String, MachServiceEntry> {
+        self.mach_services.get_or_insert_with(HashMap::new)
+    }
+
+    pub fn insert_mach_service<S: AsRef<str>, E: Into<MachServiceEntry>>(
+        &mut self,
+        name: S,
+        entry: E,
+    ) -> Option<MachServiceEntry> {
+        self.mach_services_mut()
+            .insert(name.as_ref().to_string(), entry.into())
+    }
+
+    pub fn remove_mach_service<S: AsRef<str>>(&mut self, name: S) -> Option<MachServiceEntry> {
+        let services = self.mach_services.as_mut()?;
+        let removed = services.remove(name.as_ref());
+        if services.is_empty() {
+            self.mach_services = None;
+        }
+        removed
+    }
+
+    /// Returns `Sockets` for editing, adding an empty dictionary if it isn't set.
+    pub fn sockets_mut(&mut self) -> &mut Sockets {
+        self.sockets
+            .get_or_insert_with(|| Sockets::Dictionary(HashMap::new()))
+    }
+
+    pub fn remove_socket<S: AsRef<str>>(&mut self, name: S) -> Option<SocketOptions> {
+        let sockets = self.sockets.as_mut()?;
+        let removed = sockets.remove(name);
+        if sockets.is_empty() {
+            self.sockets = None;
+        }
+        removed
+    }
+
+    /// Returns `LaunchEvents` for editing, adding it if it isn't set.
+    pub fn launch_events_mut(&mut self) -> &mut LaunchEvents {
+        self.launch_events.get_or_insert_with(LaunchEvents::default)
+    }
+
+    pub fn remove_launch_event<S: AsRef<str>, E: AsRef<str>>(
+        &mut self,
+        stream: S,
+        event: E,
+    ) -> bool {
+        let events = match self.launch_events.as_mut() {
+            Some(events) => events,
+            None => return false,
+        };
+        let removed = events.remove_event(stream, event);
+        if events.is_empty() {
+            self.launch_events = None;
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "io")]
+    use crate::tests::test_case;
+    use crate::{MachServiceOptions, NotifydMatching};
+
+    #[test]
+    fn setters_clear_and_take_edit_in_place() {
+        let mut launchd = Launchd::new("LABEL", "/bin/true").unwrap();
+        launchd.set_on_demand(false);
+        if launchd.take_on_demand() == Some(false) {
+            launchd.set_keep_alive(KeepAliveType::Enabled(true));
+        }
+        launchd
+            .set_label("com.example.migrated")
+            .set_throttle_interval(30)
+            .clear_program()
+            .set_program_arguments(vec!["/bin/sleep".to_string(), "60".to_string()]);
+        launchd.set_standard_out_path("/tmp/out.log").unwrap();
+
+        let expected = Launchd::new("com.example.migrated", "/bin/true")
+            .unwrap()
+            .with_keep_alive(KeepAliveType::Enabled(true))
+            .with_throttle_interval(30)
+            .with_program_arguments(vec!["/bin/sleep".to_string(), "60".to_string()])
+            .with_standard_out_path("/tmp/out.log")
+            .unwrap();
+        assert_eq!(launchd.take_program(), None);
+        assert_eq!(
+            launchd,
+            Launchd {
+                program: None,
+                ..expected
+            }
+        );
+        assert_eq!(launchd.take_throttle_interval(), Some(30));
+        assert_eq!(launchd.throttle_interval(), None);
+    }
+
+    #[test]
+    fn removing_the_last_entry_removes_the_key() {
+        let mut launchd = Launchd::new("LABEL", "/bin/true").unwrap();
+        assert_eq!(launchd.insert_environment_variable("LANG", "C"), None);
+        assert_eq!(
+            launchd.insert_environment_variable("LANG", "en_US.UTF-8"),
+            Some("C".to_string())
+        );
+        assert_eq!(
+            launchd.remove_environment_variable("LANG"),
+            Some("en_US.UTF-8".to_string())
+        );
+        assert_eq!(launchd.environment_variables(), None);
+
+        launchd.insert_mach_service(
+            "com.example.xpc",
+            MachServiceOptions::new().reset_at_close(),
+        );
+        launchd.insert_mach_service("com.example.wake", true);
+        assert_eq!(
+            launchd.remove_mach_service("com.example.wake"),
+            Some(MachServiceEntry::Boolean(true))
+        );
+        assert!(launchd.remove_mach_service("com.example.xpc").is_some());
+        assert_eq!(launchd.mach_services(), None);
+
+        launchd
+            .sockets_mut()
+            .insert("Listeners", SocketOptions::new().passive());
+        assert!(launchd.remove_socket("Listeners").is_some());
+        assert_eq!(launchd.sockets(), None);
+
+        launchd.launch_events_mut().notifyd_matching_mut().insert(
+            "timezone".to_string(),
+            NotifydMatching::new("com.apple.system.timezone"),
+        );
+        assert!(!launchd.remove_launch_event("com.apple.iokit.matching", "timezone"));
+        assert!(launchd.remove_launch_event("com.apple.notifyd.matching", "timezone"));
+        assert_eq!(launchd.launch_events(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn a_parsed_plist_can_be_migrated() {
+        let mut launchd = Launchd::from_file(test_case!("sockets-1.plist")).unwrap();
+        let listeners = launchd.remove_socket("Listeners").unwrap();
+        launchd
+            .sockets_mut()
+            .insert("Listeners", listeners.with_service_name("2222"));
+        launchd.clear_inetd_compatibility().set_run_at_load(true);
+
+        let mut buffer = Vec::new();
+        launchd.to_writer_xml(&mut buffer).unwrap();
+        let migrated = Launchd::from_bytes(&buffer).unwrap();
+        assert_eq!(migrated, launchd);
+        assert_eq!(migrated.inetd_compatibility(), None);
+        assert_eq!(
+            migrated
+                .sockets()
+                .unwrap()
+                .get("Listeners")
+                .unwrap()
+                .service_name(),
+            Some("2222")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "io")]
+use crate::KeyPath;
 use std::fmt;
 use std::ops::RangeInclusive;
 use thiserror::Error;
@@ -10,9 +12,41 @@ pub enum Error {
     // TODO: Show path. Is this really needed (invalid paths are not rejected)
     PathConversion,
 
+    #[error("The calendar intervals can't be expressed as a crontab: {0}")]
+    CronUnrepresentable(String),
+
     #[cfg(feature = "cron")]
     #[error("The crontab generated an invalid value: {0}")]
     InvalidCronField(u32),
+    #[cfg(feature = "cron")]
+    #[error("The cron schedule can't be expressed in launchd: {0}")]
+    UnsupportedCronSchedule(String),
+
+    #[error("The OnCalendar expression is invalid: {0}")]
+    InvalidOnCalendar(String),
+    #[error("The OnCalendar expression can't be expressed in launchd: {0}")]
+    UnsupportedOnCalendar(String),
+    #[error("The job can't be converted to systemd units: {0}")]
+    SystemdConversion(String),
+    #[error("The systemd unit file is invalid: {0}")]
+    InvalidUnitFile(String),
+
+    #[error("The plist contains unknown keys: {}", .0.join(", "))]
+    UnknownKeys(Vec<String>),
+    #[error("{0} is modelled by Launchd, so it can't be an unknown key")]
+    KnownKey(String),
+
+    #[error("The key path is invalid: {0:?}")]
+    InvalidKeyPath(String),
+    #[cfg(feature = "io")]
+    #[error("There is no value at {0}")]
+    KeyPathNotFound(KeyPath),
+    #[cfg(feature = "io")]
+    #[error("The value at {0} is not a dictionary or an array")]
+    NotAContainer(KeyPath),
+    #[cfg(feature = "io")]
+    #[error("The value at {0} is invalid: {1}")]
+    InvalidValueAt(KeyPath, String),
 
     #[cfg(feature = "io")]
     #[error(transparent)]
@@ -20,6 +54,34 @@ pub enum Error {
     #[cfg(feature = "io")]
     #[error(transparent)]
     Write(plist::Error),
+    #[cfg(feature = "io")]
+    #[error(transparent)]
+    Io(std::io::Error),
+    #[cfg(feature = "io")]
+    #[error("The XML plist is invalid: {0}")]
+    InvalidXml(String),
+    #[cfg(feature = "io")]
+    #[error("The binary plist is invalid: {0}")]
+    InvalidBinary(String),
+    #[cfg(feature = "io")]
+    #[error("The value at {0} has no canonical XML representation")]
+    UnsupportedValue(KeyPath),
+    #[cfg(feature = "io")]
+    #[error("Parsing the plist panicked: {0}")]
+    ParserPanicked(String),
+
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    Json(serde_json::Error),
+    #[cfg(feature = "yaml")]
+    #[error(transparent)]
+    Yaml(serde_yaml::Error),
+    #[cfg(feature = "toml")]
+    #[error(transparent)]
+    TomlRead(toml::de::Error),
+    #[cfg(feature = "toml")]
+    #[error(transparent)]
+    TomlWrite(toml::ser::Error),
 }
 
 // Errors for deserializing Strings into enums that have invalid values.
@@ -0,0 +1,137 @@
+// JSON, YAML and TOML front-ends, for writing jobs by hand and converting them to plists.
+//
+// They use the serde implementations of Launchd, so the keys are the same as in a plist, and
+// unknown keys are kept. The keys are written in the declared order. Dates and data are written as
+// tagged dictionaries, see Value.
+use crate::{Error, Launchd};
+
+#[cfg(feature = "json")]
+impl Launchd {
+    /// Reads a job from JSON, with the same keys as a plist.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::Launchd;
+    ///
+    /// let json = r#"{"Label": "LABEL", "ProgramArguments": ["/bin/sleep", "60"], "RunAtLoad": true}"#;
+    /// let launchd = Launchd::from_json(json).unwrap();
+    /// assert!(launchd.to_json().unwrap().contains(r#""RunAtLoad": true"#));
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(Error::Json)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(Error::Json)
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl Launchd {
+    /// Reads a job from YAML, with the same keys as a plist.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::Launchd;
+    ///
+    /// let yaml = "
+    /// Label: com.example.backup
+    /// ProgramArguments: [/usr/local/bin/backup, --quiet]
+    /// StartCalendarInterval:
+    ///   - Hour: 3
+    ///     Minute: 15
+    /// ";
+    /// let launchd = Launchd::from_yaml(yaml).unwrap();
+    /// assert!(launchd.to_yaml().unwrap().starts_with("Label: com.example.backup\n"));
+    /// ```
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        serde_yaml::from_str(yaml).map_err(Error::Yaml)
+    }
+
+    pub fn to_yaml(&self) -> Result<String, Error> {
+        serde_yaml::to_string(self).map_err(Error::Yaml)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Launchd {
+    /// Reads a job from TOML, with the same keys as a plist.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::Launchd;
+    ///
+    /// let toml = r#"
+    /// Label = "com.example.backup"
+    /// ProgramArguments = ["/usr/local/bin/backup", "--quiet"]
+    ///
+    /// [EnvironmentVariables]
+    /// BACKUP_TARGET = "/Volumes/Backup"
+    /// "#;
+    /// let launchd = Launchd::from_toml(toml).unwrap();
+    /// assert!(launchd.to_toml().unwrap().contains("[EnvironmentVariables]"));
+    /// ```
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        toml::from_str(toml).map_err(Error::TomlRead)
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string(self).map_err(Error::TomlWrite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::corpus;
+    use crate::tests::test_case;
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_round_trip_on_the_corpus() {
+        for file in corpus() {
+            let launchd = Launchd::from_file(&file).unwrap();
+            let json = launchd.to_json().unwrap();
+            assert_eq!(Launchd::from_json(&json).unwrap(), launchd, "{:?}", file);
+        }
+
+        let json = Launchd::from_file(test_case!("unknown-keys.plist"))
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert!(json.contains(r#""$date": "2021-06-01T12:00:00Z""#));
+        assert!(json.contains(r#""$data": "3q2+7w==""#));
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn yaml_round_trip_on_the_corpus() {
+        for file in corpus() {
+            let launchd = Launchd::from_file(&file).unwrap();
+            let yaml = launchd.to_yaml().unwrap();
+            assert_eq!(Launchd::from_yaml(&yaml).unwrap(), launchd, "{:?}", file);
+        }
+
+        let yaml = std::fs::read_to_string(test_case!("keepalive-1.yaml")).unwrap();
+        assert_eq!(
+            Launchd::from_yaml(&yaml).unwrap(),
+            Launchd::from_file(test_case!("keepalive-1.plist")).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn toml_round_trip_on_the_corpus() {
+        for file in corpus() {
+            let launchd = Launchd::from_file(&file).unwrap();
+            let toml = launchd.to_toml().unwrap();
+            assert_eq!(Launchd::from_toml(&toml).unwrap(), launchd, "{:?}", file);
+        }
+
+        let toml = Launchd::from_file(test_case!("unknown-keys.plist"))
+            .unwrap()
+            .to_toml()
+            .unwrap();
+        assert!(toml.contains("[_AdditionalProperties.Fingerprint]\n\"$data\" = \"3q2+7w==\"\n"));
+    }
+}
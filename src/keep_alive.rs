@@ -28,10 +28,12 @@ impl From<KeepAliveOptions> for KeepAliveType {
 #[cfg_attr(feature = "io", serde(rename_all = "PascalCase"))]
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct KeepAliveOptions {
-    successful_exit: Option<bool>,
-    network_state: Option<bool>,
-    path_state: Option<HashMap<String, bool>>,
-    other_job_enabled: Option<HashMap<String, bool>>,
+    pub(crate) successful_exit: Option<bool>,
+    pub(crate) network_state: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::option_map"))]
+    pub(crate) path_state: Option<HashMap<String, bool>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::option_map"))]
+    pub(crate) other_job_enabled: Option<HashMap<String, bool>>,
 }
 
 impl KeepAliveOptions {
@@ -58,4 +60,31 @@ impl KeepAliveOptions {
         self.other_job_enabled = Some(value);
         self
     }
+
+    pub fn successful_exit(&self) -> Option<bool> {
+        self.successful_exit
+    }
+
+    pub fn network_state(&self) -> Option<bool> {
+        self.network_state
+    }
+
+    pub fn path_state(&self) -> Option<&HashMap<String, bool>> {
+        self.path_state.as_ref()
+    }
+
+    /// Iterates over `PathState`, sorted by path.
+    pub fn iter_path_state(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        crate::sorted_entries(self.path_state.as_ref()).map(|(path, state)| (path.as_str(), *state))
+    }
+
+    pub fn other_job_enabled(&self) -> Option<&HashMap<String, bool>> {
+        self.other_job_enabled.as_ref()
+    }
+
+    /// Iterates over `OtherJobEnabled`, sorted by label.
+    pub fn iter_other_job_enabled(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        crate::sorted_entries(self.other_job_enabled.as_ref())
+            .map(|(label, enabled)| (label.as_str(), *enabled))
+    }
 }
@@ -0,0 +1,150 @@
+// Ordering of dictionary keys when a job is written as a plist.
+//
+// By default the output is deterministic: top-level keys follow the order of the struct, and keys
+// of nested dictionaries that come from HashMaps are sorted. A job that was read from a plist also
+// remembers the order of that file, so it can be written back without a noisy diff.
+use crate::{Error, Launchd};
+use plist::{Dictionary, Value};
+
+/// How the keys of dictionaries are ordered when a [`Launchd`] is written as a plist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyOrder {
+    /// Top-level keys in a fixed order, and the keys of nested maps (e.g. `EnvironmentVariables`)
+    /// sorted. Unknown keys come last, sorted.
+    #[default]
+    Declared,
+    /// The order of the plist the job was read from. Keys that weren't in that plist come after
+    /// the original keys, in the `Declared` order.
+    Original,
+    /// The keys of every dictionary sorted, like `plutil` does.
+    Sorted,
+}
+
+// The order of the keys of every dictionary in a plist, recorded when a job is read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KeyOrdering {
+    pub(crate) order: KeyOrder,
+    original: Option<Node>,
+}
+
+// Equality of jobs doesn't depend on how they are written.
+impl PartialEq for KeyOrdering {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dictionary(Vec<(String, Node)>),
+    Array(Vec<Node>),
+    Leaf,
+}
+
+impl Node {
+    fn record(value: &Value) -> Self {
+        match value {
+            Value::Dictionary(dictionary) => Node::Dictionary(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Node::record(value)))
+                    .collect(),
+            ),
+            Value::Array(array) => Node::Array(array.iter().map(Node::record).collect()),
+            _ => Node::Leaf,
+        }
+    }
+
+    fn apply(&self, value: &mut Value) {
+        // A key like StartCalendarInterval is read from a single dictionary as well as an array,
+        // but always written as an array. Write it in the shape it was read in.
+        if let (Node::Dictionary(_), Value::Array(array)) = (self, &mut *value) {
+            if let [Value::Dictionary(_)] = array.as_slice() {
+                *value = array.remove(0);
+            }
+        }
+        match (self, value) {
+            (Node::Dictionary(keys), Value::Dictionary(dictionary)) => {
+                let mut ordered = Dictionary::new();
+                for (key, node) in keys {
+                    if let Some(mut value) = dictionary.remove(key) {
+                        node.apply(&mut value);
+                        ordered.insert(key.clone(), value);
+                    }
+                }
+                for (key, value) in std::mem::take(dictionary) {
+                    ordered.insert(key, value);
+                }
+                *dictionary = ordered;
+            }
+            (Node::Array(nodes), Value::Array(array)) => {
+                for (node, value) in nodes.iter().zip(array.iter_mut()) {
+                    node.apply(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Dictionary(dictionary) => {
+            dictionary.sort_keys();
+            dictionary.values_mut().for_each(sort_keys);
+        }
+        Value::Array(array) => array.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+impl KeyOrdering {
+    pub(crate) fn record(value: &Value) -> Self {
+        Self {
+            order: KeyOrder::Declared,
+            original: Some(Node::record(value)),
+        }
+    }
+
+    pub(crate) fn apply(&self, value: &mut Value) {
+        match (self.order, &self.original) {
+            (KeyOrder::Original, Some(original)) => original.apply(value),
+            (KeyOrder::Sorted, _) => sort_keys(value),
+            _ => {}
+        }
+    }
+}
+
+impl Launchd {
+    /// Sets how the keys are ordered when this job is written as a plist.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{KeyOrder, Launchd};
+    ///
+    /// let plist = br#"<plist version="1.0"><dict>
+    ///     <key>ProgramArguments</key><array><string>/bin/true</string></array>
+    ///     <key>Label</key><string>LABEL</string>
+    /// </dict></plist>"#;
+    /// let launchd = Launchd::from_bytes(plist).unwrap().with_key_order(KeyOrder::Original);
+    ///
+    /// let mut buffer = Vec::new();
+    /// launchd.to_writer_xml(&mut buffer).unwrap();
+    /// let xml = String::from_utf8(buffer).unwrap();
+    /// assert!(xml.find("ProgramArguments") < xml.find("Label"));
+    /// ```
+    pub fn with_key_order(mut self, order: KeyOrder) -> Self {
+        self.key_order.order = order;
+        self
+    }
+
+    // The plist of this job in the order of the file it was read from, whatever order it is
+    // written in, for edits that read the job back from the plist.
+    pub(crate) fn to_original_plist_value(&self) -> Result<Value, Error> {
+        let mut value = self.to_plist_value()?;
+        if let Some(original) = &self.key_order.original {
+            original.apply(&mut value);
+        }
+        Ok(value)
+    }
+}
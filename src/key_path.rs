@@ -0,0 +1,132 @@
+// Paths to keys inside a launchd.plist, used to point at the location of diagnostics and to
+// address values in the PlistBuddy-style accessors.
+use crate::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Path to a value in a launchd.plist, e.g. `Sockets.Listeners.SockPathName` or
+/// `ProgramArguments[0]`. When it is displayed, keys with other characters than letters, digits,
+/// `_` and `-` are quoted, e.g. `KeepAlive.PathState["/var/run/agent.disabled"]`.
+///
+/// Usage:
+/// ```
+/// use launchd::KeyPath;
+///
+/// let path = KeyPath::new().key("StartCalendarInterval").index(1).key("Hour");
+/// assert_eq!(path.to_string(), "StartCalendarInterval[1].Hour");
+///
+/// let path = KeyPath::new().key("LaunchEvents").key("com.apple.notifyd.matching");
+/// assert_eq!(path.to_string(), r#"LaunchEvents["com.apple.notifyd.matching"]"#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyPath {
+    segments: Vec<Segment>,
+}
+
+/// A single step of a [`KeyPath`]: a key of a dictionary or an index into an array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl KeyPath {
+    /// Creates an empty path, which points at the root dictionary.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key<S: AsRef<str>>(mut self, key: S) -> Self {
+        self.segments.push(Segment::Key(key.as_ref().to_string()));
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.segments.push(Segment::Index(index));
+        self
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub(crate) fn segment(mut self, segment: Segment) -> Self {
+        self.segments.push(segment);
+        self
+    }
+}
+
+/// Parses a path in the syntax of PlistBuddy(8), e.g. `:Sockets:Listeners:SockServiceName` or
+/// `:ProgramArguments:0`. The leading colon is optional, a colon in a key is written as `\:`, and
+/// a segment of only digits is an index (or a key, when it is used on a dictionary).
+///
+/// Usage:
+/// ```
+/// use launchd::KeyPath;
+///
+/// let path: KeyPath = ":StartCalendarInterval:1:Hour".parse().unwrap();
+/// assert_eq!(path, KeyPath::new().key("StartCalendarInterval").index(1).key("Hour"));
+///
+/// let path: KeyPath = r":EnvironmentVariables:A\:B".parse().unwrap();
+/// assert_eq!(path, KeyPath::new().key("EnvironmentVariables").key("A:B"));
+/// assert!(":Sockets::Listeners".parse::<KeyPath>().is_err());
+/// ```
+impl FromStr for KeyPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let relative = path.strip_prefix(':').unwrap_or(path);
+        let mut key_path = KeyPath::new();
+        if relative.is_empty() {
+            return Ok(key_path);
+        }
+
+        let mut segments = vec![String::new()];
+        let mut chars = relative.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if matches!(chars.peek(), Some(':') | Some('\\')) => {
+                    segments.last_mut().unwrap().extend(chars.next());
+                }
+                ':' => segments.push(String::new()),
+                c => segments.last_mut().unwrap().push(c),
+            }
+        }
+        for segment in segments {
+            if segment.is_empty() {
+                return Err(Error::InvalidKeyPath(path.to_string()));
+            }
+            key_path = key_path.segment(match segment.parse() {
+                Ok(index) if segment.bytes().all(|b| b.is_ascii_digit()) => Segment::Index(index),
+                _ => Segment::Key(segment),
+            });
+        }
+        Ok(key_path)
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if needs_quotes(key) => write!(f, "[{:?}]", key)?,
+                Segment::Key(key) if i == 0 => write!(f, "{}", key)?,
+                Segment::Key(key) => write!(f, ".{}", key)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether a key can't be told apart from the separators when it is displayed as is.
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
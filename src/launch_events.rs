@@ -0,0 +1,462 @@
+// See the LaunchEvents section in https://www.manpagez.com/man/5/launchd.plist/
+//
+// LaunchEvents is a dictionary of event streams, each of which is a dictionary of named events,
+// each of which is a dictionary that describes the event. The descriptors of the common streams are
+// modelled; other streams are kept as Values. A common stream whose descriptors have keys that
+// aren't modelled is kept as Values as well, so nothing is lost.
+use crate::Value;
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::fmt;
+
+const IOKIT_MATCHING: &str = "com.apple.iokit.matching";
+const NOTIFYD_MATCHING: &str = "com.apple.notifyd.matching";
+const DISTNOTED_MATCHING: &str = "com.apple.distnoted.matching";
+
+/// The event streams that launch a job on demand.
+///
+/// Usage:
+/// ```
+/// use launchd::{IOKitMatching, LaunchEvents, NotifydMatching};
+///
+/// let events = LaunchEvents::new()
+///     .with_iokit_matching(
+///         "com.apple.device-attach",
+///         IOKitMatching::new()
+///             .with_provider_class("IOUSBDevice")
+///             .with_vendor_id(4176)
+///             .with_product_id("*")
+///             .match_launch_stream(),
+///     )
+///     .with_notifyd_matching(
+///         "com.apple.system.timezone",
+///         NotifydMatching::new("com.apple.system.timezone"),
+///     );
+///
+/// assert_eq!(
+///     events.iokit_matching()["com.apple.device-attach"].provider_class(),
+///     Some("IOUSBDevice")
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LaunchEvents {
+    pub(crate) iokit: HashMap<String, IOKitMatching>,
+    pub(crate) notifyd: HashMap<String, NotifydMatching>,
+    pub(crate) distnoted: HashMap<String, DistnotedMatching>,
+    pub(crate) other: HashMap<String, HashMap<String, HashMap<String, Value>>>,
+}
+
+/// An IOKit matching dictionary, from the `com.apple.iokit.matching` stream.
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IOKitMatching {
+    #[cfg_attr(feature = "serde", serde(rename = "IOProviderClass"))]
+    pub(crate) provider_class: Option<String>,
+    #[cfg_attr(feature = "serde", serde(rename = "idVendor"))]
+    pub(crate) vendor_id: Option<DeviceId>,
+    #[cfg_attr(feature = "serde", serde(rename = "idProduct"))]
+    pub(crate) product_id: Option<DeviceId>,
+    #[cfg_attr(feature = "serde", serde(rename = "IOMatchLaunchStream"))]
+    pub(crate) match_launch_stream: Option<bool>,
+}
+
+/// A USB vendor or product ID, or a pattern such as `*` that matches any ID.
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceId {
+    Number(u32),
+    Pattern(String),
+}
+
+/// A notify(3) notification, from the `com.apple.notifyd.matching` stream.
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifydMatching {
+    #[cfg_attr(feature = "serde", serde(rename = "Notification"))]
+    pub(crate) notification: String,
+}
+
+/// A distributed notification, from the `com.apple.distnoted.matching` stream.
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistnotedMatching {
+    #[cfg_attr(feature = "serde", serde(rename = "Name"))]
+    pub(crate) name: String,
+}
+
+impl LaunchEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_iokit_matching<S: AsRef<str>>(mut self, event: S, matching: IOKitMatching) -> Self {
+        self.iokit.insert(event.as_ref().to_string(), matching);
+        self
+    }
+
+    pub fn with_notifyd_matching<S: AsRef<str>>(
+        mut self,
+        event: S,
+        matching: NotifydMatching,
+    ) -> Self {
+        self.notifyd.insert(event.as_ref().to_string(), matching);
+        self
+    }
+
+    pub fn with_distnoted_matching<S: AsRef<str>>(
+        mut self,
+        event: S,
+        matching: DistnotedMatching,
+    ) -> Self {
+        self.distnoted.insert(event.as_ref().to_string(), matching);
+        self
+    }
+
+    /// The events of the `com.apple.iokit.matching` stream, by name.
+    pub fn iokit_matching(&self) -> &HashMap<String, IOKitMatching> {
+        &self.iokit
+    }
+
+    /// The events of the `com.apple.notifyd.matching` stream, by name.
+    pub fn notifyd_matching(&self) -> &HashMap<String, NotifydMatching> {
+        &self.notifyd
+    }
+
+    /// The events of the `com.apple.distnoted.matching` stream, by name.
+    pub fn distnoted_matching(&self) -> &HashMap<String, DistnotedMatching> {
+        &self.distnoted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iokit.is_empty()
+            && self.notifyd.is_empty()
+            && self.distnoted.is_empty()
+            && self.other.is_empty()
+    }
+
+    /// Adds an event to a stream that isn't modelled, e.g. `com.apple.fsevents.matching`.
+    pub fn with_event<S: AsRef<str>, E: AsRef<str>>(
+        mut self,
+        stream: S,
+        event: E,
+        descriptor: HashMap<String, Value>,
+    ) -> Self {
+        self.other
+            .entry(stream.as_ref().to_string())
+            .or_default()
+            .insert(event.as_ref().to_string(), descriptor);
+        self
+    }
+
+    /// The streams that aren't modelled, including a common stream with descriptors that don't fit
+    /// the model (e.g. an IOKit matching dictionary with an `IONameMatch` key).
+    pub fn other_streams(&self) -> &HashMap<String, HashMap<String, HashMap<String, Value>>> {
+        &self.other
+    }
+
+    pub fn iokit_matching_mut(&mut self) -> &mut HashMap<String, IOKitMatching> {
+        &mut self.iokit
+    }
+
+    pub fn notifyd_matching_mut(&mut self) -> &mut HashMap<String, NotifydMatching> {
+        &mut self.notifyd
+    }
+
+    pub fn distnoted_matching_mut(&mut self) -> &mut HashMap<String, DistnotedMatching> {
+        &mut self.distnoted
+    }
+
+    /// The streams that aren't modelled, for editing. A common stream added here is written
+    /// alongside the typed events of that stream.
+    pub fn other_streams_mut(
+        &mut self,
+    ) -> &mut HashMap<String, HashMap<String, HashMap<String, Value>>> {
+        &mut self.other
+    }
+
+    /// Removes an event from a stream, whether it is modelled or not. Returns whether the event was
+    /// there; a stream that isn't modelled is removed with its last event.
+    pub fn remove_event<S: AsRef<str>, E: AsRef<str>>(&mut self, stream: S, event: E) -> bool {
+        let (stream, event) = (stream.as_ref(), event.as_ref());
+        let removed = match stream {
+            IOKIT_MATCHING => self.iokit.remove(event).is_some(),
+            NOTIFYD_MATCHING => self.notifyd.remove(event).is_some(),
+            DISTNOTED_MATCHING => self.distnoted.remove(event).is_some(),
+            _ => false,
+        };
+        let removed_other = match self.other.get_mut(stream) {
+            Some(events) => {
+                let removed = events.remove(event).is_some();
+                if events.is_empty() {
+                    self.other.remove(stream);
+                }
+                removed
+            }
+            None => false,
+        };
+        removed || removed_other
+    }
+}
+
+impl IOKitMatching {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_provider_class<S: AsRef<str>>(mut self, value: S) -> Self {
+        self.provider_class = Some(value.as_ref().to_string());
+        self
+    }
+
+    pub fn with_vendor_id<T: Into<DeviceId>>(mut self, value: T) -> Self {
+        self.vendor_id = Some(value.into());
+        self
+    }
+
+    pub fn with_product_id<T: Into<DeviceId>>(mut self, value: T) -> Self {
+        self.product_id = Some(value.into());
+        self
+    }
+
+    pub fn with_match_launch_stream(mut self, value: bool) -> Self {
+        self.match_launch_stream = Some(value);
+        self
+    }
+
+    pub fn match_launch_stream(self) -> Self {
+        self.with_match_launch_stream(true)
+    }
+
+    pub fn provider_class(&self) -> Option<&str> {
+        self.provider_class.as_deref()
+    }
+
+    pub fn vendor_id(&self) -> Option<&DeviceId> {
+        self.vendor_id.as_ref()
+    }
+
+    pub fn product_id(&self) -> Option<&DeviceId> {
+        self.product_id.as_ref()
+    }
+
+    pub fn get_match_launch_stream(&self) -> Option<bool> {
+        self.match_launch_stream
+    }
+}
+
+impl From<u32> for DeviceId {
+    fn from(value: u32) -> Self {
+        DeviceId::Number(value)
+    }
+}
+
+impl From<&str> for DeviceId {
+    fn from(value: &str) -> Self {
+        DeviceId::Pattern(value.to_string())
+    }
+}
+
+impl NotifydMatching {
+    pub fn new<S: AsRef<str>>(notification: S) -> Self {
+        Self {
+            notification: notification.as_ref().to_string(),
+        }
+    }
+
+    pub fn notification(&self) -> &str {
+        &self.notification
+    }
+}
+
+impl DistnotedMatching {
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        Self {
+            name: name.as_ref().to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// Streams and events are written sorted, so the output doesn't depend on the order of the HashMaps.
+#[cfg(feature = "serde")]
+impl Serialize for LaunchEvents {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Descriptor<'a> {
+            IOKit(&'a IOKitMatching),
+            Notifyd(&'a NotifydMatching),
+            Distnoted(&'a DistnotedMatching),
+            Other(BTreeMap<&'a String, &'a Value>),
+        }
+
+        fn add<'a>(
+            streams: &mut BTreeMap<&'a str, BTreeMap<&'a str, Descriptor<'a>>>,
+            stream: &'a str,
+            event: &'a str,
+            descriptor: Descriptor<'a>,
+        ) {
+            streams.entry(stream).or_default().insert(event, descriptor);
+        }
+
+        let mut streams = BTreeMap::new();
+        for (event, matching) in &self.iokit {
+            add(
+                &mut streams,
+                IOKIT_MATCHING,
+                event,
+                Descriptor::IOKit(matching),
+            );
+        }
+        for (event, matching) in &self.notifyd {
+            add(
+                &mut streams,
+                NOTIFYD_MATCHING,
+                event,
+                Descriptor::Notifyd(matching),
+            );
+        }
+        for (event, matching) in &self.distnoted {
+            add(
+                &mut streams,
+                DISTNOTED_MATCHING,
+                event,
+                Descriptor::Distnoted(matching),
+            );
+        }
+        for (stream, events) in &self.other {
+            for (event, descriptor) in events {
+                add(
+                    &mut streams,
+                    stream,
+                    event,
+                    Descriptor::Other(descriptor.iter().collect()),
+                );
+            }
+        }
+        streams.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for LaunchEvents {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StreamsVisitor;
+
+        impl<'de> Visitor<'de> for StreamsVisitor {
+            type Value = LaunchEvents;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a dictionary of event streams")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut events = LaunchEvents::new();
+                while let Some(stream) = map.next_key::<String>()? {
+                    events.insert_stream(stream, &mut map)?;
+                }
+                Ok(events)
+            }
+        }
+
+        deserializer.deserialize_map(StreamsVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl LaunchEvents {
+    // A common stream is read as values first, and only modelled if every descriptor fits.
+    fn insert_stream<'de, A: MapAccess<'de>>(
+        &mut self,
+        stream: String,
+        map: &mut A,
+    ) -> Result<(), A::Error> {
+        let value: Value = map.next_value()?;
+        let typed = match stream.as_str() {
+            IOKIT_MATCHING => HashMap::deserialize(value.clone()).map(|events| self.iokit = events),
+            NOTIFYD_MATCHING => {
+                HashMap::deserialize(value.clone()).map(|events| self.notifyd = events)
+            }
+            DISTNOTED_MATCHING => {
+                HashMap::deserialize(value.clone()).map(|events| self.distnoted = events)
+            }
+            _ => Err(de::Error::custom("not a modelled stream")),
+        };
+        if typed.is_err() {
+            let events = HashMap::deserialize(value).map_err(de::Error::custom)?;
+            self.other.insert(stream, events);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untyped_streams_survive_any_serde_backend() {
+        let descriptor = vec![
+            ("Path".to_string(), Value::from("/Volumes")),
+            ("Recursive".to_string(), Value::from(true)),
+            ("Depth".to_string(), Value::from(2)),
+        ]
+        .into_iter()
+        .collect();
+        let events = LaunchEvents::new()
+            .with_notifyd_matching(
+                "timezone",
+                NotifydMatching::new("com.apple.system.timezone"),
+            )
+            .with_event("com.apple.fsevents.matching", "volumes", descriptor);
+
+        let json = serde_json::to_string(&events).unwrap();
+        assert_eq!(
+            json,
+            r#"{"com.apple.fsevents.matching":{"volumes":{"Depth":2,"Path":"/Volumes","Recursive":true}},"com.apple.notifyd.matching":{"timezone":{"Notification":"com.apple.system.timezone"}}}"#
+        );
+        assert_eq!(serde_json::from_str::<LaunchEvents>(&json).unwrap(), events);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn descriptors_that_do_not_fit_are_kept() {
+        let plist = br#"<plist version="1.0"><dict>
+            <key>com.apple.iokit.matching</key><dict>
+                <key>com.apple.usb.device</key><dict>
+                    <key>IOProviderClass</key><string>IOUSBHostDevice</string>
+                    <key>IONameMatch</key><string>usb</string>
+                </dict>
+            </dict>
+            <key>com.apple.fsevents.matching</key><dict>
+                <key>volumes</key><dict><key>Path</key><string>/Volumes</string></dict>
+            </dict>
+        </dict></plist>"#;
+        let events: LaunchEvents = plist::from_bytes(plist).unwrap();
+        assert!(events.iokit_matching().is_empty());
+        let mut streams: Vec<_> = events.other_streams().keys().collect();
+        streams.sort();
+        assert_eq!(
+            streams,
+            vec!["com.apple.fsevents.matching", "com.apple.iokit.matching"]
+        );
+
+        let mut buffer = Vec::new();
+        plist::to_writer_xml(&mut buffer, &events).unwrap();
+        assert_eq!(plist::from_bytes::<LaunchEvents>(&buffer).unwrap(), events);
+    }
+}
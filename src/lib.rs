@@ -47,7 +47,7 @@
 //!                 <string>Hello</string>
 //!                 <string>World!</string>
 //!         </array>
-//!         <key>StartCalendarIntervals</key>
+//!         <key>StartCalendarInterval</key>
 //!         <array>
 //!                 <dict>
 //!                         <key>Minute</key>
@@ -62,35 +62,86 @@
 //! </plist>
 //! ```
 
+#[cfg(feature = "io")]
+mod borrowed;
+#[cfg(feature = "io")]
+mod canonical;
+mod crontab;
+mod describe;
+#[cfg(feature = "io")]
+pub mod diff;
+mod edit;
 mod error;
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+mod formats;
 pub mod keep_alive;
+#[cfg(feature = "io")]
+mod key_order;
+pub mod key_path;
+pub mod launch_events;
+mod lint;
+#[cfg(feature = "io")]
+pub mod loader;
 pub mod mach_services;
+pub mod merge;
+mod minimize;
+#[cfg(feature = "io")]
+mod partial;
+#[cfg(feature = "io")]
+mod path_access;
 pub mod process_type;
 pub mod resource_limits;
+#[cfg(feature = "chrono")]
+pub mod schedule;
 pub mod sockets;
+#[cfg(feature = "serde")]
+mod sorted;
+pub mod systemd;
+#[cfg(feature = "serde")]
+mod unknown_keys;
+pub mod validate;
+pub mod value;
+#[cfg(feature = "io")]
+mod xml;
 
+#[cfg(feature = "io")]
+pub use self::borrowed::LaunchdRef;
+#[cfg(feature = "io")]
+pub use self::diff::{diff, Change, Diff};
 pub use self::error::Error;
 pub use self::keep_alive::{KeepAliveOptions, KeepAliveType};
+#[cfg(feature = "io")]
+pub use self::key_order::KeyOrder;
+pub use self::key_path::KeyPath;
+pub use self::launch_events::{
+    DeviceId, DistnotedMatching, IOKitMatching, LaunchEvents, NotifydMatching,
+};
+#[cfg(feature = "io")]
+pub use self::loader::{Jobs, LoadIssue, LoadedJob, Loader, ParseStats, Parsed, ParsedFile};
 pub use self::mach_services::{MachServiceEntry, MachServiceOptions};
+pub use self::merge::MergeOptions;
+#[cfg(feature = "io")]
+pub use self::merge::{Conflict, ThreeWayMerge};
 pub use self::process_type::ProcessType;
 pub use self::resource_limits::ResourceLimits;
+#[cfg(feature = "chrono")]
+pub use self::schedule::FireTimes;
 pub use self::sockets::{BonjourType, Socket, SocketOptions, Sockets};
+pub use self::systemd::SystemdUnits;
+pub use self::validate::{Diagnostic, Severity};
+pub use self::value::Value;
 
 #[cfg(feature = "cron")]
 use cron::{Schedule, TimeUnitSpec};
-#[cfg(feature = "plist")]
-use plist::Value;
 #[cfg(feature = "io")]
-use plist::{from_bytes, from_file, from_reader, from_reader_xml};
-#[cfg(feature = "io")]
-use plist::{to_file_binary, to_file_xml, to_writer_binary, to_writer_xml};
+use key_order::KeyOrdering;
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 #[cfg(feature = "cron")]
 use std::convert::TryInto;
 #[cfg(feature = "io")]
-use std::io::{Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, Write};
 use std::path::Path;
 
 /// Representation of a launchd.plist file.
@@ -120,8 +171,10 @@ use std::path::Path;
 /// NB: The usage is still subject to change.
 // TODO: Fill with all options in https://www.manpagez.com/man/5/launchd.plist/
 // TODO: remove owned Strings (?)
+// The derived implementations only cover the modelled keys. Serialize and Deserialize are
+// implemented on top of them in unknown_keys.rs, to write and collect the other keys as well.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+#[cfg_attr(feature = "serde", serde(remote = "Self"))]
 #[cfg_attr(feature = "io", serde(rename_all = "PascalCase"))]
 #[derive(Debug, Default, PartialEq)]
 pub struct Launchd {
@@ -134,7 +187,9 @@ pub struct Launchd {
     limit_load_to_hosts: Option<Vec<String>>,
     limit_load_from_hosts: Option<Vec<String>>,
     limit_load_to_session_type: Option<LoadSessionType>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "sorted::option_map"))]
     limit_load_to_hardware: Option<HashMap<String, Vec<String>>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "sorted::option_map"))]
     limit_load_from_hardware: Option<HashMap<String, Vec<String>>>,
     program: Option<String>, // TODO: Ensure this: "NOTE: The Program key must be an absolute path."
     bundle_program: Option<String>,
@@ -149,6 +204,7 @@ pub struct Launchd {
     run_at_load: Option<bool>,
     root_directory: Option<String>,
     working_directory: Option<String>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "sorted::option_map"))]
     environment_variables: Option<HashMap<String, String>>,
     umask: Option<u16>, // NB: This is a Unix permission mask. Defined as: typedef __uint16_t __darwin_mode_t;
     time_out: Option<u32>,
@@ -159,6 +215,17 @@ pub struct Launchd {
     queue_directories: Option<Vec<String>>,
     start_on_mount: Option<bool>,
     start_interval: Option<u32>,
+    // launchd accepts a single dictionary as well as an array. Older versions of this crate wrote
+    // the key as "StartCalendarIntervals", which launchd ignores, so that is still read.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "StartCalendarInterval",
+            alias = "StartCalendarIntervals",
+            default,
+            deserialize_with = "deserialize_calendar_intervals"
+        )
+    )]
     start_calendar_intervals: Option<Vec<CalendarInterval>>,
     standard_in_path: Option<String>,
     standard_out_path: Option<String>,
@@ -176,6 +243,7 @@ pub struct Launchd {
     low_priority_background_io: Option<bool>,
     materialize_dataless_files: Option<bool>,
     launch_only_once: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "sorted::option_map"))]
     mach_services: Option<HashMap<String, MachServiceEntry>>,
     sockets: Option<Sockets>,
     launch_events: Option<LaunchEvents>,
@@ -183,18 +251,31 @@ pub struct Launchd {
     hopefully_exits_first: Option<bool>, // NB: Deprecated, keep for reading old plists.
     session_create: Option<bool>,
     legacy_timers: Option<bool>, // NB: Deprecated, keep for reading old plists.
-                                 // associated_bundle_identifiers: Option<<string or array of strings>>
+    // Keys that aren't modelled above, e.g. AssociatedBundleIdentifiers or Apple's private keys.
+    // They are kept with any serde backend, so that reading and writing a job doesn't lose any data.
+    // NB: serde(flatten) can't be used, as the plist crate then wraps every Option in a dictionary.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unknown_keys: HashMap<String, Value>,
+    #[cfg(feature = "io")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    key_order: KeyOrdering,
 }
 
-// Defined as a "<dictionary of dictionaries of dictionaries>" in launchd.plist(5)
-// Use plist::Value as the value can be String, Integer, Boolean, etc.
-// Doing this precludes the use of #[derive(Eq)] on the Launchd struct, but in practice "PartialEq" is fine.
-#[cfg(feature = "plist")]
-type LaunchEvents = HashMap<String, HashMap<String, HashMap<String, Value>>>;
-
-// TODO: the current implementation is dependent on plist. Is this necessary?
-#[cfg(not(feature = "plist"))]
-type LaunchEvents = ();
+#[cfg(feature = "serde")]
+fn deserialize_calendar_intervals<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<CalendarInterval>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(CalendarInterval),
+        Many(Vec<CalendarInterval>),
+    }
+    Ok(Some(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(ci) => vec![ci],
+        OneOrMany::Many(intervals) => intervals,
+    }))
+}
 
 /// Representation of a CalendarInterval
 ///
@@ -211,7 +292,7 @@ type LaunchEvents = ();
 /// ```
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "io", serde(rename_all = "PascalCase"))]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 pub struct CalendarInterval {
     minute: Option<u8>,
     hour: Option<u8>,
@@ -633,53 +714,380 @@ impl Launchd {
     }
 }
 
+// Getters --
+// The Option<bool> fields that have a shortcut builder (e.g. `disabled()`) are read with `get_*`.
+impl Launchd {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_disabled(&self) -> Option<bool> {
+        self.disabled
+    }
+
+    pub fn user_name(&self) -> Option<&str> {
+        self.user_name.as_deref()
+    }
+
+    pub fn group_name(&self) -> Option<&str> {
+        self.group_name.as_deref()
+    }
+
+    /// Returns the `Wait` flag of `inetdCompatibility`.
+    pub fn inetd_compatibility(&self) -> Option<bool> {
+        self.inetd_compatibility
+            .as_ref()
+            .and_then(|inetd| inetd.get(&InetdCompatibility::Wait).copied())
+    }
+
+    pub fn limit_load_to_hosts(&self) -> Option<&[String]> {
+        self.limit_load_to_hosts.as_deref()
+    }
+
+    pub fn limit_load_from_hosts(&self) -> Option<&[String]> {
+        self.limit_load_from_hosts.as_deref()
+    }
+
+    pub fn limit_load_to_session_type(&self) -> Option<&LoadSessionType> {
+        self.limit_load_to_session_type.as_ref()
+    }
+
+    pub fn limit_load_to_hardware(&self) -> Option<&HashMap<String, Vec<String>>> {
+        self.limit_load_to_hardware.as_ref()
+    }
+
+    /// Iterates over `LimitLoadToHardware`, sorted by key.
+    pub fn iter_limit_load_to_hardware(&self) -> impl Iterator<Item = (&str, &[String])> + '_ {
+        sorted_entries(self.limit_load_to_hardware.as_ref())
+            .map(|(key, values)| (key.as_str(), values.as_slice()))
+    }
+
+    pub fn limit_load_from_hardware(&self) -> Option<&HashMap<String, Vec<String>>> {
+        self.limit_load_from_hardware.as_ref()
+    }
+
+    /// Iterates over `LimitLoadFromHardware`, sorted by key.
+    pub fn iter_limit_load_from_hardware(&self) -> impl Iterator<Item = (&str, &[String])> + '_ {
+        sorted_entries(self.limit_load_from_hardware.as_ref())
+            .map(|(key, values)| (key.as_str(), values.as_slice()))
+    }
+
+    pub fn program(&self) -> Option<&Path> {
+        self.program.as_deref().map(Path::new)
+    }
+
+    pub fn bundle_program(&self) -> Option<&str> {
+        self.bundle_program.as_deref()
+    }
+
+    pub fn program_arguments(&self) -> Option<&[String]> {
+        self.program_arguments.as_deref()
+    }
+
+    pub fn get_enable_globbing(&self) -> Option<bool> {
+        self.enable_globbing
+    }
+
+    pub fn get_enable_transactions(&self) -> Option<bool> {
+        self.enable_transactions
+    }
+
+    pub fn get_enable_pressured_exit(&self) -> Option<bool> {
+        self.enable_pressured_exit
+    }
+
+    /// Deprecated by launchd in favour of `KeepAlive`, see [`Launchd::modernize`].
+    pub fn on_demand(&self) -> Option<bool> {
+        self.on_demand
+    }
+
+    /// Ignored by launchd.
+    pub fn service_ipc(&self) -> Option<bool> {
+        self.service_ipc
+    }
+
+    pub fn keep_alive(&self) -> Option<&KeepAliveType> {
+        self.keep_alive.as_ref()
+    }
+
+    pub fn get_run_at_load(&self) -> Option<bool> {
+        self.run_at_load
+    }
+
+    pub fn root_directory(&self) -> Option<&Path> {
+        self.root_directory.as_deref().map(Path::new)
+    }
+
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.working_directory.as_deref().map(Path::new)
+    }
+
+    pub fn environment_variables(&self) -> Option<&HashMap<String, String>> {
+        self.environment_variables.as_ref()
+    }
+
+    /// Iterates over `EnvironmentVariables`, sorted by name.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    /// use std::collections::HashMap;
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let launchd = Launchd::new("LABEL", "/bin/true")?.with_environment_variables(
+    ///         HashMap::from([
+    ///             ("PATH".to_string(), "/usr/bin".to_string()),
+    ///             ("LANG".to_string(), "C".to_string()),
+    ///         ]),
+    ///     );
+    ///
+    ///     let env: Vec<_> = launchd.iter_environment_variables().collect();
+    ///     assert_eq!(env, vec![("LANG", "C"), ("PATH", "/usr/bin")]);
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn iter_environment_variables(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        sorted_entries(self.environment_variables.as_ref())
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn umask(&self) -> Option<u16> {
+        self.umask
+    }
+
+    pub fn timeout(&self) -> Option<u32> {
+        self.time_out
+    }
+
+    pub fn exit_timeout(&self) -> Option<u32> {
+        self.exit_time_out
+    }
+
+    pub fn throttle_interval(&self) -> Option<u32> {
+        self.throttle_interval
+    }
+
+    pub fn get_init_groups(&self) -> Option<bool> {
+        self.init_groups
+    }
+
+    pub fn watch_paths(&self) -> Option<&[String]> {
+        self.watch_paths.as_deref()
+    }
+
+    pub fn queue_directories(&self) -> Option<&[String]> {
+        self.queue_directories.as_deref()
+    }
+
+    pub fn get_start_on_mount(&self) -> Option<bool> {
+        self.start_on_mount
+    }
+
+    pub fn start_interval(&self) -> Option<u32> {
+        self.start_interval
+    }
+
+    /// Returns the `StartCalendarInterval`s. A single dictionary is returned as a slice of one.
+    pub fn start_calendar_intervals(&self) -> Option<&[CalendarInterval]> {
+        self.start_calendar_intervals.as_deref()
+    }
+
+    pub fn standard_in_path(&self) -> Option<&Path> {
+        self.standard_in_path.as_deref().map(Path::new)
+    }
+
+    pub fn standard_out_path(&self) -> Option<&Path> {
+        self.standard_out_path.as_deref().map(Path::new)
+    }
+
+    pub fn standard_error_path(&self) -> Option<&Path> {
+        self.standard_error_path.as_deref().map(Path::new)
+    }
+
+    pub fn get_debug(&self) -> Option<bool> {
+        self.debug
+    }
+
+    pub fn get_wait_for_debugger(&self) -> Option<bool> {
+        self.wait_for_debugger
+    }
+
+    pub fn soft_resource_limits(&self) -> Option<&ResourceLimits> {
+        self.soft_resource_limits.as_ref()
+    }
+
+    pub fn hard_resource_limits(&self) -> Option<&ResourceLimits> {
+        self.hard_resource_limits.as_ref()
+    }
+
+    pub fn nice(&self) -> Option<i32> {
+        self.nice
+    }
+
+    pub fn process_type(&self) -> Option<&ProcessType> {
+        self.process_type.as_ref()
+    }
+
+    pub fn get_abandon_process_group(&self) -> Option<bool> {
+        self.abandon_process_group
+    }
+
+    pub fn get_low_priority_io(&self) -> Option<bool> {
+        self.low_priority_io
+    }
+
+    pub fn get_low_priority_background_io(&self) -> Option<bool> {
+        self.low_priority_background_io
+    }
+
+    pub fn get_materialize_dataless_files(&self) -> Option<bool> {
+        self.materialize_dataless_files
+    }
+
+    pub fn get_launch_only_once(&self) -> Option<bool> {
+        self.launch_only_once
+    }
+
+    pub fn mach_services(&self) -> Option<&HashMap<String, MachServiceEntry>> {
+        self.mach_services.as_ref()
+    }
+
+    /// Iterates over `MachServices`, sorted by service name.
+    pub fn iter_mach_services(&self) -> impl Iterator<Item = (&str, &MachServiceEntry)> + '_ {
+        sorted_entries(self.mach_services.as_ref()).map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    pub fn sockets(&self) -> Option<&Sockets> {
+        self.sockets.as_ref()
+    }
+
+    pub fn launch_events(&self) -> Option<&LaunchEvents> {
+        self.launch_events.as_ref()
+    }
+
+    /// Deprecated and ignored by launchd.
+    pub fn hopefully_exits_last(&self) -> Option<bool> {
+        self.hopefully_exits_last
+    }
+
+    /// Deprecated and ignored by launchd.
+    pub fn hopefully_exits_first(&self) -> Option<bool> {
+        self.hopefully_exits_first
+    }
+
+    pub fn get_session_create(&self) -> Option<bool> {
+        self.session_create
+    }
+
+    /// Deprecated and ignored by launchd.
+    pub fn legacy_timers(&self) -> Option<bool> {
+        self.legacy_timers
+    }
+}
+
+// The entries of an optional HashMap in key order, so that iterating over a job is deterministic.
+pub(crate) fn sorted_entries<K: Ord, V>(
+    map: Option<&HashMap<K, V>>,
+) -> std::vec::IntoIter<(&K, &V)> {
+    let mut entries: Vec<_> = map.into_iter().flatten().collect();
+    entries.sort_by(|left, right| left.0.cmp(right.0));
+    entries.into_iter()
+}
+
 #[cfg(feature = "io")]
 impl Launchd {
     // Write --
     pub fn to_writer_xml<W: Write>(&self, writer: W) -> Result<(), Error> {
-        to_writer_xml(writer, self).map_err(Error::Write)
+        self.to_plist_value()?
+            .to_writer_xml(writer)
+            .map_err(Error::Write)
     }
 
     pub fn to_file_xml<P: AsRef<Path>>(&self, file: P) -> Result<(), Error> {
-        to_file_xml(file, self).map_err(Error::Write)
+        self.to_plist_value()?
+            .to_file_xml(file)
+            .map_err(Error::Write)
     }
 
     pub fn to_writer_binary<W: Write>(&self, writer: W) -> Result<(), Error> {
-        to_writer_binary(writer, self).map_err(Error::Write)
+        self.to_plist_value()?
+            .to_writer_binary(writer)
+            .map_err(Error::Write)
     }
 
     pub fn to_file_binary<P: AsRef<Path>>(&self, file: P) -> Result<(), Error> {
-        to_file_binary(file, self).map_err(Error::Write)
+        self.to_plist_value()?
+            .to_file_binary(file)
+            .map_err(Error::Write)
     }
 
     // Read --
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
-        from_bytes(bytes).map_err(Error::Read)
+        Self::from_reader(Cursor::new(bytes))
     }
 
     pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Self, Error> {
-        from_file(file).map_err(Error::Read)
+        Self::from_plist_value(plist::Value::from_file(file).map_err(Error::Read)?)
     }
 
     pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self, Error> {
-        from_reader(reader).map_err(Error::Read)
+        Self::from_plist_value(plist::Value::from_reader(reader).map_err(Error::Read)?)
     }
 
     pub fn from_reader_xml<R: Read + Seek>(reader: R) -> Result<Self, Error> {
-        from_reader_xml(reader).map_err(Error::Read)
+        Self::from_plist_value(plist::Value::from_reader_xml(reader).map_err(Error::Read)?)
     }
 }
 
-impl CalendarInterval {
-    #[cfg(feature = "cron")] // This has some use for launchd::with_start_calendar_intervals as well
-    fn is_initialized(&self) -> bool {
-        self.minute.is_some()
-            || self.hour.is_some()
-            || self.day.is_some()
-            || self.weekday.is_some()
-            || self.month.is_some()
+impl Launchd {
+    /// Returns the top-level keys that aren't modelled by this struct, such as
+    /// `AssociatedBundleIdentifiers` or Apple's private keys. They are written back unchanged.
+    pub fn unknown_keys(&self) -> &HashMap<String, Value> {
+        &self.unknown_keys
     }
+}
 
+#[cfg(feature = "serde")]
+impl Launchd {
+    /// Adds a key that isn't modelled by this struct. The keys it does model, such as `Label`, are
+    /// rejected, as they would replace the value of the field in the output.
+    pub fn with_unknown_key<S: AsRef<str>>(mut self, key: S, value: Value) -> Result<Self, Error> {
+        let key = key.as_ref();
+        if unknown_keys::known_keys().contains(&key) {
+            return Err(Error::KnownKey(key.to_string()));
+        }
+        self.unknown_keys.insert(key.to_string(), value);
+        Ok(self)
+    }
+
+    /// Rejects a job with unknown keys, like this crate did before it kept them.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let launchd = Launchd::new("LABEL", "/bin/true")?
+    ///         .with_unknown_key("POSIXSpawnType", "Interactive".into())?;
+    ///
+    ///     assert!(launchd.unknown_keys().contains_key("POSIXSpawnType"));
+    ///     assert!(matches!(launchd.deny_unknown_keys(), Err(Error::UnknownKeys(_))));
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn deny_unknown_keys(self) -> Result<Self, Error> {
+        if self.unknown_keys.is_empty() {
+            return Ok(self);
+        }
+        let mut keys: Vec<String> = self.unknown_keys.into_keys().collect();
+        keys.sort();
+        Err(Error::UnknownKeys(keys))
+    }
+}
+
+impl CalendarInterval {
     pub fn with_minute(self, minute: u8) -> Result<Self, Error> {
         if minute > 59 {
             Err(Error::CalendarFieldOutOfBounds(0..=59, minute))
@@ -730,73 +1138,128 @@ impl CalendarInterval {
         }
     }
 
+    pub fn minute(&self) -> Option<u8> {
+        self.minute
+    }
+
+    pub fn hour(&self) -> Option<u8> {
+        self.hour
+    }
+
+    pub fn day(&self) -> Option<u8> {
+        self.day
+    }
+
+    pub fn weekday(&self) -> Option<u8> {
+        self.weekday
+    }
+
+    pub fn month(&self) -> Option<u8> {
+        self.month
+    }
+
+    /// Converts a cron schedule to `CalendarInterval`s that fire at exactly the same minutes.
+    ///
+    /// Like crontab(5), a schedule that restricts both the day of the month and the day of the
+    /// week fires when either of them matches. Those are split into separate dictionaries, one
+    /// set restricting the day of the month and one restricting the day of the week. Note that
+    /// `Schedule::upcoming` of the cron crate requires both to match instead.
+    ///
+    /// Returns an error if the schedule fires on specific seconds other than `0`, or in specific
+    /// years, as launchd can't express either. A schedule that fires every minute is an error as
+    /// well, as it would need an empty dictionary: use `StartInterval` 60 instead.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error};
+    /// use std::str::FromStr;
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let schedule = cron::Schedule::from_str("0 0 12 1 * Mon").unwrap();
+    ///     let intervals = CalendarInterval::from_cron_schedule(schedule)?;
+    ///     assert_eq!(intervals, vec![
+    ///         CalendarInterval::default().with_minute(0)?.with_hour(12)?.with_day(1)?,
+    ///         CalendarInterval::default().with_minute(0)?.with_hour(12)?.with_weekday(1)?,
+    ///     ]);
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
     #[cfg(feature = "cron")]
     pub fn from_cron_schedule(schedule: Schedule) -> Result<Vec<Self>, Error> {
+        if schedule.seconds().count() != 1 || !schedule.seconds().includes(0) {
+            return Err(Error::UnsupportedCronSchedule(
+                "launchd can only fire at the start of a minute".to_string(),
+            ));
+        }
+        if !schedule.years().is_all() {
+            return Err(Error::UnsupportedCronSchedule(
+                "launchd can't restrict the year".to_string(),
+            ));
+        }
+
+        // None stands for an unrestricted field, which is left out of the dictionaries.
+        fn values(spec: &impl TimeUnitSpec, offset: u32) -> Result<Vec<Option<u8>>, Error> {
+            if spec.is_all() {
+                return Ok(vec![None]);
+            }
+            spec.iter()
+                .map(|value| {
+                    (value - offset)
+                        .try_into()
+                        .map(Some)
+                        .map_err(|_| Error::InvalidCronField(value))
+                })
+                .collect()
+        }
+
+        let months = values(schedule.months(), 0)?;
+        let hours = values(schedule.hours(), 0)?;
+        let minutes = values(schedule.minutes(), 0)?;
+        // The cron crate numbers the days of the week from 1 (Sunday) to 7 (Saturday).
+        let weekdays = values(schedule.days_of_week(), 1)?;
+        let days = values(schedule.days_of_month(), 0)?;
+        let days: Vec<(Option<u8>, Option<u8>)> = match (days[0], weekdays[0]) {
+            (Some(_), Some(_)) => days
+                .iter()
+                .map(|day| (*day, None))
+                .chain(weekdays.iter().map(|weekday| (None, *weekday)))
+                .collect(),
+            (_, None) => days.iter().map(|day| (*day, None)).collect(),
+            (None, _) => weekdays.iter().map(|weekday| (None, *weekday)).collect(),
+        };
+
         let mut result_vec = Vec::new();
-        for month in schedule.months().iter() {
-            for weekday in schedule.days_of_week().iter() {
-                for day in schedule.days_of_month().iter() {
-                    for hour in schedule.hours().iter() {
-                        for minute in schedule.minutes().iter() {
-                            let result = Self::default();
-
-                            // TODO: clean this mess up (thiserror + anyhow ?)
-                            if !schedule.months().is_all() {
-                                result.with_month(
-                                    month
-                                        .try_into()
-                                        .map_err(|_| Error::InvalidCronField(month))?,
-                                )?;
-                            }
-                            if !schedule.days_of_week().is_all() {
-                                result.with_weekday(
-                                    weekday
-                                        .try_into()
-                                        .map_err(|_| Error::InvalidCronField(weekday))?,
-                                )?;
-                            }
-                            if !schedule.days_of_month().is_all() {
-                                result.with_day(
-                                    day.try_into().map_err(|_| Error::InvalidCronField(day))?,
-                                )?;
-                            }
-                            if !schedule.hours().is_all() {
-                                result.with_hour(
-                                    hour.try_into().map_err(|_| Error::InvalidCronField(hour))?,
-                                )?;
-                            }
-                            if !schedule.minutes().is_all() {
-                                result.with_minute(
-                                    minute
-                                        .try_into()
-                                        .map_err(|_| Error::InvalidCronField(minute))?,
-                                )?;
-                            }
-
-                            if result.is_initialized() {
-                                result_vec.push(result);
-                            }
-
-                            if schedule.minutes().is_all() {
-                                break;
-                            }
+        for month in &months {
+            for (day, weekday) in &days {
+                for hour in &hours {
+                    for minute in &minutes {
+                        let mut result = Self::default();
+                        if let Some(month) = month {
+                            result = result.with_month(*month)?;
                         }
-                        if schedule.hours().is_all() {
-                            break;
+                        if let Some(day) = day {
+                            result = result.with_day(*day)?;
                         }
-                    }
-                    if schedule.days_of_month().is_all() {
-                        break;
+                        if let Some(weekday) = weekday {
+                            result = result.with_weekday(*weekday)?;
+                        }
+                        if let Some(hour) = hour {
+                            result = result.with_hour(*hour)?;
+                        }
+                        if let Some(minute) = minute {
+                            result = result.with_minute(*minute)?;
+                        }
+                        result_vec.push(result);
                     }
                 }
-                if schedule.days_of_week().is_all() {
-                    break;
-                }
-            }
-            if schedule.months().is_all() {
-                break;
             }
         }
+        if result_vec.contains(&Self::default()) {
+            return Err(Error::UnsupportedCronSchedule(
+                "the schedule fires every minute, use StartInterval 60 instead".to_string(),
+            ));
+        }
         Ok(result_vec)
     }
 }
@@ -804,12 +1267,15 @@ impl CalendarInterval {
 #[cfg(test)]
 mod tests {
 
+    // The path of a plist in tests/resources, for the tests of every module.
     #[cfg(feature = "io")]
     macro_rules! test_case {
         ($fname:expr) => {
             concat!(env!("CARGO_MANIFEST_DIR"), "/tests/resources/", $fname)
         };
     }
+    #[cfg(feature = "io")]
+    pub(crate) use test_case;
 
     use super::*;
 
@@ -870,6 +1336,9 @@ mod tests {
             wait_for_debugger: None,
             watch_paths: None,
             working_directory: None,
+            unknown_keys: HashMap::new(),
+            #[cfg(feature = "io")]
+            key_order: KeyOrdering::default(),
         };
         let test = Launchd::new("Label", "./henk.sh");
         assert!(test.is_ok());
@@ -909,13 +1378,182 @@ mod tests {
         eprintln!("{}", test.unwrap_err());
     }
 
+    #[test]
+    #[cfg(feature = "io")]
+    fn getters_read_a_parsed_plist() {
+        let test = Launchd::from_file(test_case!("keepalive-1.plist")).unwrap();
+        assert_eq!(test.label(), "com.example.agent");
+        assert_eq!(test.program(), None);
+        assert_eq!(
+            test.program_arguments().unwrap()[0],
+            "/usr/local/bin/agent".to_string()
+        );
+        let env: Vec<_> = test
+            .iter_environment_variables()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(env, vec!["AGENT_MODE", "LANG", "PATH"]);
+        assert_eq!(test.umask(), Some(18));
+        assert_eq!(test.nice(), Some(-5));
+        assert_eq!(test.throttle_interval(), Some(30));
+        assert_eq!(test.process_type(), Some(&ProcessType::Background));
+        assert_eq!(test.get_run_at_load(), None);
+        assert_eq!(
+            test.soft_resource_limits()
+                .and_then(|limits| limits.number_of_files()),
+            Some(1024)
+        );
+
+        let intervals = test.start_calendar_intervals().unwrap();
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(
+            (
+                intervals[0].weekday(),
+                intervals[0].hour(),
+                intervals[0].minute()
+            ),
+            (Some(1), Some(9), Some(30))
+        );
+        assert_eq!(intervals[1].day(), None);
+
+        let options = match test.keep_alive() {
+            Some(KeepAliveType::Options(options)) => options,
+            keep_alive => panic!("unexpected KeepAlive: {:?}", keep_alive),
+        };
+        assert_eq!(options.successful_exit(), Some(false));
+        let paths: Vec<_> = options.iter_path_state().collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("/var/run/agent.disabled", false),
+                ("/var/run/agent.enabled", true)
+            ]
+        );
+
+        let test = Launchd::from_file(test_case!("sockets-1.plist")).unwrap();
+        assert_eq!(test.inetd_compatibility(), Some(false));
+        assert_eq!(test.standard_error_path(), Some(Path::new("/dev/null")));
+        let (name, options) = test.sockets().unwrap().iter().next().unwrap();
+        assert_eq!(name, "Listeners");
+        assert_eq!(options.service_name(), Some("ssh"));
+        assert_eq!(options.get_passive(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn unknown_keys_survive_a_round_trip() {
+        let test = Launchd::from_file(test_case!("unknown-keys.plist")).unwrap();
+        let mut keys: Vec<_> = test.unknown_keys().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "AssociatedBundleIdentifiers",
+                "POSIXSpawnType",
+                "_AdditionalProperties"
+            ]
+        );
+        let properties = test.unknown_keys()["_AdditionalProperties"]
+            .as_dictionary()
+            .unwrap();
+        assert!(properties["Created"].as_date().is_some());
+        assert_eq!(
+            properties["Fingerprint"].as_data(),
+            Some(&[0xde, 0xad, 0xbe, 0xef][..])
+        );
+        assert_eq!(
+            test.start_calendar_intervals,
+            Some(vec![CalendarInterval::default()
+                .with_hour(3)
+                .and_then(|ci| ci.with_minute(15))
+                .unwrap()])
+        );
+
+        let mut buffer = Vec::new();
+        test.to_writer_xml(&mut buffer).unwrap();
+        let xml = String::from_utf8(buffer.clone()).unwrap();
+        assert!(xml.contains("<date>2021-06-01T12:00:00Z</date>"));
+        assert!(xml.contains("<data>"));
+        assert!(!xml.contains("$date"));
+        assert_eq!(Launchd::from_bytes(&buffer).unwrap(), test);
+        assert!(matches!(
+            test.deny_unknown_keys(),
+            Err(Error::UnknownKeys(keys)) if keys.len() == 3
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn unknown_keys_survive_a_serde_json_round_trip() {
+        let test = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_run_at_load(true)
+            .with_unknown_key(
+                "AssociatedBundleIdentifiers",
+                vec![Value::from("com.example.app")].into(),
+            )
+            .unwrap();
+        let json = serde_json::to_string(&test).unwrap();
+        assert!(json.ends_with(r#","AssociatedBundleIdentifiers":["com.example.app"]}"#));
+        assert!(!json.contains("null"));
+        assert_eq!(serde_json::from_str::<Launchd>(&json).unwrap(), test);
+
+        let with_nulls = json.replacen('{', r#"{"Extra":null,"#, 1);
+        assert_eq!(serde_json::from_str::<Launchd>(&with_nulls).unwrap(), test);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn modelled_keys_are_not_unknown_keys() {
+        let test = Launchd::new("LABEL", "/bin/true").unwrap();
+        assert!(matches!(
+            test.with_unknown_key("Label", "other".into()),
+            Err(Error::KnownKey(key)) if key == "Label"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn old_start_calendar_intervals_key_is_read() {
+        let plist = br#"<plist version="1.0"><dict>
+            <key>Label</key><string>LABEL</string>
+            <key>StartCalendarIntervals</key><array><dict><key>Minute</key><integer>5</integer></dict></array>
+        </dict></plist>"#;
+        let test = Launchd::from_bytes(plist).unwrap();
+        assert!(test.unknown_keys().is_empty());
+        assert_eq!(
+            test.start_calendar_intervals,
+            Some(vec![CalendarInterval::default().with_minute(5).unwrap()])
+        );
+
+        let mut buffer = Vec::new();
+        test.to_writer_xml(&mut buffer).unwrap();
+        let xml = String::from_utf8(buffer).unwrap();
+        assert!(xml.contains("<key>StartCalendarInterval</key>"));
+    }
+
     #[test]
     #[cfg(feature = "io")]
     fn load_complex_launch_events_1_plist() {
         let test = Launchd::from_file(test_case!("launchevents-1.plist")).unwrap();
 
         match test.launch_events {
-            Some(events) => assert!(events.contains_key("com.apple.distnoted.matching")),
+            Some(events) => {
+                assert_eq!(
+                    events.distnoted_matching()
+                        ["com.apple.callhistory.save.distributed.notification"]
+                        .name(),
+                    "com.apple.callhistory.save.distributed.notification"
+                );
+                assert_eq!(events.notifyd_matching().len(), 4);
+                assert_eq!(
+                    events.notifyd_matching()
+                        ["com.apple.telephonyutilities.callservicesd.kFaceTimeChangedNotification"]
+                        .notification(),
+                    "kFaceTimeChangedNotification"
+                );
+                assert!(events.other_streams().is_empty());
+            }
             _ => panic!("No launch events found"),
         };
     }
@@ -923,24 +1561,14 @@ mod tests {
     #[test]
     #[cfg(feature = "io")]
     fn load_complex_launch_events_2_plist() {
-        let check: LaunchEvents = vec![(
-            "com.apple.iokit.matching".to_string(),
-            vec![(
-                "com.apple.device-attach".to_string(),
-                vec![
-                    ("IOMatchLaunchStream".to_string(), Value::from(true)),
-                    ("idProduct".to_string(), Value::from("*")),
-                    ("idVendor".to_string(), Value::from(4176)),
-                    ("IOProviderClass".to_string(), Value::from("IOUSBDevice")),
-                ]
-                .into_iter()
-                .collect(),
-            )]
-            .into_iter()
-            .collect(),
-        )]
-        .into_iter()
-        .collect();
+        let check = LaunchEvents::new().with_iokit_matching(
+            "com.apple.device-attach",
+            IOKitMatching::new()
+                .with_provider_class("IOUSBDevice")
+                .with_vendor_id(4176)
+                .with_product_id("*")
+                .match_launch_stream(),
+        );
 
         let test = Launchd::from_file(test_case!("launchevents-2.plist")).unwrap();
 
@@ -981,4 +1609,239 @@ mod tests {
             _ => panic!("No launch events found"),
         };
     }
+
+    // The keys of every dictionary in a plist, in order, prefixed with their key path.
+    #[cfg(feature = "io")]
+    fn key_sequence(value: &plist::Value, path: &str, keys: &mut Vec<String>) {
+        match value {
+            plist::Value::Dictionary(dictionary) => {
+                for (key, value) in dictionary {
+                    let path = format!("{}.{}", path, key);
+                    keys.push(path.clone());
+                    key_sequence(value, &path, keys);
+                }
+            }
+            plist::Value::Array(array) => {
+                for (index, value) in array.iter().enumerate() {
+                    key_sequence(value, &format!("{}[{}]", path, index), keys);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "io")]
+    pub(crate) fn corpus() -> Vec<std::path::PathBuf> {
+        let mut files: Vec<_> = std::fs::read_dir(test_case!(""))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "plist"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty());
+        files
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn round_trip_is_the_identity_on_the_corpus() {
+        for file in corpus() {
+            for order in [KeyOrder::Declared, KeyOrder::Original, KeyOrder::Sorted] {
+                let test = Launchd::from_file(&file).unwrap().with_key_order(order);
+
+                let mut xml = Vec::new();
+                test.to_writer_xml(&mut xml).unwrap();
+                let parsed = Launchd::from_bytes(&xml).unwrap();
+                assert_eq!(parsed, test, "{:?} in {:?} order", file, order);
+
+                let mut binary = Vec::new();
+                test.to_writer_binary(&mut binary).unwrap();
+                assert_eq!(Launchd::from_bytes(&binary).unwrap(), test);
+
+                // Writing the parsed job again gives the same bytes.
+                let mut again = Vec::new();
+                parsed
+                    .with_key_order(order)
+                    .to_writer_xml(&mut again)
+                    .unwrap();
+                assert_eq!(xml, again, "{:?} in {:?} order", file, order);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn original_key_order_is_kept() {
+        for file in corpus() {
+            let mut expected = Vec::new();
+            key_sequence(&plist::Value::from_file(&file).unwrap(), "", &mut expected);
+
+            let mut xml = Vec::new();
+            Launchd::from_file(&file)
+                .unwrap()
+                .with_key_order(KeyOrder::Original)
+                .to_writer_xml(&mut xml)
+                .unwrap();
+            let mut keys = Vec::new();
+            key_sequence(
+                &plist::Value::from_reader_xml(&xml[..]).unwrap(),
+                "",
+                &mut keys,
+            );
+            assert_eq!(keys, expected, "{:?}", file);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn sorted_key_order_sorts_every_dictionary() {
+        let mut xml = Vec::new();
+        Launchd::from_file(test_case!("keepalive-1.plist"))
+            .unwrap()
+            .with_key_order(KeyOrder::Sorted)
+            .to_writer_xml(&mut xml)
+            .unwrap();
+        let mut keys = Vec::new();
+        key_sequence(
+            &plist::Value::from_reader_xml(&xml[..]).unwrap(),
+            "",
+            &mut keys,
+        );
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn declared_key_order_is_deterministic() {
+        let variables = [("PATH", "/bin"), ("HOME", "/var/root"), ("LANG", "C")];
+        let write = |variables: &mut dyn Iterator<Item = &(&str, &str)>| {
+            let launchd = Launchd::new("LABEL", "/bin/true")
+                .unwrap()
+                .with_environment_variables(
+                    variables
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                );
+            let mut xml = Vec::new();
+            launchd.to_writer_xml(&mut xml).unwrap();
+            String::from_utf8(xml).unwrap()
+        };
+
+        let forward = write(&mut variables.iter());
+        assert_eq!(forward, write(&mut variables.iter().rev()));
+        assert!(forward.find("HOME") < forward.find("LANG"));
+        assert!(forward.find("LANG") < forward.find("PATH"));
+    }
+
+    // Fire times of a cron expression following crontab(5): if both the day of the month and the
+    // day of the week are restricted, the expression fires when either matches.
+    #[cfg(feature = "cron")]
+    fn crontab_fire_times(
+        expression: &str,
+        start: chrono::NaiveDateTime,
+        end: chrono::NaiveDateTime,
+    ) -> Vec<chrono::NaiveDateTime> {
+        use chrono::{TimeZone, Utc};
+        use std::str::FromStr;
+
+        let fields: Vec<&str> = expression.split(' ').collect();
+        let expressions = if fields[3] != "*" && fields[5] != "*" {
+            let mut only_days = fields.clone();
+            only_days[5] = "*";
+            let mut only_weekdays = fields.clone();
+            only_weekdays[3] = "*";
+            vec![only_days.join(" "), only_weekdays.join(" ")]
+        } else {
+            vec![expression.to_string()]
+        };
+
+        let mut times: Vec<_> = expressions
+            .iter()
+            .flat_map(|expression| {
+                Schedule::from_str(expression)
+                    .unwrap()
+                    .after(&Utc.from_utc_datetime(&start))
+                    .map(|time| time.naive_utc())
+                    .take_while(|time| *time < end)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        times.sort();
+        times.dedup();
+        times
+    }
+
+    #[test]
+    #[cfg(feature = "cron")]
+    fn cron_schedules_fire_at_the_same_times() {
+        use chrono::NaiveDate;
+        use std::str::FromStr;
+
+        let expressions = [
+            "0 0 * * * *",
+            "0 */5 9-17 * * Mon-Fri",
+            "0 30 2 1 * *",
+            "0 15 10 * Jan,Jul *",
+            "0 0 0 1,15 * Mon",
+            "0 0 0 31 * Sun",
+            "0 0 8-10 */10 * Sat,Sun",
+            "0 0 12 29 Feb *",
+            "0 0,30 6 13 * Fri",
+            "0 0 0 * * Sun",
+            "0 0 0 * * Sat",
+        ];
+        // 2024 is a leap year and starts on a Monday.
+        let start = NaiveDate::from_ymd_opt(2023, 12, 31)
+            .unwrap()
+            .and_hms_opt(23, 59, 0)
+            .unwrap();
+        let end = NaiveDate::from_ymd_opt(2025, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        for expression in expressions.iter() {
+            let schedule = Schedule::from_str(expression).unwrap();
+            let intervals = CalendarInterval::from_cron_schedule(schedule).unwrap();
+            let launchd_times: Vec<_> = FireTimes::new(&intervals, start)
+                .take_while(|time| *time < end)
+                .collect();
+
+            assert!(!launchd_times.is_empty(), "{} never fires", expression);
+            assert_eq!(
+                launchd_times,
+                crontab_fire_times(expression, start, end),
+                "{} fires at different times",
+                expression
+            );
+            assert!(CalendarInterval::equivalent(
+                &intervals,
+                &CalendarInterval::minimize(intervals.clone())
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cron")]
+    fn cron_schedules_launchd_cannot_express() {
+        use std::str::FromStr;
+
+        for expression in [
+            "*/30 * * * * *",
+            "15 0 * * * *",
+            "0 0 0 1 1 * 2030",
+            "0 * * * * *",
+        ]
+        .iter()
+        {
+            let schedule = Schedule::from_str(expression).unwrap();
+            assert!(matches!(
+                CalendarInterval::from_cron_schedule(schedule),
+                Err(Error::UnsupportedCronSchedule(_))
+            ));
+        }
+    }
 }
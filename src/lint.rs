@@ -0,0 +1,176 @@
+// Lints for deprecated keys and discouraged combinations of keys.
+//
+// The deprecated keys are only kept in the struct so old plists can still be read. launchd
+// ignores most of them, except OnDemand, which is the inverse of a boolean KeepAlive.
+use crate::key_path::KeyPath;
+use crate::validate::{Diagnostic, Severity};
+use crate::{KeepAliveType, Launchd};
+
+// launchd doesn't spawn a job more than once every 10 seconds, unless ThrottleInterval is lower.
+const DEFAULT_THROTTLE_INTERVAL: u32 = 10;
+
+impl Launchd {
+    /// Returns a warning for every deprecated key in this job, with a suggested replacement, and
+    /// for combinations of keys that probably don't do what was intended.
+    ///
+    /// The deprecated keys can be replaced automatically with [`Launchd::modernize`].
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let launchd = Launchd::new("LABEL", "/bin/true")?.with_throttle_interval(1);
+    ///     let lints: Vec<String> = launchd.lint().iter().map(ToString::to_string).collect();
+    ///
+    ///     assert_eq!(lints, vec![
+    ///         "warning: ThrottleInterval: is below the default of 10 seconds, so a crashing job is respawned in a tight loop",
+    ///     ]);
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut lints = Vec::new();
+        let mut warn = |key: &str, message: &str| {
+            lints.push(Diagnostic::new(
+                Severity::Warning,
+                KeyPath::new().key(key),
+                message,
+            ))
+        };
+
+        if let Some(on_demand) = self.on_demand {
+            let replacement = if self.keep_alive.is_some() {
+                "is deprecated and overridden by KeepAlive, remove it".to_string()
+            } else {
+                format!("is deprecated, use KeepAlive = {} instead", !on_demand)
+            };
+            warn("OnDemand", &replacement);
+        }
+        if self.service_ipc.is_some() {
+            warn("ServiceIPC", "is deprecated and has no effect, remove it");
+        }
+        if self.hopefully_exits_first.is_some() {
+            warn(
+                "HopefullyExitsFirst",
+                "is deprecated and has no effect, remove it",
+            );
+        }
+        if self.hopefully_exits_last.is_some() {
+            warn(
+                "HopefullyExitsLast",
+                "is deprecated and has no effect, remove it",
+            );
+        }
+        if self.legacy_timers.is_some() {
+            warn("LegacyTimers", "is deprecated and has no effect, remove it");
+        }
+
+        let keep_alive = match &self.keep_alive {
+            Some(KeepAliveType::Enabled(enabled)) => *enabled,
+            Some(KeepAliveType::Options(_)) => true,
+            None => self.on_demand == Some(false),
+        };
+        if keep_alive && self.launch_only_once == Some(true) {
+            warn(
+                "LaunchOnlyOnce",
+                "prevents KeepAlive from restarting the job, remove one of them",
+            );
+        }
+        if let Some(interval) = self.throttle_interval {
+            if interval < DEFAULT_THROTTLE_INTERVAL {
+                warn(
+                    "ThrottleInterval",
+                    &format!(
+                        "is below the default of {} seconds, so a crashing job is respawned in a tight loop",
+                        DEFAULT_THROTTLE_INTERVAL
+                    ),
+                );
+            }
+        }
+        lints
+    }
+
+    /// Replaces the deprecated keys reported by [`Launchd::lint`]: `OnDemand` becomes the
+    /// equivalent `KeepAlive` (unless `KeepAlive` is already set), and keys without an effect
+    /// are removed. Discouraged combinations of keys are left alone, as there is no single fix.
+    pub fn modernize(&mut self) {
+        if let Some(on_demand) = self.on_demand.take() {
+            if self.keep_alive.is_none() && !on_demand {
+                self.keep_alive = Some(KeepAliveType::Enabled(true));
+            }
+        }
+        self.service_ipc = None;
+        self.hopefully_exits_first = None;
+        self.hopefully_exits_last = None;
+        self.legacy_timers = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deprecated() -> Launchd {
+        Launchd {
+            on_demand: Some(false),
+            service_ipc: Some(true),
+            hopefully_exits_last: Some(true),
+            legacy_timers: Some(false),
+            ..Launchd::new("LABEL", "/bin/true").unwrap()
+        }
+    }
+
+    #[test]
+    fn deprecated_keys_are_flagged() {
+        let paths: Vec<_> = deprecated()
+            .lint()
+            .iter()
+            .map(|lint| lint.path().to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "OnDemand",
+                "ServiceIPC",
+                "HopefullyExitsLast",
+                "LegacyTimers"
+            ]
+        );
+    }
+
+    #[test]
+    fn modernize_replaces_deprecated_keys() {
+        let mut launchd = deprecated();
+        launchd.modernize();
+        assert_eq!(
+            launchd,
+            Launchd::new("LABEL", "/bin/true")
+                .unwrap()
+                .with_keep_alive(true.into())
+        );
+        assert!(launchd.lint().is_empty());
+
+        let mut launchd = Launchd {
+            on_demand: Some(true),
+            ..Launchd::new("LABEL", "/bin/true").unwrap()
+        };
+        launchd.modernize();
+        assert_eq!(launchd, Launchd::new("LABEL", "/bin/true").unwrap());
+    }
+
+    #[test]
+    fn discouraged_combinations() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_keep_alive(true.into())
+            .launch_only_once()
+            .with_throttle_interval(10);
+        let messages: Vec<_> = launchd.lint().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec!["warning: LaunchOnlyOnce: prevents KeepAlive from restarting the job, remove one of them"]
+        );
+    }
+}
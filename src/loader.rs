@@ -0,0 +1,726 @@
+// Loading every job in a directory, or in the launchd domains of a Mac that is mounted or copied
+// under a prefix, e.g. `Loader::new().with_prefix("/srv/audit/host-1")`.
+//
+// A file that can't be read is reported with its error, and the other files are loaded anyway.
+// Jobs are indexed by label. launchd refuses to load a second job with a label that is already
+// loaded, so only the first file (in the order of the domains, then of the file names) is indexed,
+// and the others are reported as duplicates.
+//
+// The files are parsed on a pool of threads, which send the jobs back over a bounded channel, so
+// `Loader::parse` can stream a large corpus without holding more than a few jobs per thread. A
+// panic while parsing a file is caught and reported as the error of that file, so every file is
+// sent back.
+use crate::{Error, Launchd};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The directories, relative to the root of a Mac, that launchd loads jobs from.
+pub const DOMAINS: [&str; 4] = [
+    "Library/LaunchAgents",
+    "Library/LaunchDaemons",
+    "System/Library/LaunchAgents",
+    "System/Library/LaunchDaemons",
+];
+
+/// Finds and parses the plists in a set of domain directories.
+///
+/// Usage:
+/// ```no_run
+/// use launchd::Loader;
+///
+/// let jobs = Loader::new().with_prefix("/srv/audit/host-1").load();
+/// for issue in jobs.issues() {
+///     eprintln!("{}", issue);
+/// }
+/// if let Some(job) = jobs.get("com.openssh.sshd") {
+///     println!("{}: {:?}", job.path().display(), job.launchd().program_arguments());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loader {
+    prefix: PathBuf,
+    domains: Vec<PathBuf>,
+    // A missing domain is skipped, unless it was asked for with Loader::directory.
+    required: bool,
+    // 0 is one thread per core.
+    threads: usize,
+}
+
+impl Loader {
+    /// A loader for the domains in `DOMAINS`, under `/`.
+    pub fn new() -> Self {
+        Loader {
+            prefix: PathBuf::from("/"),
+            domains: DOMAINS.iter().map(PathBuf::from).collect(),
+            required: false,
+            threads: 0,
+        }
+    }
+
+    /// A loader for the plists in a single directory.
+    pub fn directory<P: AsRef<Path>>(directory: P) -> Self {
+        Loader {
+            prefix: directory.as_ref().to_path_buf(),
+            domains: vec![PathBuf::new()],
+            required: true,
+            threads: 0,
+        }
+    }
+
+    pub fn with_prefix<P: AsRef<Path>>(self, prefix: P) -> Self {
+        Loader {
+            prefix: prefix.as_ref().to_path_buf(),
+            ..self
+        }
+    }
+
+    /// Replaces the domains, which are relative to the prefix.
+    pub fn with_domains<I, P>(self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Loader {
+            domains: domains
+                .into_iter()
+                .map(|domain| domain.as_ref().to_path_buf())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Adds a domain, e.g. `Users/admin/Library/LaunchAgents`.
+    pub fn with_domain<P: AsRef<Path>>(mut self, domain: P) -> Self {
+        self.domains.push(domain.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the number of threads that parse the files. The default, 0, uses one thread per core.
+    pub fn with_threads(self, threads: usize) -> Self {
+        Loader { threads, ..self }
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    pub fn domains(&self) -> &[PathBuf] {
+        &self.domains
+    }
+
+    /// Parses every `.plist` file in the domains. Subdirectories and hidden files (such as the
+    /// `._*.plist` files that copying from a Mac can leave behind) are skipped.
+    pub fn load(&self) -> Jobs {
+        let mut parsed = self.parse();
+        let mut files: Vec<_> = parsed.by_ref().collect();
+        files.sort_by_key(|file| file.index);
+
+        let mut jobs = Jobs {
+            jobs: BTreeMap::new(),
+            issues: std::mem::take(&mut parsed.issues),
+            stats: parsed.stats(),
+        };
+        for file in files {
+            jobs.insert(file.domain, file.path, file.result);
+        }
+        jobs
+    }
+
+    /// Parses the files like `load`, but streams them in the order they are parsed instead of
+    /// indexing them, so a large corpus can be processed as it is read.
+    ///
+    /// Usage:
+    /// ```no_run
+    /// use launchd::Loader;
+    ///
+    /// let mut parsed = Loader::directory("/srv/audit/plists").with_threads(8).parse();
+    /// for file in parsed.by_ref() {
+    ///     if let Err(error) = file.launchd() {
+    ///         eprintln!("{}: {} ({:?})", file.path().display(), error, file.elapsed());
+    ///     }
+    /// }
+    /// println!("{}", parsed.stats());
+    /// ```
+    pub fn parse(&self) -> Parsed {
+        let mut issues = Vec::new();
+        let files = self.files(&mut issues);
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            threads => threads,
+        };
+        Parsed::spawn(files, issues, threads)
+    }
+
+    // Lists the plists of every domain, as (domain, path), and reports the unreadable domains.
+    fn files(&self, issues: &mut Vec<LoadIssue>) -> Vec<(PathBuf, PathBuf)> {
+        let mut files = Vec::new();
+        for domain in &self.domains {
+            let directory = self.prefix.join(domain);
+            let entries = match std::fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound && !self.required => {
+                    continue
+                }
+                Err(error) => {
+                    issues.push(LoadIssue::Unreadable(directory, Error::Io(error)));
+                    continue;
+                }
+            };
+
+            let mut paths = Vec::new();
+            for entry in entries {
+                match entry {
+                    Ok(entry) => paths.push(entry.path()),
+                    Err(error) => {
+                        issues.push(LoadIssue::Unreadable(directory.clone(), Error::Io(error)))
+                    }
+                }
+            }
+            paths.retain(|path| {
+                let hidden = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with('.'));
+                !hidden && path.extension().is_some_and(|ext| ext == "plist") && path.is_file()
+            });
+            paths.sort();
+            files.extend(paths.into_iter().map(|path| (domain.clone(), path)));
+        }
+        files
+    }
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The jobs found by a `Loader`, indexed by label, and the problems it ran into.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: BTreeMap<String, LoadedJob>,
+    issues: Vec<LoadIssue>,
+    stats: ParseStats,
+}
+
+impl Jobs {
+    pub fn get(&self, label: &str) -> Option<&LoadedJob> {
+        self.jobs.get(label)
+    }
+
+    /// Iterates over the jobs in label order.
+    pub fn iter(&self) -> impl Iterator<Item = &LoadedJob> {
+        self.jobs.values()
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.jobs.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// The unreadable files and directories, duplicate labels and label/file name mismatches, in
+    /// the order they were found.
+    pub fn issues(&self) -> &[LoadIssue] {
+        &self.issues
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn stats(&self) -> ParseStats {
+        self.stats
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        domain: PathBuf,
+        path: PathBuf,
+        launchd: Result<Launchd, Error>,
+    ) {
+        let launchd = match launchd {
+            Ok(launchd) => launchd,
+            Err(error) => {
+                self.issues.push(LoadIssue::Unreadable(path, error));
+                return;
+            }
+        };
+
+        let label = launchd.label().to_string();
+        if path.file_stem().and_then(|stem| stem.to_str()) != Some(&label) {
+            self.issues
+                .push(LoadIssue::LabelMismatch(label.clone(), path.clone()));
+        }
+        match self.jobs.get(&label) {
+            Some(first) => {
+                let first = first.path.clone();
+                self.issues
+                    .push(LoadIssue::DuplicateLabel(label, path, first));
+            }
+            None => {
+                self.jobs.insert(
+                    label,
+                    LoadedJob {
+                        domain,
+                        path,
+                        launchd,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Jobs {
+    type Item = &'a LoadedJob;
+    type IntoIter = std::collections::btree_map::Values<'a, String, LoadedJob>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.jobs.values()
+    }
+}
+
+/// A job and the file it was read from.
+#[derive(Debug, PartialEq)]
+pub struct LoadedJob {
+    domain: PathBuf,
+    path: PathBuf,
+    launchd: Launchd,
+}
+
+impl LoadedJob {
+    /// The domain the file was found in, relative to the prefix of the loader.
+    pub fn domain(&self) -> &Path {
+        &self.domain
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn launchd(&self) -> &Launchd {
+        &self.launchd
+    }
+
+    pub fn into_launchd(self) -> Launchd {
+        self.launchd
+    }
+}
+
+/// The files of a `Loader`, in the order they are parsed. See `Loader::parse`.
+#[derive(Debug)]
+pub struct Parsed {
+    receiver: Receiver<ParsedFile>,
+    issues: Vec<LoadIssue>,
+    remaining: usize,
+    started: Instant,
+    stats: ParseStats,
+}
+
+impl Parsed {
+    fn spawn(files: Vec<(PathBuf, PathBuf)>, issues: Vec<LoadIssue>, threads: usize) -> Self {
+        let remaining = files.len();
+        let threads = threads.clamp(1, remaining.max(1));
+        let (sender, receiver) = mpsc::sync_channel(2 * threads);
+        let queue = Arc::new(Mutex::new(files.into_iter().enumerate()));
+        for _ in 0..threads {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                // The lock is released before parsing, so the threads only wait for each other to
+                // take a path.
+                let next = queue.lock().unwrap().next();
+                let (index, (domain, path)) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                // The receiver is gone when the iterator is dropped early.
+                if sender.send(ParsedFile::parse(index, domain, path)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Parsed {
+            receiver,
+            issues,
+            remaining,
+            started: Instant::now(),
+            stats: ParseStats::default(),
+        }
+    }
+
+    /// The domains that couldn't be read.
+    pub fn issues(&self) -> &[LoadIssue] {
+        &self.issues
+    }
+
+    /// The statistics of the files parsed so far.
+    pub fn stats(&self) -> ParseStats {
+        ParseStats {
+            elapsed: self.started.elapsed(),
+            ..self.stats
+        }
+    }
+}
+
+impl Iterator for Parsed {
+    type Item = ParsedFile;
+
+    fn next(&mut self) -> Option<ParsedFile> {
+        let file = self.receiver.recv().ok()?;
+        self.remaining -= 1;
+        self.stats.files += 1;
+        self.stats.failed += usize::from(file.result.is_err());
+        self.stats.bytes += file.bytes;
+        self.stats.parse_time += file.elapsed;
+        Some(file)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+// Every file is sent back, as a panic while parsing is sent as an error.
+impl ExactSizeIterator for Parsed {}
+
+/// A file parsed by `Loader::parse`, and how long parsing it took.
+#[derive(Debug)]
+pub struct ParsedFile {
+    index: usize,
+    domain: PathBuf,
+    path: PathBuf,
+    bytes: u64,
+    elapsed: Duration,
+    result: Result<Launchd, Error>,
+}
+
+impl ParsedFile {
+    fn parse(index: usize, domain: PathBuf, path: PathBuf) -> Self {
+        let started = Instant::now();
+        let (bytes, result) = match std::fs::read(&path) {
+            Ok(bytes) => (
+                bytes.len() as u64,
+                catch_panic(|| Launchd::from_bytes(&bytes)),
+            ),
+            Err(error) => (0, Err(Error::Io(error))),
+        };
+        ParsedFile {
+            index,
+            domain,
+            path,
+            bytes,
+            elapsed: started.elapsed(),
+            result,
+        }
+    }
+
+    pub fn domain(&self) -> &Path {
+        &self.domain
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the file.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The time it took to read and parse the file.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn launchd(&self) -> Result<&Launchd, &Error> {
+        self.result.as_ref()
+    }
+
+    pub fn into_launchd(self) -> Result<Launchd, Error> {
+        self.result
+    }
+}
+
+// Turns a panic into an error, so the file is reported rather than lost with its thread.
+fn catch_panic<F: FnOnce() -> Result<Launchd, Error> + panic::UnwindSafe>(
+    parse: F,
+) -> Result<Launchd, Error> {
+    panic::catch_unwind(parse).unwrap_or_else(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_default(),
+        };
+        Err(Error::ParserPanicked(message))
+    })
+}
+
+/// The throughput of a `Loader`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    files: usize,
+    failed: usize,
+    bytes: u64,
+    elapsed: Duration,
+    parse_time: Duration,
+}
+
+impl ParseStats {
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// The files that couldn't be read or parsed.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The wall-clock time since the files started to be parsed.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The time spent parsing, summed over the files. It is larger than `elapsed` when the
+    /// threads parse in parallel.
+    pub fn parse_time(&self) -> Duration {
+        self.parse_time
+    }
+
+    pub fn files_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.files as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for ParseStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} files ({} failed, {} bytes) in {:.2?}, {:.0} files/s",
+            self.files,
+            self.failed,
+            self.bytes,
+            self.elapsed,
+            self.files_per_second()
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadIssue {
+    /// A file or directory (path) couldn't be read or parsed.
+    Unreadable(PathBuf, Error),
+    /// A file (path) uses the same label as a file that was loaded earlier (path).
+    DuplicateLabel(String, PathBuf, PathBuf),
+    /// The name of a file (path) is not the label followed by `.plist`.
+    LabelMismatch(String, PathBuf),
+}
+
+impl LoadIssue {
+    pub fn path(&self) -> &Path {
+        match self {
+            LoadIssue::Unreadable(path, _)
+            | LoadIssue::DuplicateLabel(_, path, _)
+            | LoadIssue::LabelMismatch(_, path) => path,
+        }
+    }
+}
+
+impl fmt::Display for LoadIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadIssue::Unreadable(path, error) => write!(f, "{}: {}", path.display(), error),
+            LoadIssue::DuplicateLabel(label, path, first) => write!(
+                f,
+                "{}: {} is already loaded from {}",
+                path.display(),
+                label,
+                first.display()
+            ),
+            LoadIssue::LabelMismatch(label, path) => write!(
+                f,
+                "{}: the file name doesn't match the label {}",
+                path.display(),
+                label
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_case;
+
+    #[test]
+    fn domains_are_loaded_under_a_prefix() {
+        let prefix = std::env::temp_dir().join(format!("launchd-loader-{}", std::process::id()));
+        let copy = |fixture: &str, path: &str| {
+            let path = prefix.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::copy(fixture, path).unwrap();
+        };
+        copy(
+            test_case!("keepalive-1.plist"),
+            "Library/LaunchAgents/com.example.agent.plist",
+        );
+        copy(
+            test_case!("keepalive-1.plist"),
+            "Library/LaunchAgents/._com.example.agent.plist",
+        );
+        copy(
+            test_case!("unknown-keys.plist"),
+            "Library/LaunchDaemons/com.example.agent.plist",
+        );
+        copy(
+            test_case!("sockets-1.plist"),
+            "System/Library/LaunchDaemons/ssh.plist",
+        );
+        copy(
+            test_case!("keepalive-1.yaml"),
+            "System/Library/LaunchDaemons/broken.plist",
+        );
+        copy(
+            test_case!("keepalive-1.yaml"),
+            "System/Library/LaunchDaemons/README.yaml",
+        );
+
+        let jobs = Loader::new().with_prefix(&prefix).load();
+        std::fs::remove_dir_all(&prefix).unwrap();
+
+        assert_eq!(
+            jobs.labels().collect::<Vec<_>>(),
+            ["com.example.agent", "com.openssh.sshd"]
+        );
+        let agent = jobs.get("com.example.agent").unwrap();
+        assert_eq!(agent.domain(), Path::new("Library/LaunchAgents"));
+        assert_eq!(
+            agent.launchd(),
+            &Launchd::from_file(test_case!("keepalive-1.plist")).unwrap()
+        );
+
+        let issues: Vec<_> = jobs
+            .issues()
+            .iter()
+            .map(|issue| issue.path().strip_prefix(&prefix).unwrap())
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                Path::new("Library/LaunchDaemons/com.example.agent.plist"),
+                Path::new("System/Library/LaunchDaemons/broken.plist"),
+                Path::new("System/Library/LaunchDaemons/ssh.plist"),
+            ]
+        );
+        assert!(matches!(jobs.issues()[0], LoadIssue::DuplicateLabel(..)));
+        assert!(matches!(jobs.issues()[1], LoadIssue::Unreadable(..)));
+        assert!(matches!(
+            &jobs.issues()[2],
+            LoadIssue::LabelMismatch(label, _) if label == "com.openssh.sshd"
+        ));
+    }
+
+    #[test]
+    fn files_are_streamed_from_the_threads() {
+        let mut parsed = Loader::directory(test_case!("")).with_threads(3).parse();
+        assert_eq!(parsed.len(), 7);
+        let mut files: Vec<_> = parsed.by_ref().collect();
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(files.len(), 7);
+        for file in &files {
+            assert_eq!(
+                file.launchd().unwrap(),
+                &Launchd::from_file(file.path()).unwrap()
+            );
+            assert_eq!(file.bytes(), std::fs::metadata(file.path()).unwrap().len());
+        }
+
+        let stats = parsed.stats();
+        assert_eq!(stats.files(), 7);
+        assert_eq!(stats.failed(), 0);
+        assert_eq!(
+            stats.bytes(),
+            files.iter().map(ParsedFile::bytes).sum::<u64>()
+        );
+        assert_eq!(
+            stats.parse_time(),
+            files.iter().map(ParsedFile::elapsed).sum::<Duration>()
+        );
+
+        // Dropping the iterator stops the threads instead of blocking them.
+        let first = Loader::directory(test_case!(""))
+            .with_threads(1)
+            .parse()
+            .next();
+        assert!(first.is_some());
+
+        // The threads don't change which file wins a duplicate label.
+        let describe = |jobs: &Jobs| -> Vec<String> {
+            jobs.issues().iter().map(ToString::to_string).collect()
+        };
+        let jobs = Loader::directory(test_case!("")).with_threads(1).load();
+        for threads in 2..5 {
+            let parallel = Loader::directory(test_case!(""))
+                .with_threads(threads)
+                .load();
+            assert_eq!(describe(&parallel), describe(&jobs));
+            assert!(parallel.iter().eq(jobs.iter()));
+        }
+        assert_eq!(jobs.stats().files(), 7);
+    }
+
+    #[test]
+    fn a_single_directory_can_be_loaded() {
+        let jobs = Loader::directory(test_case!("")).load();
+        assert_eq!(jobs.len(), 6);
+        assert!(jobs.issues().iter().all(|issue| matches!(
+            issue,
+            LoadIssue::LabelMismatch(..) | LoadIssue::DuplicateLabel(..)
+        )));
+
+        let jobs = Loader::directory(test_case!("missing")).load();
+        assert!(jobs.is_empty());
+        assert!(matches!(
+            jobs.issues(),
+            [LoadIssue::Unreadable(_, Error::Io(_))]
+        ));
+        assert!(Loader::new()
+            .with_prefix(test_case!("missing"))
+            .load()
+            .is_clean());
+    }
+
+    #[test]
+    fn a_panic_is_an_error() {
+        assert!(catch_panic(|| Launchd::new("LABEL", "/bin/true")).is_ok());
+        let error = catch_panic(|| panic!("unexpected {}", "value")).unwrap_err();
+        assert!(matches!(error, Error::ParserPanicked(message) if message == "unexpected value"));
+    }
+}
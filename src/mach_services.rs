@@ -41,6 +41,14 @@ impl MachServiceOptions {
     pub fn hide_until_check_in(self) -> Self {
         self.with_hide_until_check_in(true)
     }
+
+    pub fn get_reset_at_close(&self) -> Option<bool> {
+        self.reset_at_close
+    }
+
+    pub fn get_hide_until_check_in(&self) -> Option<bool> {
+        self.hide_until_check_in
+    }
 }
 
 impl From<MachServiceOptions> for MachServiceEntry {
@@ -0,0 +1,605 @@
+// Layering of jobs: a partial job (an overlay) on top of a base job, and three-way merges of a
+// locally edited job with a new version of the job it was derived from.
+//
+// An overlay only sets the keys it changes. Dictionaries are merged key by key, and everything
+// else (including arrays such as ProgramArguments) is replaced, with two exceptions: sockets are
+// merged by name, and StartCalendarIntervals can be appended instead of replaced.
+#[cfg(feature = "io")]
+use crate::{Error, KeyPath, Value};
+use crate::{KeepAliveOptions, KeepAliveType, LaunchEvents, Launchd, ResourceLimits, Sockets};
+use std::collections::HashMap;
+#[cfg(feature = "io")]
+use std::fmt;
+use std::hash::Hash;
+
+/// How [`Launchd::merge_with`] layers an overlay on a job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    append_calendar_intervals: bool,
+}
+
+impl MergeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the `StartCalendarInterval`s of the overlay that the job doesn't have yet, instead of
+    /// replacing them.
+    pub fn with_append_calendar_intervals(mut self, value: bool) -> Self {
+        self.append_calendar_intervals = value;
+        self
+    }
+
+    pub fn append_calendar_intervals(self) -> Self {
+        self.with_append_calendar_intervals(true)
+    }
+}
+
+impl Launchd {
+    /// Layers an overlay on this job: every key that is set in the overlay replaces the key in
+    /// this job, except for dictionaries, which are merged key by key. Sockets are merged by name,
+    /// and `KeepAlive` and resource limits by field. The label is only replaced if the overlay's
+    /// label isn't empty, so an overlay can be built from `Launchd::default()`.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    /// use std::collections::HashMap;
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut job = Launchd::new("com.example.agent", "/usr/local/bin/agent")?
+    ///         .with_environment_variables(HashMap::from([
+    ///             ("LANG".to_string(), "C".to_string()),
+    ///             ("MODE".to_string(), "production".to_string()),
+    ///         ]));
+    ///     let staging = Launchd::default()
+    ///         .with_environment_variables(HashMap::from([
+    ///             ("MODE".to_string(), "staging".to_string()),
+    ///         ]))
+    ///         .with_standard_out_path("/var/log/agent-staging.log")?;
+    ///
+    ///     job.merge(staging);
+    ///     assert_eq!(job.label(), "com.example.agent");
+    ///     assert_eq!(job.environment_variables().unwrap()["LANG"], "C");
+    ///     assert_eq!(job.environment_variables().unwrap()["MODE"], "staging");
+    ///     assert!(job.standard_out_path().is_some());
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn merge(&mut self, overlay: Launchd) {
+        self.merge_with(overlay, MergeOptions::default())
+    }
+
+    pub fn merge_with(&mut self, overlay: Launchd, options: MergeOptions) {
+        // Destructured, so that a new field can't be forgotten here.
+        let Launchd {
+            label,
+            disabled,
+            user_name,
+            group_name,
+            inetd_compatibility,
+            limit_load_to_hosts,
+            limit_load_from_hosts,
+            limit_load_to_session_type,
+            limit_load_to_hardware,
+            limit_load_from_hardware,
+            program,
+            bundle_program,
+            program_arguments,
+            enable_globbing,
+            enable_transactions,
+            enable_pressured_exit,
+            on_demand,
+            service_ipc,
+            keep_alive,
+            run_at_load,
+            root_directory,
+            working_directory,
+            environment_variables,
+            umask,
+            time_out,
+            exit_time_out,
+            throttle_interval,
+            init_groups,
+            watch_paths,
+            queue_directories,
+            start_on_mount,
+            start_interval,
+            start_calendar_intervals,
+            standard_in_path,
+            standard_out_path,
+            standard_error_path,
+            debug,
+            wait_for_debugger,
+            soft_resource_limits,
+            hard_resource_limits,
+            nice,
+            process_type,
+            abandon_process_group,
+            low_priority_io,
+            low_priority_background_io,
+            materialize_dataless_files,
+            launch_only_once,
+            mach_services,
+            sockets,
+            launch_events,
+            hopefully_exits_last,
+            hopefully_exits_first,
+            session_create,
+            legacy_timers,
+            unknown_keys,
+            #[cfg(feature = "io")]
+                key_order: _,
+        } = overlay;
+
+        if !label.is_empty() {
+            self.label = label;
+        }
+        replace(&mut self.disabled, disabled);
+        replace(&mut self.user_name, user_name);
+        replace(&mut self.group_name, group_name);
+        merge_map(&mut self.inetd_compatibility, inetd_compatibility);
+        replace(&mut self.limit_load_to_hosts, limit_load_to_hosts);
+        replace(&mut self.limit_load_from_hosts, limit_load_from_hosts);
+        replace(
+            &mut self.limit_load_to_session_type,
+            limit_load_to_session_type,
+        );
+        merge_map(&mut self.limit_load_to_hardware, limit_load_to_hardware);
+        merge_map(&mut self.limit_load_from_hardware, limit_load_from_hardware);
+        replace(&mut self.program, program);
+        replace(&mut self.bundle_program, bundle_program);
+        replace(&mut self.program_arguments, program_arguments);
+        replace(&mut self.enable_globbing, enable_globbing);
+        replace(&mut self.enable_transactions, enable_transactions);
+        replace(&mut self.enable_pressured_exit, enable_pressured_exit);
+        replace(&mut self.on_demand, on_demand);
+        replace(&mut self.service_ipc, service_ipc);
+        merge_keep_alive(&mut self.keep_alive, keep_alive);
+        replace(&mut self.run_at_load, run_at_load);
+        replace(&mut self.root_directory, root_directory);
+        replace(&mut self.working_directory, working_directory);
+        merge_map(&mut self.environment_variables, environment_variables);
+        replace(&mut self.umask, umask);
+        replace(&mut self.time_out, time_out);
+        replace(&mut self.exit_time_out, exit_time_out);
+        replace(&mut self.throttle_interval, throttle_interval);
+        replace(&mut self.init_groups, init_groups);
+        replace(&mut self.watch_paths, watch_paths);
+        replace(&mut self.queue_directories, queue_directories);
+        replace(&mut self.start_on_mount, start_on_mount);
+        replace(&mut self.start_interval, start_interval);
+        match (&mut self.start_calendar_intervals, start_calendar_intervals) {
+            (Some(intervals), Some(overlay)) if options.append_calendar_intervals => {
+                for interval in overlay {
+                    if !intervals.contains(&interval) {
+                        intervals.push(interval);
+                    }
+                }
+            }
+            (intervals, overlay) => replace(intervals, overlay),
+        }
+        replace(&mut self.standard_in_path, standard_in_path);
+        replace(&mut self.standard_out_path, standard_out_path);
+        replace(&mut self.standard_error_path, standard_error_path);
+        replace(&mut self.debug, debug);
+        replace(&mut self.wait_for_debugger, wait_for_debugger);
+        merge_resource_limits(&mut self.soft_resource_limits, soft_resource_limits);
+        merge_resource_limits(&mut self.hard_resource_limits, hard_resource_limits);
+        replace(&mut self.nice, nice);
+        replace(&mut self.process_type, process_type);
+        replace(&mut self.abandon_process_group, abandon_process_group);
+        replace(&mut self.low_priority_io, low_priority_io);
+        replace(
+            &mut self.low_priority_background_io,
+            low_priority_background_io,
+        );
+        replace(
+            &mut self.materialize_dataless_files,
+            materialize_dataless_files,
+        );
+        replace(&mut self.launch_only_once, launch_only_once);
+        merge_map(&mut self.mach_services, mach_services);
+        merge_sockets(&mut self.sockets, sockets);
+        merge_launch_events(&mut self.launch_events, launch_events);
+        replace(&mut self.hopefully_exits_last, hopefully_exits_last);
+        replace(&mut self.hopefully_exits_first, hopefully_exits_first);
+        replace(&mut self.session_create, session_create);
+        replace(&mut self.legacy_timers, legacy_timers);
+        self.unknown_keys.extend(unknown_keys);
+    }
+}
+
+fn replace<T>(base: &mut Option<T>, overlay: Option<T>) {
+    if overlay.is_some() {
+        *base = overlay;
+    }
+}
+
+fn merge_map<K: Eq + Hash, V>(base: &mut Option<HashMap<K, V>>, overlay: Option<HashMap<K, V>>) {
+    if let Some(overlay) = overlay {
+        base.get_or_insert_with(HashMap::new).extend(overlay);
+    }
+}
+
+fn merge_keep_alive(base: &mut Option<KeepAliveType>, overlay: Option<KeepAliveType>) {
+    match (base, overlay) {
+        (Some(KeepAliveType::Options(base)), Some(KeepAliveType::Options(overlay))) => {
+            let KeepAliveOptions {
+                successful_exit,
+                network_state,
+                path_state,
+                other_job_enabled,
+            } = overlay;
+            replace(&mut base.successful_exit, successful_exit);
+            replace(&mut base.network_state, network_state);
+            merge_map(&mut base.path_state, path_state);
+            merge_map(&mut base.other_job_enabled, other_job_enabled);
+        }
+        (base, overlay) => replace(base, overlay),
+    }
+}
+
+fn merge_resource_limits(base: &mut Option<ResourceLimits>, overlay: Option<ResourceLimits>) {
+    let (base, overlay) = match (base, overlay) {
+        (Some(base), Some(overlay)) => (base, overlay),
+        (base, overlay) => return replace(base, overlay),
+    };
+    let ResourceLimits {
+        core,
+        cpu,
+        data,
+        file_size,
+        memory_lock,
+        number_of_files,
+        number_of_processes,
+        resident_set_size,
+        stack,
+    } = overlay;
+    replace(&mut base.core, core);
+    replace(&mut base.cpu, cpu);
+    replace(&mut base.data, data);
+    replace(&mut base.file_size, file_size);
+    replace(&mut base.memory_lock, memory_lock);
+    replace(&mut base.number_of_files, number_of_files);
+    replace(&mut base.number_of_processes, number_of_processes);
+    replace(&mut base.resident_set_size, resident_set_size);
+    replace(&mut base.stack, stack);
+}
+
+fn merge_sockets(base: &mut Option<Sockets>, overlay: Option<Sockets>) {
+    let (base, overlay) = match (base, overlay) {
+        (Some(base), Some(overlay)) => (base, overlay),
+        (base, overlay) => return replace(base, overlay),
+    };
+    let dictionaries = match overlay {
+        Sockets::Dictionary(dictionary) => vec![dictionary],
+        Sockets::Array(array) => array,
+    };
+    for (name, options) in dictionaries.into_iter().flatten() {
+        base.insert(name, options);
+    }
+}
+
+fn merge_launch_events(base: &mut Option<LaunchEvents>, overlay: Option<LaunchEvents>) {
+    let (base, overlay) = match (base, overlay) {
+        (Some(base), Some(overlay)) => (base, overlay),
+        (base, overlay) => return replace(base, overlay),
+    };
+    base.iokit.extend(overlay.iokit);
+    base.notifyd.extend(overlay.notifyd);
+    base.distnoted.extend(overlay.distnoted);
+    for (stream, events) in overlay.other {
+        base.other.entry(stream).or_default().extend(events);
+    }
+}
+
+/// The result of [`Launchd::merge3`]: the merged job, and the keys that were changed differently
+/// on both sides.
+#[cfg(feature = "io")]
+#[derive(Debug)]
+pub struct ThreeWayMerge {
+    merged: Launchd,
+    conflicts: Vec<Conflict>,
+}
+
+#[cfg(feature = "io")]
+impl ThreeWayMerge {
+    /// The merged job. Conflicting keys have their local value.
+    pub fn merged(&self) -> &Launchd {
+        &self.merged
+    }
+
+    pub fn into_merged(self) -> Launchd {
+        self.merged
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A key that was changed both locally and upstream, to different values. `None` stands for a
+/// key that isn't set.
+#[cfg(feature = "io")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    path: KeyPath,
+    base: Option<Value>,
+    local: Option<Value>,
+    upstream: Option<Value>,
+}
+
+#[cfg(feature = "io")]
+impl Conflict {
+    pub fn path(&self) -> &KeyPath {
+        &self.path
+    }
+
+    pub fn base(&self) -> Option<&Value> {
+        self.base.as_ref()
+    }
+
+    pub fn local(&self) -> Option<&Value> {
+        self.local.as_ref()
+    }
+
+    pub fn upstream(&self) -> Option<&Value> {
+        self.upstream.as_ref()
+    }
+}
+
+#[cfg(feature = "io")]
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |value: &Option<Value>| match value {
+            Some(_) => "changed",
+            None => "removed",
+        };
+        write!(
+            f,
+            "{}: {} locally and {} upstream",
+            self.path,
+            side(&self.local),
+            side(&self.upstream)
+        )
+    }
+}
+
+#[cfg(feature = "io")]
+impl Launchd {
+    /// Merges the changes from `base` to `upstream` into `local`, which was derived from `base`,
+    /// like `git merge-file` does for text. Keys that only changed on one side take that change,
+    /// and dictionaries are merged key by key. A key that changed differently on both sides is a
+    /// conflict, and keeps its local value.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let base = Launchd::new("com.example.agent", "/usr/local/bin/agent")?;
+    ///     let local = Launchd::new("com.example.agent", "/usr/local/bin/agent")?
+    ///         .with_throttle_interval(30)
+    ///         .with_nice(5);
+    ///     let upstream = Launchd::new("com.example.agent", "/usr/local/bin/agent")?
+    ///         .with_throttle_interval(60)
+    ///         .with_run_at_load(true);
+    ///
+    ///     let merge = Launchd::merge3(&base, &local, &upstream)?;
+    ///     assert_eq!(merge.merged().nice(), Some(5));
+    ///     assert_eq!(merge.merged().get_run_at_load(), Some(true));
+    ///     assert_eq!(merge.merged().throttle_interval(), Some(30));
+    ///     assert_eq!(
+    ///         merge.conflicts()[0].to_string(),
+    ///         "ThrottleInterval: changed locally and changed upstream"
+    ///     );
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn merge3(
+        base: &Launchd,
+        local: &Launchd,
+        upstream: &Launchd,
+    ) -> Result<ThreeWayMerge, Error> {
+        let base = base.to_plist_value()?;
+        let local_value = local.to_original_plist_value()?;
+        let upstream = upstream.to_plist_value()?;
+
+        let mut conflicts = Vec::new();
+        let merged = merge_values(
+            KeyPath::new(),
+            Some(&base),
+            Some(&local_value),
+            Some(&upstream),
+            &mut conflicts,
+        )
+        .unwrap_or_else(|| plist::Dictionary::new().into());
+        let mut merged = Launchd::from_plist_value(merged)?;
+        merged.key_order.order = local.key_order.order;
+        Ok(ThreeWayMerge { merged, conflicts })
+    }
+}
+
+#[cfg(feature = "io")]
+fn merge_values(
+    path: KeyPath,
+    base: Option<&plist::Value>,
+    local: Option<&plist::Value>,
+    upstream: Option<&plist::Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<plist::Value> {
+    if local == upstream || upstream == base {
+        return local.cloned();
+    }
+    if local == base {
+        return upstream.cloned();
+    }
+    let empty = plist::Dictionary::new();
+    match (base, local, upstream) {
+        (base, Some(plist::Value::Dictionary(local)), Some(plist::Value::Dictionary(upstream)))
+            if base.is_none_or(|base| base.as_dictionary().is_some()) =>
+        {
+            let base = base.and_then(plist::Value::as_dictionary).unwrap_or(&empty);
+            let keys = local
+                .keys()
+                .chain(upstream.keys().filter(|key| !local.contains_key(key)))
+                .chain(
+                    base.keys()
+                        .filter(|key| !local.contains_key(key) && !upstream.contains_key(key)),
+                );
+            let mut merged = plist::Dictionary::new();
+            for key in keys {
+                let value = merge_values(
+                    path.clone().key(key),
+                    base.get(key),
+                    local.get(key),
+                    upstream.get(key),
+                    conflicts,
+                );
+                if let Some(value) = value {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Some(merged.into())
+        }
+        _ => {
+            conflicts.push(Conflict {
+                path,
+                base: base.cloned().map(Value::from),
+                local: local.cloned().map(Value::from),
+                upstream: upstream.cloned().map(Value::from),
+            });
+            local.cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CalendarInterval, Socket, SocketOptions};
+
+    fn job() -> Launchd {
+        Launchd::new("com.example.agent", "/usr/local/bin/agent").unwrap()
+    }
+
+    #[test]
+    fn overlays_merge_maps_and_replace_the_rest() {
+        let mut base = job()
+            .with_program_arguments(vec!["agent".to_string(), "--quiet".to_string()])
+            .with_keep_alive(KeepAliveOptions::new().with_successful_exit(false).into())
+            .with_soft_resource_limits(ResourceLimits::new().with_number_of_files(256))
+            .with_socket(
+                Socket::new("Listeners", SocketOptions::new().with_service_name("ssh")).into(),
+            );
+        let overlay = Launchd::default()
+            .with_program_arguments(vec!["agent".to_string()])
+            .with_keep_alive(KeepAliveOptions::new().with_network_state(true).into())
+            .with_soft_resource_limits(ResourceLimits::new().with_core(0))
+            .with_socket(Sockets::Array(vec![
+                HashMap::from([(
+                    "Listeners".to_string(),
+                    SocketOptions::new().with_service_name("2222"),
+                )]),
+                HashMap::from([("Control".to_string(), SocketOptions::new())]),
+            ]));
+        base.merge(overlay);
+
+        let expected = job()
+            .with_program_arguments(vec!["agent".to_string()])
+            .with_keep_alive(
+                KeepAliveOptions::new()
+                    .with_successful_exit(false)
+                    .with_network_state(true)
+                    .into(),
+            )
+            .with_soft_resource_limits(ResourceLimits::new().with_number_of_files(256).with_core(0))
+            .with_socket(Sockets::Dictionary(HashMap::from([
+                (
+                    "Listeners".to_string(),
+                    SocketOptions::new().with_service_name("2222"),
+                ),
+                ("Control".to_string(), SocketOptions::new()),
+            ])));
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn calendar_intervals_are_replaced_or_appended() {
+        let at = |hour| CalendarInterval::default().with_hour(hour).unwrap();
+        let base = || job().with_start_calendar_intervals(vec![at(1), at(2)]);
+        let overlay = || Launchd::default().with_start_calendar_intervals(vec![at(2), at(3)]);
+
+        let mut replaced = base();
+        replaced.merge(overlay());
+        assert_eq!(
+            replaced.start_calendar_intervals(),
+            Some(&[at(2), at(3)][..])
+        );
+
+        let mut appended = base();
+        appended.merge_with(overlay(), MergeOptions::new().append_calendar_intervals());
+        assert_eq!(
+            appended.start_calendar_intervals(),
+            Some(&[at(1), at(2), at(3)][..])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn three_way_merges_take_both_sides() {
+        let env = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let base = job().with_environment_variables(env(&[("A", "1"), ("B", "1")]));
+        let local = job()
+            .with_environment_variables(env(&[("A", "2"), ("B", "1")]))
+            .with_unknown_key("AssociatedBundleIdentifiers", "com.example".into())
+            .unwrap();
+        let upstream = job()
+            .with_environment_variables(env(&[("A", "1"), ("C", "1")]))
+            .with_umask(0o22);
+
+        let merge = Launchd::merge3(&base, &local, &upstream).unwrap();
+        assert!(merge.is_clean());
+        assert_eq!(
+            merge.into_merged(),
+            job()
+                .with_environment_variables(env(&[("A", "2"), ("C", "1")]))
+                .with_umask(0o22)
+                .with_unknown_key("AssociatedBundleIdentifiers", "com.example".into())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn conflicting_changes_keep_the_local_value() {
+        let base = job().with_keep_alive(true.into());
+        let local =
+            job().with_keep_alive(KeepAliveOptions::new().with_successful_exit(false).into());
+        let upstream = job();
+
+        let merge = Launchd::merge3(&base, &local, &upstream).unwrap();
+        assert_eq!(merge.conflicts().len(), 1);
+        let conflict = &merge.conflicts()[0];
+        assert_eq!(conflict.path().to_string(), "KeepAlive");
+        assert_eq!(conflict.base(), Some(&Value::Boolean(true)));
+        assert_eq!(conflict.upstream(), None);
+        assert_eq!(
+            conflict.to_string(),
+            "KeepAlive: changed locally and removed upstream"
+        );
+        assert_eq!(merge.merged().keep_alive(), local.keep_alive());
+    }
+}
@@ -0,0 +1,328 @@
+// Minimisation and comparison of sets of StartCalendarIntervals.
+//
+// Every dictionary fires on a set of (Month, Day, Weekday, Hour, Minute) combinations. Two sets of
+// dictionaries fire at the same times iff they cover the same combinations, as every valid
+// combination of month, day of the month and weekday occurs in some year.
+use crate::CalendarInterval;
+use std::collections::HashSet;
+
+const DAYS_IN_MONTH: [u8; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+const COMBINATIONS: usize = 12 * 31 * 7 * 24 * 60;
+
+#[derive(Clone, Copy)]
+enum Field {
+    Minute,
+    Hour,
+    Day,
+    Weekday,
+    Month,
+}
+
+impl Field {
+    fn values(self) -> std::ops::RangeInclusive<u8> {
+        match self {
+            Field::Minute => 0..=59,
+            Field::Hour => 0..=23,
+            Field::Day => 1..=31,
+            Field::Weekday => 0..=6,
+            Field::Month => 1..=12,
+        }
+    }
+
+    fn get(self, ci: &CalendarInterval) -> Option<u8> {
+        match self {
+            Field::Minute => ci.minute,
+            Field::Hour => ci.hour,
+            Field::Day => ci.day,
+            Field::Weekday => ci.weekday,
+            Field::Month => ci.month,
+        }
+    }
+
+    fn set(self, ci: &mut CalendarInterval, value: Option<u8>) {
+        match self {
+            Field::Minute => ci.minute = value,
+            Field::Hour => ci.hour = value,
+            Field::Day => ci.day = value,
+            Field::Weekday => ci.weekday = value,
+            Field::Month => ci.month = value,
+        }
+    }
+}
+
+impl CalendarInterval {
+    /// Reduces a set of `CalendarInterval`s to a smaller set that fires at exactly the same times.
+    ///
+    /// Duplicates and dictionaries that are covered by another dictionary are dropped, and every
+    /// group of dictionaries that only differ in one field and cover all of its values is
+    /// replaced by a single dictionary without that field. This is repeated until nothing changes.
+    ///
+    /// Intervals that fire every minute are reduced to a single empty dictionary. launchd accepts
+    /// it, but [`Launchd::validate`](crate::Launchd::validate) reports it as an error, as
+    /// `StartInterval` 60 says the same more clearly.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut intervals = Vec::new();
+    ///     for hour in 0..24 {
+    ///         intervals.push(CalendarInterval::default().with_hour(hour)?.with_minute(15)?);
+    ///     }
+    ///     let minimized = CalendarInterval::minimize(intervals.clone());
+    ///
+    ///     assert_eq!(minimized, vec![CalendarInterval::default().with_minute(15)?]);
+    ///     assert!(CalendarInterval::equivalent(&intervals, &minimized));
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn minimize(intervals: Vec<CalendarInterval>) -> Vec<CalendarInterval> {
+        let mut intervals: Vec<_> = intervals
+            .into_iter()
+            .map(|mut ci| {
+                // Weekday 7 is Sunday as well, so use 0 to detect duplicates.
+                if ci.weekday == Some(7) {
+                    ci.weekday = Some(0);
+                }
+                ci
+            })
+            .collect();
+        loop {
+            let count = intervals.len();
+            intervals = drop_covered(intervals);
+            for field in [Field::Minute, Field::Hour, Field::Month] {
+                intervals = collapse(intervals, field, None);
+            }
+            intervals = collapse(intervals, Field::Day, Some(Field::Weekday));
+            intervals = collapse(intervals, Field::Weekday, Some(Field::Day));
+            if intervals.len() == count {
+                return intervals;
+            }
+        }
+    }
+
+    /// Returns whether two sets of `CalendarInterval`s fire at exactly the same times.
+    pub fn equivalent(left: &[CalendarInterval], right: &[CalendarInterval]) -> bool {
+        combinations(left) == combinations(right)
+    }
+
+    // Returns whether launchd fires this interval at least on every combination `other` fires on.
+    fn covers(&self, other: &CalendarInterval) -> bool {
+        let covers_field = |field: Field| {
+            let value = field.get(self);
+            value.is_none() || value == field.get(other)
+        };
+        let covers_days = match (self.day, self.weekday) {
+            (None, None) => true,
+            (day, None) => other.day == day && other.weekday.is_none(),
+            (None, weekday) => other.weekday == weekday && other.day.is_none(),
+            // Day and Weekday are combined with OR.
+            (day, weekday) => match (other.day, other.weekday) {
+                (None, None) => false,
+                (None, other_weekday) => other_weekday == weekday,
+                (other_day, None) => other_day == day,
+                (other_day, other_weekday) => other_day == day && other_weekday == weekday,
+            },
+        };
+        covers_field(Field::Minute)
+            && covers_field(Field::Hour)
+            && covers_field(Field::Month)
+            && covers_days
+    }
+}
+
+fn drop_covered(intervals: Vec<CalendarInterval>) -> Vec<CalendarInterval> {
+    let mut seen = HashSet::new();
+    let unique: Vec<_> = intervals
+        .into_iter()
+        .filter(|ci| seen.insert(*ci))
+        .collect();
+    unique
+        .iter()
+        .filter(|ci| !unique.iter().any(|other| other != *ci && other.covers(ci)))
+        .copied()
+        .collect()
+}
+
+// Replaces every group of intervals that only differ in `field` and cover all of its values by a
+// single interval without that field. As Day and Weekday are combined with OR, covering all values
+// of one of them while the `other` is set means the interval fires every day.
+fn collapse(
+    intervals: Vec<CalendarInterval>,
+    field: Field,
+    other: Option<Field>,
+) -> Vec<CalendarInterval> {
+    let key = |ci: &CalendarInterval| {
+        let mut key = *ci;
+        field.set(&mut key, None);
+        key
+    };
+    let present: HashSet<CalendarInterval> = intervals.iter().copied().collect();
+    let complete: HashSet<CalendarInterval> = intervals
+        .iter()
+        .map(key)
+        .filter(|k| {
+            field.values().all(|value| {
+                let mut ci = *k;
+                field.set(&mut ci, Some(value));
+                present.contains(&ci)
+            })
+        })
+        .collect();
+
+    let mut emitted = HashSet::new();
+    let mut result = Vec::with_capacity(intervals.len());
+    for ci in intervals {
+        let k = key(&ci);
+        if field.get(&ci).is_none() || !complete.contains(&k) {
+            result.push(ci);
+        } else if emitted.insert(k) {
+            let mut collapsed = k;
+            if let Some(other) = other {
+                other.set(&mut collapsed, None);
+            }
+            result.push(collapsed);
+        }
+    }
+    result
+}
+
+// Returns a bitset of every (Month, Day, Weekday, Hour, Minute) combination the intervals fire on.
+fn combinations(intervals: &[CalendarInterval]) -> Vec<u64> {
+    let mut bits = vec![0u64; COMBINATIONS.div_ceil(64)];
+    let values = |value: Option<u8>, all: std::ops::RangeInclusive<u8>| match value {
+        Some(value) => value..=value,
+        None => all,
+    };
+    for ci in intervals {
+        let weekday = ci
+            .weekday
+            .map(|weekday| if weekday == 7 { 0 } else { weekday });
+        for month in values(ci.month, Field::Month.values()).filter(|m| (1..=12).contains(m)) {
+            let days = 1..=DAYS_IN_MONTH[usize::from(month - 1)];
+            for day in days {
+                for wd in Field::Weekday.values() {
+                    let fires = match (ci.day, weekday) {
+                        (None, None) => true,
+                        (Some(d), None) => d == day,
+                        (None, Some(w)) => w == wd,
+                        (Some(d), Some(w)) => d == day || w == wd,
+                    };
+                    if !fires {
+                        continue;
+                    }
+                    for hour in values(ci.hour, Field::Hour.values()).filter(|h| *h < 24) {
+                        for minute in values(ci.minute, Field::Minute.values()).filter(|m| *m < 60)
+                        {
+                            let index = (((usize::from(month - 1) * 31 + usize::from(day - 1))
+                                * 7
+                                + usize::from(wd))
+                                * 24
+                                + usize::from(hour))
+                                * 60
+                                + usize::from(minute);
+                            bits[index / 64] |= 1 << (index % 64);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ci(
+        minute: Option<u8>,
+        hour: Option<u8>,
+        day: Option<u8>,
+        weekday: Option<u8>,
+    ) -> CalendarInterval {
+        CalendarInterval {
+            minute,
+            hour,
+            day,
+            weekday,
+            month: None,
+        }
+    }
+
+    fn assert_minimizes_to(intervals: Vec<CalendarInterval>, expected: Vec<CalendarInterval>) {
+        let minimized = CalendarInterval::minimize(intervals.clone());
+        assert!(CalendarInterval::equivalent(&intervals, &minimized));
+        assert_eq!(minimized, expected);
+    }
+
+    #[test]
+    fn every_minute_collapses_to_empty_interval() {
+        let mut intervals = Vec::new();
+        for hour in 0..24 {
+            for minute in 0..60 {
+                intervals.push(ci(Some(minute), Some(hour), None, None));
+            }
+        }
+        assert_minimizes_to(intervals, vec![CalendarInterval::default()]);
+    }
+
+    #[test]
+    fn covered_intervals_are_dropped() {
+        let intervals = vec![
+            ci(Some(0), Some(9), None, Some(1)),
+            ci(Some(0), None, None, None),
+            ci(Some(0), Some(9), Some(1), Some(3)),
+            ci(Some(0), None, None, None),
+        ];
+        assert_minimizes_to(intervals, vec![ci(Some(0), None, None, None)]);
+    }
+
+    #[test]
+    fn sunday_duplicates_are_merged() {
+        let mut intervals: Vec<_> = (1..=7)
+            .map(|wd| ci(Some(0), Some(12), None, Some(wd)))
+            .collect();
+        intervals.push(ci(Some(0), Some(12), None, Some(0)));
+        assert_minimizes_to(intervals, vec![ci(Some(0), Some(12), None, None)]);
+    }
+
+    #[test]
+    fn all_days_with_weekday_fire_every_day() {
+        let intervals = (1..=31)
+            .map(|day| ci(Some(0), Some(0), Some(day), Some(2)))
+            .collect();
+        assert_minimizes_to(intervals, vec![ci(Some(0), Some(0), None, None)]);
+    }
+
+    #[test]
+    fn partial_coverage_is_kept() {
+        let mut intervals = Vec::new();
+        for weekday in 1..=5 {
+            for hour in 9..=17 {
+                for minute in (0..60).step_by(5) {
+                    intervals.push(ci(Some(minute), Some(hour), None, Some(weekday)));
+                }
+            }
+        }
+        assert_minimizes_to(intervals.clone(), intervals);
+    }
+
+    #[test]
+    fn equivalence_detects_differences() {
+        let monday = [ci(Some(0), Some(0), None, Some(1))];
+        let first = [ci(Some(0), Some(0), Some(1), None)];
+        let either = [ci(Some(0), Some(0), Some(1), Some(1))];
+        assert!(!CalendarInterval::equivalent(&monday, &first));
+        assert!(CalendarInterval::equivalent(
+            &[monday[0], first[0]],
+            &either
+        ));
+        assert!(CalendarInterval::equivalent(
+            &[ci(None, None, Some(30), None).with_month(2).unwrap()],
+            &[]
+        ));
+    }
+}
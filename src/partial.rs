@@ -0,0 +1,403 @@
+// Reading a few top-level keys of a plist, without reading the rest of it into a Launchd.
+//
+// The values of the other keys are skipped: in an XML plist they are scanned for their closing
+// tag but not decoded, and in a binary plist they aren't visited at all. The values are returned
+// as they are in the plist, so a key that the struct doesn't support, or a value it would reject,
+// doesn't keep the requested keys from being read.
+use crate::xml::{Parser, MAX_DEPTH};
+use crate::{Error, Launchd, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BINARY_MAGIC: &[u8] = b"bplist00";
+
+impl Launchd {
+    /// Reads the values of the given top-level keys from an XML or binary plist. Keys that aren't
+    /// in the plist are left out of the result.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd, Value};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut buffer = Vec::new();
+    ///     Launchd::new("LABEL", "/bin/true")?
+    ///         .with_start_interval(60)
+    ///         .to_writer_binary(&mut buffer)?;
+    ///
+    ///     let values = Launchd::read_keys_from_bytes(&buffer, &["Label", "ProgramArguments"])?;
+    ///     assert_eq!(values["Label"], Value::from("LABEL"));
+    ///     assert!(!values.contains_key("ProgramArguments"));
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn read_keys_from_bytes(
+        bytes: &[u8],
+        keys: &[&str],
+    ) -> Result<BTreeMap<String, Value>, Error> {
+        if bytes.starts_with(BINARY_MAGIC) {
+            BinaryPlist::new(bytes)?.read_keys(keys)
+        } else {
+            let xml =
+                std::str::from_utf8(bytes).map_err(|error| Error::InvalidXml(error.to_string()))?;
+            read_xml_keys(xml, keys)
+        }
+    }
+
+    pub fn read_keys_from_file<P: AsRef<Path>>(
+        file: P,
+        keys: &[&str],
+    ) -> Result<BTreeMap<String, Value>, Error> {
+        let bytes = std::fs::read(file).map_err(Error::Io)?;
+        Self::read_keys_from_bytes(&bytes, keys)
+    }
+
+    pub fn read_keys_from_reader<R: Read>(
+        mut reader: R,
+        keys: &[&str],
+    ) -> Result<BTreeMap<String, Value>, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(Error::Io)?;
+        Self::read_keys_from_bytes(&bytes, keys)
+    }
+}
+
+// The rest of the plist isn't read once every key is found.
+fn read_xml_keys(xml: &str, keys: &[&str]) -> Result<BTreeMap<String, Value>, Error> {
+    let wanted = distinct(keys);
+    let mut parser = Parser::new(xml);
+    parser.open_root()?;
+
+    let mut values = BTreeMap::new();
+    while values.len() < wanted {
+        let key = match parser.key()? {
+            Some(key) => key,
+            None => break,
+        };
+        if keys.contains(&key.as_ref()) {
+            let value = parser.value()?;
+            values.insert(key.into_owned(), value);
+        } else {
+            parser.skip()?;
+        }
+    }
+    Ok(values)
+}
+
+fn distinct(keys: &[&str]) -> usize {
+    let mut keys = keys.to_vec();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.len()
+}
+
+// A binary plist (bplist00), whose objects are read by their index in the offset table.
+struct BinaryPlist<'a> {
+    bytes: &'a [u8],
+    offset_size: usize,
+    reference_size: usize,
+    objects: usize,
+    root: usize,
+    offset_table: usize,
+}
+
+impl<'a> BinaryPlist<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let trailer = bytes
+            .len()
+            .checked_sub(32)
+            .filter(|start| *start >= BINARY_MAGIC.len())
+            .map(|start| &bytes[start..])
+            .ok_or_else(|| invalid("the trailer is missing"))?;
+        let plist = BinaryPlist {
+            bytes,
+            offset_size: usize::from(trailer[6]),
+            reference_size: usize::from(trailer[7]),
+            objects: to_usize(be(&trailer[8..16]))?,
+            root: to_usize(be(&trailer[16..24]))?,
+            offset_table: to_usize(be(&trailer[24..32]))?,
+        };
+        if !(1..=8).contains(&plist.offset_size) || !(1..=8).contains(&plist.reference_size) {
+            return Err(invalid("the trailer is invalid"));
+        }
+        Ok(plist)
+    }
+
+    fn read_keys(&self, keys: &[&str]) -> Result<BTreeMap<String, Value>, Error> {
+        let (kind, info, position) = self.header(self.root)?;
+        if kind != 0xd {
+            return Err(invalid("the root is not a dictionary"));
+        }
+        let (length, position) = self.length(info, position)?;
+
+        let wanted = distinct(keys);
+        let mut values = BTreeMap::new();
+        for index in 0..length {
+            if values.len() == wanted {
+                break;
+            }
+            let key = self.string(self.reference(position, index)?)?;
+            if keys.contains(&key.as_ref()) {
+                let value = self.value(self.reference(position, length + index)?, 1)?;
+                values.insert(key.into_owned(), value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn slice(&self, start: usize, length: usize) -> Result<&'a [u8], Error> {
+        start
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(start..end))
+            .ok_or_else(|| invalid("an object is out of bounds"))
+    }
+
+    // Returns the type and the size nibble of an object, and the position of what follows them.
+    fn header(&self, object: usize) -> Result<(u8, usize, usize), Error> {
+        if object >= self.objects {
+            return Err(invalid("an object reference is out of bounds"));
+        }
+        let offset = object
+            .checked_mul(self.offset_size)
+            .and_then(|offset| offset.checked_add(self.offset_table))
+            .ok_or_else(|| invalid("an object reference is out of bounds"))?;
+        let offset = self.slice(offset, self.offset_size)?;
+        let position = to_usize(be(offset))?;
+        let marker = self.slice(position, 1)?[0];
+        Ok((marker >> 4, usize::from(marker & 0xf), position + 1))
+    }
+
+    // Returns the length of a string, data or collection, and the position of its contents. A
+    // length that doesn't fit in the size nibble follows it as an integer object.
+    fn length(&self, info: usize, position: usize) -> Result<(usize, usize), Error> {
+        let (length, position) = if info < 0xf {
+            (info, position)
+        } else {
+            let marker = self.slice(position, 1)?[0];
+            if marker >> 4 != 0x1 || marker & 0xf > 3 {
+                return Err(invalid("a length is invalid"));
+            }
+            let size = 1 << (marker & 0xf);
+            (
+                to_usize(be(self.slice(position + 1, size)?))?,
+                position + 1 + size,
+            )
+        };
+        // Every element takes at least a byte, so a larger length can't be right.
+        if length > self.bytes.len() {
+            return Err(invalid("a length is out of bounds"));
+        }
+        Ok((length, position))
+    }
+
+    // The index-th object reference of the collection whose references start at position.
+    fn reference(&self, position: usize, index: usize) -> Result<usize, Error> {
+        let reference = self.slice(position + index * self.reference_size, self.reference_size)?;
+        to_usize(be(reference))
+    }
+
+    fn string(&self, object: usize) -> Result<Cow<'a, str>, Error> {
+        let (kind, info, position) = self.header(object)?;
+        let (length, position) = self.length(info, position)?;
+        match kind {
+            0x5 => std::str::from_utf8(self.slice(position, length)?)
+                .map(Cow::Borrowed)
+                .map_err(|_| invalid("a string is not ASCII")),
+            0x6 => {
+                let units = self
+                    .slice(position, 2 * length)?
+                    .chunks(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+                std::char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map(Cow::Owned)
+                    .map_err(|_| invalid("a string is not UTF-16"))
+            }
+            _ => Err(invalid("a key is not a string")),
+        }
+    }
+
+    fn value(&self, object: usize, depth: usize) -> Result<Value, Error> {
+        // This also stops an array that contains itself.
+        if depth > MAX_DEPTH {
+            return Err(invalid("the values are nested too deeply"));
+        }
+        let (kind, info, position) = self.header(object)?;
+        let value = match (kind, info) {
+            (0x0, 0x8) => Value::Boolean(false),
+            (0x0, 0x9) => Value::Boolean(true),
+            (0x1, 0..=3) => {
+                let integer = be(self.slice(position, 1 << info)?);
+                // Only 8-byte integers are signed.
+                if info == 3 {
+                    Value::Integer(i128::from(integer as i64))
+                } else {
+                    Value::Integer(i128::from(integer))
+                }
+            }
+            (0x1, 4) => {
+                let mut integer = [0; 16];
+                integer.copy_from_slice(self.slice(position, 16)?);
+                Value::Integer(i128::from_be_bytes(integer))
+            }
+            (0x2, 2) => Value::Real(f64::from(f32::from_bits(
+                be(self.slice(position, 4)?) as u32
+            ))),
+            (0x2, 3) => Value::Real(f64::from_bits(be(self.slice(position, 8)?))),
+            (0x3, 3) => Value::Date(date(f64::from_bits(be(self.slice(position, 8)?)))?),
+            (0x4, _) => {
+                let (length, position) = self.length(info, position)?;
+                Value::Data(self.slice(position, length)?.to_vec())
+            }
+            (0x5, _) | (0x6, _) => Value::String(self.string(object)?.into_owned()),
+            // UIDs only occur in NSKeyedArchiver plists, and are read like plist::Value does.
+            (0x8, 0..=7) => Value::Integer(i128::from(be(self.slice(position, info + 1)?))),
+            (0xa, _) => {
+                let (length, position) = self.length(info, position)?;
+                Value::Array(
+                    (0..length)
+                        .map(|index| self.value(self.reference(position, index)?, depth + 1))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            (0xd, _) => {
+                let (length, position) = self.length(info, position)?;
+                Value::Dictionary(
+                    (0..length)
+                        .map(|index| {
+                            let key = self.string(self.reference(position, index)?)?;
+                            let value =
+                                self.value(self.reference(position, length + index)?, depth + 1)?;
+                            Ok((key.into_owned(), value))
+                        })
+                        .collect::<Result<_, Error>>()?,
+                )
+            }
+            _ => return Err(invalid("an object has an unsupported type")),
+        };
+        Ok(value)
+    }
+}
+
+// A big-endian unsigned integer of at most 8 bytes.
+fn be(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |integer, byte| integer << 8 | u64::from(*byte))
+}
+
+fn to_usize(integer: u64) -> Result<usize, Error> {
+    usize::try_from(integer).map_err(|_| invalid("an offset is out of bounds"))
+}
+
+// Dates are stored as seconds since 2001-01-01.
+fn date(seconds: f64) -> Result<SystemTime, Error> {
+    let epoch = UNIX_EPOCH + Duration::from_secs(978_307_200);
+    let duration =
+        Duration::try_from_secs_f64(seconds.abs()).map_err(|_| invalid("a date is invalid"))?;
+    if seconds < 0.0 {
+        epoch.checked_sub(duration)
+    } else {
+        epoch.checked_add(duration)
+    }
+    .ok_or_else(|| invalid("a date is out of range"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidBinary(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::corpus;
+
+    // Reads every key of a plist, as plist::Value reads it.
+    fn all_keys(value: plist::Value) -> (Vec<String>, BTreeMap<String, Value>) {
+        let dictionary = value.into_dictionary().unwrap();
+        let keys = dictionary.keys().cloned().collect();
+        let values = dictionary
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect();
+        (keys, values)
+    }
+
+    #[test]
+    fn keys_are_read_like_the_plist_crate_reads_them() {
+        for file in corpus() {
+            let value = plist::Value::from_file(&file).unwrap();
+            let mut binary = Vec::new();
+            value.to_writer_binary(&mut binary).unwrap();
+            let (keys, expected) = all_keys(value);
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+            let xml = Launchd::read_keys_from_file(&file, &keys).unwrap();
+            assert_eq!(xml, expected, "{:?}", file);
+            let binary = Launchd::read_keys_from_bytes(&binary, &keys).unwrap();
+            assert_eq!(binary, expected, "{:?}", file);
+        }
+    }
+
+    #[test]
+    fn other_keys_are_skipped() {
+        let xml = br#"<plist version="1.0"><dict>
+            <key>Label</key><string>com.example.agent</string>
+            <key>StartInterval</key><string>every minute</string>
+            <key>LaunchEvents</key><dict><key>com.apple.iokit.matching</key><dict/></dict>
+            <key>ProgramArguments</key><array><string>/bin/true</string></array>
+        </dict></plist>"#;
+        assert!(Launchd::from_bytes(xml).is_err());
+
+        let values = Launchd::read_keys_from_reader(
+            &xml[..],
+            &["Label", "ProgramArguments", "Label", "Missing"],
+        )
+        .unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            values["ProgramArguments"],
+            Value::Array(vec!["/bin/true".into()])
+        );
+    }
+
+    #[test]
+    fn invalid_binary_plists_are_an_error() {
+        // {"A": [<itself>]}: the array is only visited when A is asked for.
+        let mut binary = b"bplist00\xd1\x01\x02\x51A\xa1\x02\x08\x0b\x0d".to_vec();
+        binary.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1]);
+        for integer in [3u64, 0, 15] {
+            binary.extend_from_slice(&integer.to_be_bytes());
+        }
+
+        assert!(Launchd::read_keys_from_bytes(&binary, &["B"])
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            Launchd::read_keys_from_bytes(&binary, &["A"]),
+            Err(Error::InvalidBinary(_))
+        ));
+        assert!(matches!(
+            Launchd::read_keys_from_bytes(&binary[..binary.len() - 1], &["A"]),
+            Err(Error::InvalidBinary(_))
+        ));
+        assert!(matches!(
+            Launchd::read_keys_from_bytes(b"bplist00", &["A"]),
+            Err(Error::InvalidBinary(_))
+        ));
+
+        let xml = format!(
+            "<plist><dict><key>A</key>{}</dict></plist>",
+            "<array>".repeat(1000)
+        );
+        assert!(matches!(
+            Launchd::read_keys_from_bytes(xml.as_bytes(), &["A"]),
+            Err(Error::InvalidXml(_))
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+// PlistBuddy-style access to a job by key path, e.g. `launchd.get(":Sockets:Listeners:Bonjour")`.
+//
+// The paths address the plist, not the struct, so they reach into typed fields, LaunchEvents and
+// unknown keys alike. An edit is applied to the plist::Value of the job, which is then read back,
+// so a value of the wrong type is rejected like it would be when reading a file.
+use crate::key_path::Segment;
+use crate::{Error, KeyPath, Launchd, Value};
+
+impl Launchd {
+    /// Returns the value at a PlistBuddy path, like `PlistBuddy -c "Print <path>"`.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd, Value};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let mut launchd = Launchd::new("LABEL", "/bin/true")?;
+    ///     launchd.set(":KeepAlive:SuccessfulExit", false)?;
+    ///     launchd.set(":EnvironmentVariables:FOO", "bar")?;
+    ///
+    ///     assert_eq!(launchd.get(":KeepAlive:SuccessfulExit")?, Value::Boolean(false));
+    ///     assert_eq!(launchd.delete(":EnvironmentVariables:FOO")?, Value::from("bar"));
+    ///     assert!(matches!(
+    ///         launchd.get(":EnvironmentVariables:FOO"),
+    ///         Err(Error::KeyPathNotFound(_))
+    ///     ));
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn get(&self, path: &str) -> Result<Value, Error> {
+        self.get_path(&path.parse()?)
+    }
+
+    pub fn get_path(&self, path: &KeyPath) -> Result<Value, Error> {
+        let root = self.to_plist_value()?;
+        let mut value = &root;
+        let mut prefix = KeyPath::new();
+        for segment in path.segments() {
+            value = child(value, segment, &prefix)?;
+            prefix = prefix.segment(segment.clone());
+        }
+        Ok(value.clone().into())
+    }
+
+    /// Sets the value at a PlistBuddy path. Missing dictionaries along the path are added, like
+    /// `PlistBuddy -c "Add <path>"` would, and an index one past the end of an array appends.
+    pub fn set<V: Into<Value>>(&mut self, path: &str, value: V) -> Result<(), Error> {
+        self.set_path(&path.parse()?, value.into())
+    }
+
+    pub fn set_path(&mut self, path: &KeyPath, value: Value) -> Result<(), Error> {
+        self.edit_path(path, true, |parent, segment, prefix| {
+            let value = value.into();
+            match (parent, segment) {
+                (plist::Value::Dictionary(dictionary), segment) => {
+                    dictionary.insert(key(segment), value);
+                }
+                (plist::Value::Array(array), Segment::Index(index)) if *index < array.len() => {
+                    array[*index] = value;
+                }
+                (plist::Value::Array(array), Segment::Index(index)) if *index == array.len() => {
+                    array.push(value);
+                }
+                (plist::Value::Array(_), segment) => {
+                    return Err(Error::KeyPathNotFound(
+                        prefix.clone().segment(segment.clone()),
+                    ))
+                }
+                _ => return Err(Error::NotAContainer(prefix.clone())),
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the value at a PlistBuddy path and returns it, like `PlistBuddy -c "Delete <path>"`.
+    pub fn delete(&mut self, path: &str) -> Result<Value, Error> {
+        self.delete_path(&path.parse()?)
+    }
+
+    pub fn delete_path(&mut self, path: &KeyPath) -> Result<Value, Error> {
+        self.edit_path(path, false, |parent, segment, prefix| {
+            let removed = match (parent, segment) {
+                (plist::Value::Dictionary(dictionary), segment) => dictionary.remove(&key(segment)),
+                (plist::Value::Array(array), Segment::Index(index)) if *index < array.len() => {
+                    Some(array.remove(*index))
+                }
+                (plist::Value::Array(_), _) => None,
+                _ => return Err(Error::NotAContainer(prefix.clone())),
+            };
+            removed
+                .map(Value::from)
+                .ok_or_else(|| Error::KeyPathNotFound(prefix.clone().segment(segment.clone())))
+        })
+    }
+
+    // Applies an edit to the parent of the last segment of the path, and reads the job back.
+    fn edit_path<T, F>(&mut self, path: &KeyPath, create: bool, edit: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut plist::Value, &Segment, &KeyPath) -> Result<T, Error>,
+    {
+        let (last, parents) = path
+            .segments()
+            .split_last()
+            .ok_or_else(|| Error::InvalidKeyPath(path.to_string()))?;
+
+        let order = self.key_order.order;
+        let mut root = self.to_original_plist_value()?;
+        let mut parent = &mut root;
+        let mut prefix = KeyPath::new();
+        for segment in parents {
+            parent = child_mut(parent, segment, &prefix, create)?;
+            prefix = prefix.segment(segment.clone());
+        }
+        let result = edit(parent, last, &prefix)?;
+
+        let mut launchd = Launchd::from_plist_value(root)
+            .map_err(|error| Error::InvalidValueAt(path.clone(), error.to_string()))?;
+        launchd.key_order.order = order;
+        *self = launchd;
+        Ok(result)
+    }
+}
+
+// A segment of digits is a key when it is used on a dictionary.
+fn key(segment: &Segment) -> String {
+    match segment {
+        Segment::Key(key) => key.clone(),
+        Segment::Index(index) => index.to_string(),
+    }
+}
+
+fn child<'a>(
+    value: &'a plist::Value,
+    segment: &Segment,
+    prefix: &KeyPath,
+) -> Result<&'a plist::Value, Error> {
+    let child = match (value, segment) {
+        (plist::Value::Dictionary(dictionary), segment) => dictionary.get(&key(segment)),
+        (plist::Value::Array(array), Segment::Index(index)) => array.get(*index),
+        (plist::Value::Array(_), Segment::Key(_)) => None,
+        _ => return Err(Error::NotAContainer(prefix.clone())),
+    };
+    child.ok_or_else(|| Error::KeyPathNotFound(prefix.clone().segment(segment.clone())))
+}
+
+fn child_mut<'a>(
+    value: &'a mut plist::Value,
+    segment: &Segment,
+    prefix: &KeyPath,
+    create: bool,
+) -> Result<&'a mut plist::Value, Error> {
+    let child = match (value, segment) {
+        (plist::Value::Dictionary(dictionary), segment) => {
+            let key = key(segment);
+            if create && !dictionary.contains_key(&key) {
+                dictionary.insert(key.clone(), plist::Dictionary::new().into());
+            }
+            dictionary.get_mut(&key)
+        }
+        (plist::Value::Array(array), Segment::Index(index)) => {
+            if create && *index == array.len() {
+                array.push(plist::Dictionary::new().into());
+            }
+            array.get_mut(*index)
+        }
+        (plist::Value::Array(_), Segment::Key(_)) => None,
+        _ => return Err(Error::NotAContainer(prefix.clone())),
+    };
+    child.ok_or_else(|| Error::KeyPathNotFound(prefix.clone().segment(segment.clone())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_case;
+    use crate::{KeepAliveType, KeyOrder};
+
+    #[test]
+    fn paths_reach_typed_and_untyped_values() {
+        let launchd = Launchd::from_file(test_case!("sockets-1.plist")).unwrap();
+        assert_eq!(
+            launchd.get(":Sockets:Listeners:SockServiceName").unwrap(),
+            Value::from("ssh")
+        );
+        assert_eq!(
+            launchd.get(":Sockets:Listeners:Bonjour:1").unwrap(),
+            Value::from("sftp-ssh")
+        );
+        assert_eq!(
+            launchd.get("POSIXSpawnType").unwrap(),
+            Value::from("Interactive")
+        );
+
+        let launchd = Launchd::from_file(test_case!("launchevents-1.plist")).unwrap();
+        assert_eq!(
+            launchd
+                .get(":LaunchEvents:com.apple.notifyd.matching:com.apple.phone.idslaunchnotification:Notification")
+                .unwrap(),
+            Value::from("com.apple.phone.idslaunchnotification")
+        );
+    }
+
+    #[test]
+    fn edits_are_read_back_into_the_typed_fields() {
+        let mut launchd = Launchd::from_file(test_case!("keepalive-1.plist"))
+            .unwrap()
+            .with_key_order(KeyOrder::Original);
+        launchd.set(":KeepAlive:SuccessfulExit", true).unwrap();
+        launchd.set(":ProgramArguments:3", "--verbose").unwrap();
+        launchd
+            .set(":AssociatedBundleIdentifiers", "com.example")
+            .unwrap();
+        launchd.delete(":EnvironmentVariables:LANG").unwrap();
+        launchd.delete(":StartCalendarInterval:0").unwrap();
+
+        match launchd.keep_alive() {
+            Some(KeepAliveType::Options(options)) => {
+                assert_eq!(options.successful_exit(), Some(true))
+            }
+            keep_alive => panic!("unexpected KeepAlive: {:?}", keep_alive),
+        }
+        assert_eq!(launchd.program_arguments().unwrap()[3], "--verbose");
+        assert!(launchd
+            .unknown_keys()
+            .contains_key("AssociatedBundleIdentifiers"));
+        assert_eq!(launchd.environment_variables().unwrap().len(), 2);
+        assert_eq!(launchd.start_calendar_intervals().unwrap().len(), 1);
+
+        // The edited job is still written in the order of the file.
+        let mut buffer = Vec::new();
+        launchd.to_writer_xml(&mut buffer).unwrap();
+        let xml = String::from_utf8(buffer).unwrap();
+        assert!(xml.find("<key>KeepAlive</key>") < xml.find("<key>Label</key>"));
+    }
+
+    #[test]
+    fn wrong_paths_and_types_are_errors() {
+        let mut launchd = Launchd::from_file(test_case!("keepalive-1.plist")).unwrap();
+        assert!(matches!(
+            launchd.get(":Sockets:Listeners"),
+            Err(Error::KeyPathNotFound(path)) if path.to_string() == "Sockets"
+        ));
+        assert!(matches!(
+            launchd.get(":Label:Foo"),
+            Err(Error::NotAContainer(path)) if path.to_string() == "Label"
+        ));
+        assert!(matches!(
+            launchd.set(":ProgramArguments:5", "--verbose"),
+            Err(Error::KeyPathNotFound(path)) if path.to_string() == "ProgramArguments[5]"
+        ));
+        assert!(matches!(
+            launchd.set(":Umask", "022"),
+            Err(Error::InvalidValueAt(path, _)) if path.to_string() == "Umask"
+        ));
+        assert!(matches!(
+            launchd.delete(":Label"),
+            Err(Error::InvalidValueAt(_, _))
+        ));
+        assert!(matches!(launchd.delete(":"), Err(Error::InvalidKeyPath(_))));
+
+        // A failed edit leaves the job alone.
+        assert_eq!(
+            launchd,
+            Launchd::from_file(test_case!("keepalive-1.plist")).unwrap()
+        );
+    }
+}
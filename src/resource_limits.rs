@@ -7,16 +7,16 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(feature = "io", serde(rename_all = "PascalCase"))]
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct ResourceLimits {
-    core: Option<u64>,
+    pub(crate) core: Option<u64>,
     #[cfg_attr(feature = "serde", serde(rename = "CPU"))]
-    cpu: Option<u64>,
-    data: Option<u64>,
-    file_size: Option<u64>,
-    memory_lock: Option<u64>,
-    number_of_files: Option<u64>,
-    number_of_processes: Option<u64>,
-    resident_set_size: Option<u64>,
-    stack: Option<u64>,
+    pub(crate) cpu: Option<u64>,
+    pub(crate) data: Option<u64>,
+    pub(crate) file_size: Option<u64>,
+    pub(crate) memory_lock: Option<u64>,
+    pub(crate) number_of_files: Option<u64>,
+    pub(crate) number_of_processes: Option<u64>,
+    pub(crate) resident_set_size: Option<u64>,
+    pub(crate) stack: Option<u64>,
 }
 
 impl ResourceLimits {
@@ -68,4 +68,40 @@ impl ResourceLimits {
         self.stack = Some(value);
         self
     }
+
+    pub fn core(&self) -> Option<u64> {
+        self.core
+    }
+
+    pub fn cpu(&self) -> Option<u64> {
+        self.cpu
+    }
+
+    pub fn data(&self) -> Option<u64> {
+        self.data
+    }
+
+    pub fn file_size(&self) -> Option<u64> {
+        self.file_size
+    }
+
+    pub fn memory_lock(&self) -> Option<u64> {
+        self.memory_lock
+    }
+
+    pub fn number_of_files(&self) -> Option<u64> {
+        self.number_of_files
+    }
+
+    pub fn number_of_processes(&self) -> Option<u64> {
+        self.number_of_processes
+    }
+
+    pub fn resident_set_size(&self) -> Option<u64> {
+        self.resident_set_size
+    }
+
+    pub fn stack(&self) -> Option<u64> {
+        self.stack
+    }
 }
@@ -0,0 +1,296 @@
+// See the StartCalendarInterval section in https://www.manpagez.com/man/5/launchd.plist/
+//
+// launchd interprets a CalendarInterval like a crontab(5) entry: unset fields are wildcards,
+// Weekday 0 and 7 both mean Sunday, and when both Day and Weekday are set the job fires when
+// either of them matches. All times are local wall clock times, so they are modelled as
+// chrono::NaiveDateTime.
+use crate::{CalendarInterval, Launchd};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+// The Gregorian calendar repeats itself every 400 years (146097 days). If an interval didn't fire
+// within that window, it never will (e.g. Day 30 in Month 2).
+const SEARCH_LIMIT_DAYS: u32 = 146_097;
+
+impl CalendarInterval {
+    /// Returns whether launchd fires this interval during the minute of `date_time`.
+    pub fn matches(&self, date_time: &NaiveDateTime) -> bool {
+        self.matches_date(&date_time.date())
+            && field_matches(self.hour, date_time.hour())
+            && field_matches(self.minute, date_time.minute())
+    }
+
+    /// Returns the first time strictly after `after` at which launchd fires this interval,
+    /// or `None` if the interval can never fire.
+    pub fn next_fire_time(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = next_minute(after)?;
+        let mut date = start.date();
+        let mut earliest = start.time();
+        for _ in 0..=SEARCH_LIMIT_DAYS {
+            if self.matches_date(&date) {
+                if let Some(time) = self.first_time_from(earliest) {
+                    return Some(date.and_time(time));
+                }
+            }
+            date = date.succ_opt()?;
+            earliest = NaiveTime::from_hms_opt(0, 0, 0)?;
+        }
+        None
+    }
+
+    /// Returns the next `count` times strictly after `after` at which launchd fires this interval.
+    ///
+    /// Usage:
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use launchd::{CalendarInterval, Error};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     // Every Sunday at 12:00
+    ///     let ci = CalendarInterval::default().with_weekday(7)?.with_hour(12)?.with_minute(0)?;
+    ///     let after = NaiveDate::from_ymd_opt(2022, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    ///
+    ///     let times = ci.next_fire_times(after, 2);
+    ///     assert_eq!(times[0], NaiveDate::from_ymd_opt(2022, 1, 2).unwrap().and_hms_opt(12, 0, 0).unwrap());
+    ///     assert_eq!(times[1], NaiveDate::from_ymd_opt(2022, 1, 9).unwrap().and_hms_opt(12, 0, 0).unwrap());
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn next_fire_times(&self, after: NaiveDateTime, count: usize) -> Vec<NaiveDateTime> {
+        FireTimes::new(std::slice::from_ref(self), after)
+            .take(count)
+            .collect()
+    }
+
+    fn matches_date(&self, date: &NaiveDate) -> bool {
+        if !field_matches(self.month, date.month()) {
+            return false;
+        }
+        let day = self.day.map(|day| u32::from(day) == date.day());
+        let weekday = self.weekday.map(|weekday| {
+            let weekday = if weekday == 7 { 0 } else { weekday };
+            u32::from(weekday) == date.weekday().num_days_from_sunday()
+        });
+        match (day, weekday) {
+            (Some(day), Some(weekday)) => day || weekday,
+            (Some(matches), None) | (None, Some(matches)) => matches,
+            (None, None) => true,
+        }
+    }
+
+    fn first_time_from(&self, earliest: NaiveTime) -> Option<NaiveTime> {
+        (earliest.hour()..24)
+            .filter(|hour| field_matches(self.hour, *hour))
+            .find_map(|hour| {
+                let first_minute = if hour == earliest.hour() {
+                    earliest.minute()
+                } else {
+                    0
+                };
+                (first_minute..60)
+                    .find(|minute| field_matches(self.minute, *minute))
+                    .and_then(|minute| NaiveTime::from_hms_opt(hour, minute, 0))
+            })
+    }
+}
+
+impl Launchd {
+    /// Returns an iterator over the times strictly after `after` at which launchd fires this job
+    /// because of its `StartCalendarInterval`s. The iterator is empty if none are set.
+    pub fn calendar_fire_times(&self, after: NaiveDateTime) -> FireTimes<'_> {
+        FireTimes::new(
+            self.start_calendar_intervals.as_deref().unwrap_or_default(),
+            after,
+        )
+    }
+
+    /// Returns the next `count` times strictly after `after` at which launchd fires this job
+    /// because of its `StartCalendarInterval`s.
+    pub fn next_fire_times(&self, after: NaiveDateTime, count: usize) -> Vec<NaiveDateTime> {
+        self.calendar_fire_times(after).take(count).collect()
+    }
+}
+
+/// Iterator over the times at which launchd fires a set of `CalendarInterval`s, in chronological
+/// order. A time at which several intervals match is only yielded once, as launchd only starts
+/// the job once.
+///
+/// Usage:
+/// ```
+/// use chrono::NaiveDate;
+/// use launchd::{CalendarInterval, Error, FireTimes};
+///
+/// fn example() -> Result<(), Error> {
+///     let intervals = vec![
+///         CalendarInterval::default().with_hour(9)?.with_minute(0)?,
+///         CalendarInterval::default().with_hour(17)?.with_minute(30)?,
+///     ];
+///     let after = NaiveDate::from_ymd_opt(2022, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+///
+///     let times: Vec<_> = FireTimes::new(&intervals, after).take(2).collect();
+///     assert_eq!(times[0], NaiveDate::from_ymd_opt(2022, 1, 1).unwrap().and_hms_opt(17, 30, 0).unwrap());
+///     assert_eq!(times[1], NaiveDate::from_ymd_opt(2022, 1, 2).unwrap().and_hms_opt(9, 0, 0).unwrap());
+///     Ok(())
+/// }
+/// example().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FireTimes<'a> {
+    pending: Vec<(NaiveDateTime, &'a CalendarInterval)>,
+}
+
+impl<'a> FireTimes<'a> {
+    pub fn new(intervals: &'a [CalendarInterval], after: NaiveDateTime) -> Self {
+        Self {
+            pending: intervals
+                .iter()
+                .filter_map(|ci| ci.next_fire_time(after).map(|time| (time, ci)))
+                .collect(),
+        }
+    }
+}
+
+impl Iterator for FireTimes<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.pending.iter().map(|(time, _)| *time).min()?;
+        let mut i = 0;
+        while i < self.pending.len() {
+            let (time, ci) = self.pending[i];
+            if time == next {
+                match ci.next_fire_time(time) {
+                    Some(time) => self.pending[i].0 = time,
+                    None => {
+                        self.pending.swap_remove(i);
+                        continue;
+                    }
+                }
+            }
+            i += 1;
+        }
+        Some(next)
+    }
+}
+
+fn field_matches(field: Option<u8>, value: u32) -> bool {
+    field.is_none_or(|field| u32::from(field) == value)
+}
+
+fn next_minute(date_time: NaiveDateTime) -> Option<NaiveDateTime> {
+    date_time
+        .date()
+        .and_hms_opt(date_time.hour(), date_time.minute(), 0)?
+        .checked_add_signed(Duration::minutes(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn empty_interval_fires_every_minute() {
+        let times = CalendarInterval::default().next_fire_times(at(2022, 12, 31, 23, 58), 3);
+        assert_eq!(
+            times,
+            vec![
+                at(2022, 12, 31, 23, 59),
+                at(2023, 1, 1, 0, 0),
+                at(2023, 1, 1, 0, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn next_fire_time_is_strictly_after_start() {
+        let ci = CalendarInterval::default().with_minute(30).unwrap();
+        let start = at(2022, 6, 1, 10, 30) + Duration::seconds(15);
+        assert_eq!(ci.next_fire_time(start), Some(at(2022, 6, 1, 11, 30)));
+    }
+
+    #[test]
+    fn weekday_zero_and_seven_are_sunday() {
+        let start = at(2022, 1, 1, 0, 0); // A Saturday
+        let sunday_0 = CalendarInterval::default().with_weekday(0).unwrap();
+        let sunday_7 = CalendarInterval::default().with_weekday(7).unwrap();
+        assert_eq!(sunday_0.next_fire_time(start), Some(at(2022, 1, 2, 0, 0)));
+        assert_eq!(
+            sunday_0.next_fire_time(start),
+            sunday_7.next_fire_time(start)
+        );
+    }
+
+    #[test]
+    fn day_and_weekday_fire_when_either_matches() {
+        let ci = CalendarInterval::default()
+            .with_day(15)
+            .and_then(|ci| ci.with_weekday(1))
+            .and_then(|ci| ci.with_hour(8))
+            .and_then(|ci| ci.with_minute(0))
+            .unwrap();
+        let days: Vec<u32> = ci
+            .next_fire_times(at(2022, 8, 1, 12, 0), 5)
+            .iter()
+            .map(|time| time.day())
+            .collect();
+        assert_eq!(days, vec![8, 15, 22, 29, 5]);
+    }
+
+    #[test]
+    fn impossible_interval_never_fires() {
+        let ci = CalendarInterval::default()
+            .with_month(2)
+            .and_then(|ci| ci.with_day(30))
+            .unwrap();
+        assert_eq!(ci.next_fire_time(at(2022, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn leap_day_fires_every_four_years() {
+        let ci = CalendarInterval::default()
+            .with_month(2)
+            .and_then(|ci| ci.with_day(29))
+            .and_then(|ci| ci.with_hour(0))
+            .and_then(|ci| ci.with_minute(0))
+            .unwrap();
+        assert_eq!(
+            ci.next_fire_times(at(2022, 1, 1, 0, 0), 2),
+            vec![at(2024, 2, 29, 0, 0), at(2028, 2, 29, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn launchd_merges_overlapping_intervals() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(vec![
+                CalendarInterval::default().with_minute(0).unwrap(),
+                CalendarInterval::default().with_minute(30).unwrap(),
+                CalendarInterval::default()
+                    .with_hour(1)
+                    .and_then(|ci| ci.with_minute(0))
+                    .unwrap(),
+            ]);
+        assert_eq!(
+            launchd.next_fire_times(at(2022, 1, 1, 0, 45), 3),
+            vec![
+                at(2022, 1, 1, 1, 0),
+                at(2022, 1, 1, 1, 30),
+                at(2022, 1, 1, 2, 0)
+            ]
+        );
+        assert_eq!(
+            Launchd::default()
+                .calendar_fire_times(at(2022, 1, 1, 0, 0))
+                .next(),
+            None
+        );
+    }
+}
@@ -4,6 +4,7 @@
 use crate::error::Error;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::path::Path;
@@ -12,8 +13,121 @@ use std::path::Path;
 #[cfg_attr(feature = "serde", serde(untagged))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Sockets {
-    Dictionary(HashMap<String, SocketOptions>),
-    Array(Vec<HashMap<String, SocketOptions>>),
+    Dictionary(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::map"))]
+        HashMap<String, SocketOptions>,
+    ),
+    Array(
+        #[cfg_attr(feature = "serde", serde(serialize_with = "crate::sorted::maps"))]
+        Vec<HashMap<String, SocketOptions>>,
+    ),
+}
+
+impl Sockets {
+    /// Iterates over every socket by name, sorted by name within each dictionary. The sockets of
+    /// an array are returned in the order of the array.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Socket, SocketOptions, Sockets};
+    ///
+    /// let sockets = Sockets::from(Socket::new("Listeners", SocketOptions::new().passive()));
+    /// let names: Vec<_> = sockets.iter().map(|(name, _)| name).collect();
+    /// assert_eq!(names, vec!["Listeners"]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SocketOptions)> + '_ {
+        self.dictionaries()
+            .iter()
+            .flat_map(|dictionary| crate::sorted_entries(Some(dictionary)))
+            .map(|(name, options)| (name.as_str(), options))
+    }
+
+    pub fn get<S: AsRef<str>>(&self, name: S) -> Option<&SocketOptions> {
+        self.dictionaries()
+            .iter()
+            .find_map(|dictionary| dictionary.get(name.as_ref()))
+    }
+
+    pub fn get_mut<S: AsRef<str>>(&mut self, name: S) -> Option<&mut SocketOptions> {
+        self.dictionaries_mut()
+            .iter_mut()
+            .find_map(|dictionary| dictionary.get_mut(name.as_ref()))
+    }
+
+    /// Returns the entry of a socket for in-place editing. A socket that isn't there yet is added
+    /// to the last dictionary of an array.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Socket, SocketOptions, Sockets};
+    ///
+    /// let mut sockets = Sockets::from(Socket::new("Listeners", SocketOptions::new()));
+    /// sockets
+    ///     .entry("Listeners")
+    ///     .and_modify(|options| *options = options.clone().with_service_name("ssh"));
+    /// sockets.entry("Control").or_insert_with(SocketOptions::new);
+    ///
+    /// assert_eq!(sockets.get("Listeners").unwrap().service_name(), Some("ssh"));
+    /// assert_eq!(sockets.iter().count(), 2);
+    /// ```
+    pub fn entry<S: AsRef<str>>(&mut self, name: S) -> Entry<'_, String, SocketOptions> {
+        let name = name.as_ref();
+        if let Sockets::Array(array) = self {
+            if array.is_empty() {
+                array.push(HashMap::new());
+            }
+        }
+        let dictionaries = self.dictionaries_mut();
+        let index = dictionaries
+            .iter()
+            .position(|dictionary| dictionary.contains_key(name))
+            .unwrap_or(dictionaries.len() - 1);
+        dictionaries[index].entry(name.to_string())
+    }
+
+    pub fn insert<S: AsRef<str>>(
+        &mut self,
+        name: S,
+        options: SocketOptions,
+    ) -> Option<SocketOptions> {
+        match self.entry(name) {
+            Entry::Occupied(mut entry) => Some(entry.insert(options)),
+            Entry::Vacant(entry) => {
+                entry.insert(options);
+                None
+            }
+        }
+    }
+
+    /// Removes a socket, and the dictionary it was in if it was the last socket of an array.
+    pub fn remove<S: AsRef<str>>(&mut self, name: S) -> Option<SocketOptions> {
+        let removed = self
+            .dictionaries_mut()
+            .iter_mut()
+            .find_map(|dictionary| dictionary.remove(name.as_ref()));
+        if let Sockets::Array(array) = self {
+            array.retain(|dictionary| !dictionary.is_empty());
+        }
+        removed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dictionaries().iter().all(HashMap::is_empty)
+    }
+
+    fn dictionaries(&self) -> &[HashMap<String, SocketOptions>] {
+        match self {
+            Sockets::Dictionary(dictionary) => std::slice::from_ref(dictionary),
+            Sockets::Array(array) => array.as_slice(),
+        }
+    }
+
+    fn dictionaries_mut(&mut self) -> &mut [HashMap<String, SocketOptions>] {
+        match self {
+            Sockets::Dictionary(dictionary) => std::slice::from_mut(dictionary),
+            Sockets::Array(array) => array.as_mut_slice(),
+        }
+    }
 }
 
 impl From<Socket> for Sockets {
@@ -49,17 +163,17 @@ impl Socket {
 #[cfg_attr(feature = "io", serde(rename_all = "PascalCase"))]
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct SocketOptions {
-    sock_type: Option<SocketType>,
-    sock_passive: Option<bool>,
-    sock_node_name: Option<String>,
-    sock_service_name: Option<String>,
-    sock_family: Option<SocketFamily>,
-    sock_protocol: Option<SocketProtocol>,
-    sock_path_name: Option<String>,
-    secure_socket_with_key: Option<String>,
-    sock_path_mode: Option<i128>,
-    bonjour: Option<BonjourType>,
-    multicast_group: Option<String>,
+    pub(crate) sock_type: Option<SocketType>,
+    pub(crate) sock_passive: Option<bool>,
+    pub(crate) sock_node_name: Option<String>,
+    pub(crate) sock_service_name: Option<String>,
+    pub(crate) sock_family: Option<SocketFamily>,
+    pub(crate) sock_protocol: Option<SocketProtocol>,
+    pub(crate) sock_path_name: Option<String>,
+    pub(crate) secure_socket_with_key: Option<String>,
+    pub(crate) sock_path_mode: Option<i128>,
+    pub(crate) bonjour: Option<BonjourType>,
+    pub(crate) multicast_group: Option<String>,
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -154,6 +268,50 @@ impl SocketOptions {
         self.multicast_group = Some(value.as_ref().to_string());
         self
     }
+
+    pub fn socket_type(&self) -> Option<&SocketType> {
+        self.sock_type.as_ref()
+    }
+
+    pub fn get_passive(&self) -> Option<bool> {
+        self.sock_passive
+    }
+
+    pub fn node_name(&self) -> Option<&str> {
+        self.sock_node_name.as_deref()
+    }
+
+    pub fn service_name(&self) -> Option<&str> {
+        self.sock_service_name.as_deref()
+    }
+
+    pub fn family(&self) -> Option<&SocketFamily> {
+        self.sock_family.as_ref()
+    }
+
+    pub fn protocol(&self) -> Option<&SocketProtocol> {
+        self.sock_protocol.as_ref()
+    }
+
+    pub fn path_name(&self) -> Option<&Path> {
+        self.sock_path_name.as_deref().map(Path::new)
+    }
+
+    pub fn secure_socket_key(&self) -> Option<&str> {
+        self.secure_socket_with_key.as_deref()
+    }
+
+    pub fn path_mode(&self) -> Option<i128> {
+        self.sock_path_mode
+    }
+
+    pub fn bonjour(&self) -> Option<&BonjourType> {
+        self.bonjour.as_ref()
+    }
+
+    pub fn multicast_group(&self) -> Option<&str> {
+        self.multicast_group.as_deref()
+    }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -0,0 +1,44 @@
+// Serialization of HashMaps with sorted keys, so that writing the same job twice produces the same
+// output, regardless of the iteration order of the HashMaps.
+use serde::ser::{Serialize, Serializer};
+use std::collections::HashMap;
+
+struct Sorted<'a, K, V>(&'a HashMap<K, V>);
+
+impl<K: Ord + Serialize, V: Serialize> Serialize for Sorted<'_, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_by(|left, right| left.0.cmp(right.0));
+        serializer.collect_map(entries)
+    }
+}
+
+pub(crate) fn map<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    Sorted(map).serialize(serializer)
+}
+
+pub(crate) fn maps<S, K, V>(maps: &[HashMap<K, V>], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    serializer.collect_seq(maps.iter().map(Sorted))
+}
+
+pub(crate) fn option_map<S, K, V>(
+    map: &Option<HashMap<K, V>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    map.as_ref().map(Sorted).serialize(serializer)
+}
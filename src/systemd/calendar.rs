@@ -0,0 +1,403 @@
+// Parsing of systemd OnCalendar= expressions into CalendarIntervals.
+//
+// An expression has the normalized form "DayOfWeek Year-Month-Day Hour:Minute:Second". Every
+// component must match for systemd to fire, whereas launchd fires when either the Day or the
+// Weekday matches. So only expressions that restrict at most one of them can be converted.
+use crate::{CalendarInterval, Error};
+
+// In systemd's order, which starts the week on Monday, unlike launchd's Weekday.
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+impl CalendarInterval {
+    /// Converts a systemd `OnCalendar=` expression to `CalendarInterval`s that fire at the same
+    /// times, e.g. `Mon..Fri *-*-* 09:00:00`, `*:0/15` or `weekly`.
+    ///
+    /// Returns [`Error::UnsupportedOnCalendar`] for expressions launchd can't express: specific
+    /// years or seconds, the last days of a month (`~`), time zones, or a restricted day of the
+    /// month combined with a restricted day of the week. An expression that fires every minute,
+    /// such as `minutely`, is unsupported as well, as it would need an empty dictionary: use
+    /// `StartInterval` 60 instead.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let intervals = CalendarInterval::from_on_calendar("Mon,Fri *-*-* 09:30")?;
+    ///     assert_eq!(intervals, vec![
+    ///         CalendarInterval::default().with_minute(30)?.with_hour(9)?.with_weekday(1)?,
+    ///         CalendarInterval::default().with_minute(30)?.with_hour(9)?.with_weekday(5)?,
+    ///     ]);
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn from_on_calendar(expression: &str) -> Result<Vec<Self>, Error> {
+        let mut tokens: Vec<&str> = expression.split_whitespace().collect();
+        let shorthand = match tokens.first().map(|token| token.to_lowercase()).as_deref() {
+            Some("minutely") => Some("*-*-* *:*:00"),
+            Some("hourly") => Some("*-*-* *:00:00"),
+            Some("daily") => Some("*-*-* 00:00:00"),
+            Some("weekly") => Some("Mon *-*-* 00:00:00"),
+            Some("monthly") => Some("*-*-01 00:00:00"),
+            Some("yearly") | Some("annually") => Some("*-01-01 00:00:00"),
+            Some("quarterly") => Some("*-01,04,07,10-01 00:00:00"),
+            Some("semiannually") => Some("*-01,07-01 00:00:00"),
+            _ => None,
+        };
+        if let Some(shorthand) = shorthand {
+            tokens.splice(0..1, shorthand.split(' '));
+        }
+
+        let mut tokens = tokens.into_iter().peekable();
+        let weekdays = match tokens.peek() {
+            Some(token) if token.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                let weekdays = parse_weekdays(token)?;
+                tokens.next();
+                weekdays
+            }
+            _ => vec![None],
+        };
+        let (months, days) = match tokens.peek() {
+            Some(token) if token.contains('-') => {
+                let date = parse_date(token)?;
+                tokens.next();
+                date
+            }
+            _ => (vec![None], vec![None]),
+        };
+        let (hours, minutes) = match tokens.peek() {
+            Some(token) if token.contains(':') => {
+                let time = parse_time(token)?;
+                tokens.next();
+                time
+            }
+            _ => (vec![Some(0)], vec![Some(0)]),
+        };
+        if let Some(token) = tokens.next() {
+            return Err(Error::UnsupportedOnCalendar(format!(
+                "\"{}\" is not a weekday, date or time (time zones are not supported)",
+                token
+            )));
+        }
+        if weekdays[0].is_some() && days[0].is_some() {
+            return Err(Error::UnsupportedOnCalendar(
+                "launchd fires when either the day of the month or the day of the week matches"
+                    .to_string(),
+            ));
+        }
+
+        let mut result = Vec::new();
+        for month in &months {
+            for day in &days {
+                for weekday in &weekdays {
+                    for hour in &hours {
+                        for minute in &minutes {
+                            result.push(CalendarInterval {
+                                minute: *minute,
+                                hour: *hour,
+                                day: *day,
+                                weekday: *weekday,
+                                month: *month,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if result.contains(&CalendarInterval::default()) {
+            return Err(Error::UnsupportedOnCalendar(
+                "the expression fires every minute, use StartInterval 60 instead".to_string(),
+            ));
+        }
+        Ok(result)
+    }
+}
+
+// Parses a comma separated list of weekdays and ranges ("Mon..Fri" or "Mon-Fri").
+fn parse_weekdays(token: &str) -> Result<Vec<Option<u8>>, Error> {
+    let mut weekdays = [false; 7];
+    for item in token.split(',').filter(|item| !item.is_empty()) {
+        let (first, last) = match item.split_once("..").or_else(|| item.split_once('-')) {
+            Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+            None => (parse_weekday(item)?, parse_weekday(item)?),
+        };
+        // Like systemd, ranges don't wrap around the end of the week, which is Sunday.
+        if first > last {
+            return Err(Error::InvalidOnCalendar(format!(
+                "the weekday range \"{}\" is reversed",
+                item
+            )));
+        }
+        for weekday in first..=last {
+            weekdays[usize::from((weekday + 1) % 7)] = true;
+        }
+    }
+    if !weekdays.contains(&true) {
+        return Err(Error::InvalidOnCalendar(format!(
+            "\"{}\" has no weekdays",
+            token
+        )));
+    }
+    Ok(collect_values(&weekdays, 0))
+}
+
+// Returns the position of a weekday in the week, from 0 (Monday) to 6 (Sunday). systemd accepts
+// the full names and their three letter abbreviations.
+fn parse_weekday(name: &str) -> Result<u8, Error> {
+    let name = name.to_lowercase();
+    WEEKDAYS
+        .iter()
+        .position(|weekday| *weekday == name || weekday[..3] == name)
+        .map(|weekday| weekday as u8)
+        .ok_or_else(|| Error::InvalidOnCalendar(format!("\"{}\" is not a weekday", name)))
+}
+
+type Values = Vec<Option<u8>>;
+
+// Parses "[Year-]Month-Day" into months and days.
+fn parse_date(token: &str) -> Result<(Values, Values), Error> {
+    if token.contains('~') {
+        return Err(Error::UnsupportedOnCalendar(
+            "launchd can't fire on the last days of a month".to_string(),
+        ));
+    }
+    let components: Vec<&str> = token.split('-').collect();
+    let (month, day) = match components.as_slice() {
+        [year, month, day] => {
+            if *year != "*" {
+                return Err(Error::UnsupportedOnCalendar(
+                    "launchd can't restrict the year".to_string(),
+                ));
+            }
+            (*month, *day)
+        }
+        [month, day] => (*month, *day),
+        _ => {
+            return Err(Error::InvalidOnCalendar(format!(
+                "\"{}\" is not a date",
+                token
+            )))
+        }
+    };
+    Ok((
+        parse_component(month, 1, 12, "month")?,
+        parse_component(day, 1, 31, "day")?,
+    ))
+}
+
+// Parses "Hour:Minute[:Second]" into hours and minutes.
+fn parse_time(token: &str) -> Result<(Values, Values), Error> {
+    let components: Vec<&str> = token.split(':').collect();
+    let (hour, minute) = match components.as_slice() {
+        [hour, minute] => (*hour, *minute),
+        [hour, minute, second] => {
+            if !second.starts_with('0') || second.contains(|c: char| c != '0' && c != '.') {
+                return Err(Error::UnsupportedOnCalendar(
+                    "launchd can only fire at the start of a minute".to_string(),
+                ));
+            }
+            (*hour, *minute)
+        }
+        _ => {
+            return Err(Error::InvalidOnCalendar(format!(
+                "\"{}\" is not a time",
+                token
+            )))
+        }
+    };
+    Ok((
+        parse_component(hour, 0, 23, "hour")?,
+        parse_component(minute, 0, 59, "minute")?,
+    ))
+}
+
+// Parses a comma separated list of values, ranges ("1..5") and repetitions ("0/15", "1..31/2").
+fn parse_component(component: &str, min: u8, max: u8, name: &str) -> Result<Values, Error> {
+    let invalid = || Error::InvalidOnCalendar(format!("\"{}\" is not a valid {}", component, name));
+    let value = |value: &str| -> Result<u8, Error> {
+        match value.parse::<u8>() {
+            Ok(value) if (min..=max).contains(&value) => Ok(value),
+            _ => Err(invalid()),
+        }
+    };
+
+    let mut values = vec![false; usize::from(max) + 1];
+    for item in component.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().map_err(|_| invalid())?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (first, last) = match range.split_once("..") {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // A repetition without a range continues until the end, e.g. "0/15".
+            None if item.contains('/') => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for v in (first..=last).step_by(usize::from(step)) {
+            values[usize::from(v)] = true;
+        }
+    }
+    if !values.contains(&true) {
+        return Err(invalid());
+    }
+    Ok(collect_values(&values[usize::from(min)..], min))
+}
+
+// Returns the set values, or a single None if every value is set.
+fn collect_values(values: &[bool], offset: u8) -> Values {
+    if values.iter().all(|set| *set) {
+        vec![None]
+    } else {
+        values
+            .iter()
+            .enumerate()
+            .filter(|(_, set)| **set)
+            .map(|(value, _)| Some(value as u8 + offset))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ci(
+        minute: Option<u8>,
+        hour: Option<u8>,
+        day: Option<u8>,
+        weekday: Option<u8>,
+        month: Option<u8>,
+    ) -> CalendarInterval {
+        CalendarInterval {
+            minute,
+            hour,
+            day,
+            weekday,
+            month,
+        }
+    }
+
+    #[test]
+    fn shorthands() {
+        let cases = [
+            ("hourly", vec![ci(Some(0), None, None, None, None)]),
+            ("daily", vec![ci(Some(0), Some(0), None, None, None)]),
+            ("weekly", vec![ci(Some(0), Some(0), None, Some(1), None)]),
+            ("monthly", vec![ci(Some(0), Some(0), Some(1), None, None)]),
+            ("yearly", vec![ci(Some(0), Some(0), Some(1), None, Some(1))]),
+            (
+                "semiannually",
+                vec![
+                    ci(Some(0), Some(0), Some(1), None, Some(1)),
+                    ci(Some(0), Some(0), Some(1), None, Some(7)),
+                ],
+            ),
+        ];
+        for (expression, expected) in cases.iter() {
+            assert_eq!(
+                &CalendarInterval::from_on_calendar(expression).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn lists_ranges_and_repetitions() {
+        let intervals = CalendarInterval::from_on_calendar("Mon..Fri *-*-* 9..17:0/15").unwrap();
+        assert_eq!(intervals.len(), 5 * 9 * 4);
+        assert_eq!(
+            CalendarInterval::to_cron_lines(&intervals).unwrap(),
+            vec!["*/15 9-17 * * 1-5"]
+        );
+
+        assert_eq!(
+            CalendarInterval::from_on_calendar("Mon-Fri *-*-* 09:00").unwrap(),
+            CalendarInterval::from_on_calendar("Mon..Fri *-*-* 09:00").unwrap()
+        );
+
+        let intervals = CalendarInterval::from_on_calendar("Sat..Sun 12:00").unwrap();
+        let weekdays: Vec<_> = intervals.iter().map(|ci| ci.weekday).collect();
+        assert_eq!(weekdays, vec![Some(0), Some(6)]);
+
+        assert_eq!(
+            CalendarInterval::from_on_calendar("tuesday,Wed 12:00").unwrap(),
+            CalendarInterval::from_on_calendar("Tue..Wed 12:00").unwrap()
+        );
+
+        let intervals = CalendarInterval::from_on_calendar("*-1,7-1..31/10 06:30:00").unwrap();
+        assert_eq!(
+            CalendarInterval::to_cron_lines(&intervals).unwrap(),
+            vec!["30 6 1-31/10 1,7 *"]
+        );
+    }
+
+    #[test]
+    fn unsupported_expressions() {
+        for expression in [
+            "2024-01-01",
+            "*:*:30",
+            "*:*:*",
+            "*-*~01",
+            "daily UTC",
+            "Mon *-*-01",
+            "minutely",
+            "Mon..Sun *:*",
+        ]
+        .iter()
+        {
+            assert!(
+                matches!(
+                    CalendarInterval::from_on_calendar(expression),
+                    Err(Error::UnsupportedOnCalendar(_))
+                ),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "Someday",
+            "*-13-01",
+            "25:00",
+            "*:0/0",
+            "1-2-3-4",
+            "*-*-* 10..5:00",
+            "*-12..1-01",
+            "Mon-Someday",
+            "Fri..Mon",
+            "Sun..Mon",
+            "tues 12:00",
+            "Wedn",
+        ]
+        .iter()
+        {
+            assert!(
+                matches!(
+                    CalendarInterval::from_on_calendar(expression),
+                    Err(Error::InvalidOnCalendar(_))
+                ),
+                "{}",
+                expression
+            );
+        }
+    }
+}
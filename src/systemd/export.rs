@@ -0,0 +1,575 @@
+// Conversion of a launchd job to systemd units, see systemd.service(5), systemd.timer(5),
+// systemd.socket(5) and systemd.exec(5).
+//
+// launchd keeps everything in one plist, whereas systemd splits a job into a .service unit and
+// the units that activate it. Keys that have no systemd equivalent are reported as warnings.
+use super::unit::{quote, quote_environment, UnitFile};
+use super::{SystemdUnits, Warning};
+use crate::keep_alive::KeepAliveType;
+use crate::sockets::{SocketFamily, SocketOptions, SocketType, Sockets};
+use crate::{CalendarInterval, Error, InetdCompatibility, Launchd, ResourceLimits};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+impl Launchd {
+    /// Converts this job to a systemd `.service` unit, and a `.timer` and `.socket` unit if the
+    /// job is started on a schedule or by its sockets.
+    ///
+    /// Keys without a systemd equivalent are left out and reported in
+    /// [`SystemdUnits::warnings`]. Returns an error if the job has neither a `Program` nor
+    /// `ProgramArguments`, as a service needs a command to run.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{CalendarInterval, Error, Launchd};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let launchd = Launchd::new("com.example.backup", "/usr/local/bin/backup")?
+    ///         .with_start_calendar_intervals(vec![CalendarInterval::default().with_hour(3)?.with_minute(0)?]);
+    ///     let units = launchd.to_systemd_units()?;
+    ///
+    ///     assert!(units.service().contains("ExecStart=/usr/local/bin/backup\n"));
+    ///     assert!(units.timer().unwrap().contains("OnCalendar=*-*-* 03:00:00\n"));
+    ///     assert!(units.warnings().is_empty());
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn to_systemd_units(&self) -> Result<SystemdUnits, Error> {
+        let mut warnings = Vec::new();
+        let mut warn = |key: &str, message: &str| warnings.push(Warning::new(key, message));
+
+        let mut service = UnitFile::default();
+        service.push("Unit", "Description", self.label.as_str());
+        service.push("Service", "ExecStart", self.exec_start()?);
+        if let Some(directory) = &self.working_directory {
+            service.push("Service", "WorkingDirectory", directory.as_str());
+        }
+        if let Some(directory) = &self.root_directory {
+            service.push("Service", "RootDirectory", directory.as_str());
+        }
+        if let Some(variables) = &self.environment_variables {
+            let mut variables: Vec<_> = variables.iter().collect();
+            variables.sort();
+            for (key, value) in variables {
+                service.push(
+                    "Service",
+                    "Environment",
+                    quote_environment(&format!("{}={}", key, value)),
+                );
+            }
+        }
+        if let Some(user) = &self.user_name {
+            service.push("Service", "User", user.as_str());
+        }
+        if let Some(group) = &self.group_name {
+            service.push("Service", "Group", group.as_str());
+        }
+        match &self.keep_alive {
+            Some(KeepAliveType::Enabled(true)) => service.push("Service", "Restart", "always"),
+            Some(KeepAliveType::Options(options)) => {
+                match options.successful_exit {
+                    Some(true) => service.push("Service", "Restart", "on-success"),
+                    Some(false) => service.push("Service", "Restart", "on-failure"),
+                    None => {}
+                }
+                if options.network_state.is_some()
+                    || options.path_state.is_some()
+                    || options.other_job_enabled.is_some()
+                {
+                    warn(
+                        "KeepAlive",
+                        "only SuccessfulExit can be expressed as Restart=; \
+                         use dependencies or a .path unit for the other conditions",
+                    );
+                }
+            }
+            _ => {}
+        }
+        if let Some(interval) = self.throttle_interval {
+            service.push("Service", "RestartSec", interval.to_string());
+        }
+        if let Some(timeout) = self.exit_time_out {
+            service.push("Service", "TimeoutStopSec", timeout.to_string());
+        }
+        if self.abandon_process_group == Some(true) {
+            service.push("Service", "KillMode", "process");
+        }
+        if let Some(nice) = self.nice {
+            service.push("Service", "Nice", nice.to_string());
+        }
+        if let Some(umask) = self.umask {
+            service.push("Service", "UMask", format!("{:04o}", umask));
+        }
+        if self.low_priority_io == Some(true) {
+            service.push("Service", "IOSchedulingClass", "idle");
+        }
+        if let Some(path) = &self.standard_in_path {
+            service.push("Service", "StandardInput", format!("file:{}", path));
+        }
+        if let Some(path) = &self.standard_out_path {
+            service.push("Service", "StandardOutput", format!("append:{}", path));
+        }
+        if let Some(path) = &self.standard_error_path {
+            service.push("Service", "StandardError", format!("append:{}", path));
+        }
+        push_resource_limits(
+            &mut service,
+            self.soft_resource_limits.as_ref(),
+            self.hard_resource_limits.as_ref(),
+        );
+
+        let timer = self.timer_unit();
+        let socket = self.socket_unit(&mut warn);
+        if self.run_at_load == Some(true)
+            || matches!(
+                self.keep_alive,
+                Some(KeepAliveType::Enabled(true)) | Some(KeepAliveType::Options(_))
+            )
+        {
+            service.push("Install", "WantedBy", "default.target");
+        }
+
+        for (key, unsupported) in [
+            ("Disabled", self.disabled == Some(true)),
+            ("BundleProgram", self.bundle_program.is_some()),
+            ("EnableGlobbing", self.enable_globbing == Some(true)),
+            ("EnableTransactions", self.enable_transactions == Some(true)),
+            (
+                "EnablePressuredExit",
+                self.enable_pressured_exit == Some(true),
+            ),
+            ("OnDemand", self.on_demand.is_some()),
+            ("ServiceIPC", self.service_ipc.is_some()),
+            ("LimitLoadToHosts", self.limit_load_to_hosts.is_some()),
+            ("LimitLoadFromHosts", self.limit_load_from_hosts.is_some()),
+            (
+                "LimitLoadToSessionType",
+                self.limit_load_to_session_type.is_some(),
+            ),
+            ("LimitLoadToHardware", self.limit_load_to_hardware.is_some()),
+            (
+                "LimitLoadFromHardware",
+                self.limit_load_from_hardware.is_some(),
+            ),
+            ("TimeOut", self.time_out.is_some()),
+            ("InitGroups", self.init_groups == Some(false)),
+            ("WatchPaths", self.watch_paths.is_some()),
+            ("QueueDirectories", self.queue_directories.is_some()),
+            ("StartOnMount", self.start_on_mount == Some(true)),
+            ("Debug", self.debug == Some(true)),
+            ("WaitForDebugger", self.wait_for_debugger == Some(true)),
+            ("ProcessType", self.process_type.is_some()),
+            (
+                "LowPriorityBackgroundIO",
+                self.low_priority_background_io == Some(true),
+            ),
+            (
+                "MaterializeDatalessFiles",
+                self.materialize_dataless_files.is_some(),
+            ),
+            ("LaunchOnlyOnce", self.launch_only_once == Some(true)),
+            ("MachServices", self.mach_services.is_some()),
+            ("LaunchEvents", self.launch_events.is_some()),
+            ("HopefullyExitsLast", self.hopefully_exits_last.is_some()),
+            ("HopefullyExitsFirst", self.hopefully_exits_first.is_some()),
+            ("SessionCreate", self.session_create == Some(true)),
+            ("LegacyTimers", self.legacy_timers.is_some()),
+        ] {
+            if unsupported {
+                warn(key, "has no systemd equivalent");
+            }
+        }
+        if self.inetd_compatibility.is_some() && socket.is_none() {
+            warn(
+                "inetdCompatibility",
+                "needs Sockets to be converted to Accept=",
+            );
+        }
+
+        Ok(SystemdUnits {
+            service: service.to_string(),
+            timer: timer.map(|timer| timer.to_string()),
+            socket: socket.map(|socket| socket.to_string()),
+            warnings,
+        })
+    }
+
+    // launchd runs Program with ProgramArguments as argv, which systemd expresses with "@".
+    fn exec_start(&self) -> Result<String, Error> {
+        let arguments = self.program_arguments.as_deref().unwrap_or_default();
+        let command: Vec<String> = match (&self.program, arguments.split_first()) {
+            (Some(program), Some((argv0, rest))) if program != argv0 => {
+                let mut command = vec![format!("@{}", quote(program)), quote(argv0)];
+                command.extend(rest.iter().map(|argument| quote(argument)));
+                command
+            }
+            (_, Some(_)) => arguments.iter().map(|argument| quote(argument)).collect(),
+            (Some(program), None) => vec![quote(program)],
+            (None, None) => {
+                return Err(Error::SystemdConversion(
+                    "the job has neither a Program nor ProgramArguments".to_string(),
+                ))
+            }
+        };
+        Ok(command.join(" "))
+    }
+
+    fn timer_unit(&self) -> Option<UnitFile> {
+        let calendar_intervals = self.start_calendar_intervals.as_deref().unwrap_or_default();
+        if self.start_interval.is_none() && calendar_intervals.is_empty() {
+            return None;
+        }
+        let mut timer = UnitFile::default();
+        timer.push("Unit", "Description", format!("Timer for {}", self.label));
+        for ci in calendar_intervals {
+            // launchd fires when either the Day or the Weekday matches, systemd when both do.
+            if ci.day.is_some() && ci.weekday.is_some() {
+                timer.push(
+                    "Timer",
+                    "OnCalendar",
+                    on_calendar(&CalendarInterval {
+                        weekday: None,
+                        ..*ci
+                    }),
+                );
+                timer.push(
+                    "Timer",
+                    "OnCalendar",
+                    on_calendar(&CalendarInterval { day: None, ..*ci }),
+                );
+            } else {
+                timer.push("Timer", "OnCalendar", on_calendar(ci));
+            }
+        }
+        if let Some(interval) = self.start_interval {
+            timer.push("Timer", "OnActiveSec", interval.to_string());
+            timer.push("Timer", "OnUnitActiveSec", interval.to_string());
+        }
+        timer.push("Install", "WantedBy", "timers.target");
+        Some(timer)
+    }
+
+    fn socket_unit(&self, warn: &mut impl FnMut(&str, &str)) -> Option<UnitFile> {
+        let mut sockets: Vec<(&String, &SocketOptions)> = match self.sockets.as_ref()? {
+            Sockets::Dictionary(sockets) => sockets.iter().collect(),
+            Sockets::Array(sockets) => sockets.iter().flatten().collect(),
+        };
+        sockets.sort_by_key(|(name, _)| *name);
+
+        let mut socket = UnitFile::default();
+        socket.push("Unit", "Description", format!("Sockets for {}", self.label));
+        let mut listening = false;
+        for (name, options) in sockets {
+            let key = format!("Sockets.{}", name);
+            if options.sock_passive == Some(false) {
+                warn(&key, "systemd can't connect sockets for a service");
+                continue;
+            }
+            let directive = match options.sock_type {
+                Some(SocketType::Dgram) => "ListenDatagram",
+                Some(SocketType::Seqpacket) => "ListenSequentialPacket",
+                Some(SocketType::Stream) | None => "ListenStream",
+            };
+            let address = match (&options.sock_path_name, &options.sock_service_name) {
+                (Some(path), _) => path.clone(),
+                (None, Some(port)) if port.parse::<u16>().is_ok() => {
+                    match (&options.sock_node_name, &options.sock_family) {
+                        (Some(node), _) if node.contains(':') => format!("[{}]:{}", node, port),
+                        (Some(node), _) => format!("{}:{}", node, port),
+                        (None, Some(SocketFamily::IPv4)) => format!("0.0.0.0:{}", port),
+                        (None, Some(SocketFamily::IPv6)) => format!("[::]:{}", port),
+                        (None, _) => port.clone(),
+                    }
+                }
+                (None, Some(_)) => {
+                    warn(
+                        &key,
+                        "systemd needs a port number instead of a service name",
+                    );
+                    continue;
+                }
+                (None, None) => {
+                    warn(&key, "has neither a SockPathName nor a SockServiceName");
+                    continue;
+                }
+            };
+            socket.push("Socket", directive, address);
+            listening = true;
+            if let Some(mode) = options.sock_path_mode {
+                socket.push("Socket", "SocketMode", format!("{:04o}", mode));
+            }
+            if options.bonjour.is_some()
+                || options.multicast_group.is_some()
+                || options.secure_socket_with_key.is_some()
+            {
+                warn(
+                    &key,
+                    "Bonjour, MulticastGroup and SecureSocketWithKey have no systemd equivalent",
+                );
+            }
+        }
+        // systemd refuses to load a socket unit that doesn't listen on anything.
+        if !listening {
+            return None;
+        }
+        if let Some(wait) = self
+            .inetd_compatibility
+            .as_ref()
+            .and_then(|inetd| inetd.get(&InetdCompatibility::Wait))
+        {
+            socket.push("Socket", "Accept", if *wait { "no" } else { "yes" });
+            if !wait {
+                warn(
+                    "inetdCompatibility",
+                    "Accept=yes starts a service per connection, which must be a template unit (NAME@.service)",
+                );
+            }
+        }
+        warn(
+            "Sockets",
+            "the program must use sd_listen_fds(3) instead of launch_activate_socket(3)",
+        );
+        socket.push("Install", "WantedBy", "sockets.target");
+        Some(socket)
+    }
+}
+
+// Formats an interval as "[Weekday] *-Month-Day Hour:Minute:00". Day and Weekday can't both be set.
+fn on_calendar(ci: &CalendarInterval) -> String {
+    let field = |value: Option<u8>| value.map_or("*".to_string(), |value| format!("{:02}", value));
+    let weekday = ci
+        .weekday
+        .map(|weekday| format!("{} ", WEEKDAYS[usize::from(weekday % 7)]))
+        .unwrap_or_default();
+    format!(
+        "{}*-{}-{} {}:{}:00",
+        weekday,
+        field(ci.month),
+        field(ci.day),
+        field(ci.hour),
+        field(ci.minute)
+    )
+}
+
+// Every limit becomes "soft:hard". A limit that is only set on one side is unlimited on the other.
+fn push_resource_limits(
+    unit: &mut UnitFile,
+    soft: Option<&ResourceLimits>,
+    hard: Option<&ResourceLimits>,
+) {
+    let limits = |limits: Option<&ResourceLimits>| {
+        let limits = limits.cloned().unwrap_or_default();
+        [
+            ("LimitCORE", limits.core),
+            ("LimitCPU", limits.cpu),
+            ("LimitDATA", limits.data),
+            ("LimitFSIZE", limits.file_size),
+            ("LimitMEMLOCK", limits.memory_lock),
+            ("LimitNOFILE", limits.number_of_files),
+            ("LimitNPROC", limits.number_of_processes),
+            ("LimitRSS", limits.resident_set_size),
+            ("LimitSTACK", limits.stack),
+        ]
+    };
+    let value =
+        |limit: Option<u64>| limit.map_or("infinity".to_string(), |limit| limit.to_string());
+    for ((key, soft), (_, hard)) in limits(soft).iter().zip(limits(hard).iter()) {
+        if soft.is_some() || hard.is_some() {
+            unit.push("Service", key, format!("{}:{}", value(*soft), value(*hard)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keep_alive::KeepAliveOptions;
+    use crate::sockets::{Socket, SocketOptions};
+    use std::collections::HashMap;
+
+    #[test]
+    fn service_directives() {
+        let launchd = Launchd::new("com.example.agent", "/usr/bin/env")
+            .unwrap()
+            .with_program_arguments(vec![
+                "env".to_string(),
+                "two words".to_string(),
+                "100%".to_string(),
+            ])
+            .with_working_directory("/var/lib/agent")
+            .unwrap()
+            .with_environment_variables(HashMap::from([
+                ("B".to_string(), "2".to_string()),
+                ("A".to_string(), "1 2".to_string()),
+            ]))
+            .with_user_name("agent")
+            .with_group_name("staff")
+            .with_keep_alive(KeepAliveOptions::new().with_successful_exit(false).into())
+            .with_nice(5)
+            .with_umask(0o22)
+            .with_standard_out_path("/var/log/agent.log")
+            .unwrap()
+            .with_soft_resource_limits(ResourceLimits::new().with_number_of_files(256))
+            .with_hard_resource_limits(
+                ResourceLimits::new()
+                    .with_number_of_files(1024)
+                    .with_core(0),
+            );
+        let units = launchd.to_systemd_units().unwrap();
+        assert_eq!(
+            units.service(),
+            "[Unit]
+Description=com.example.agent
+
+[Service]
+ExecStart=@/usr/bin/env env \"two words\" 100%%
+WorkingDirectory=/var/lib/agent
+Environment=\"A=1 2\"
+Environment=B=2
+User=agent
+Group=staff
+Restart=on-failure
+Nice=5
+UMask=0022
+StandardOutput=append:/var/log/agent.log
+LimitCORE=infinity:0
+LimitNOFILE=256:1024
+
+[Install]
+WantedBy=default.target
+"
+        );
+        assert_eq!(units.timer(), None);
+        assert_eq!(units.socket(), None);
+        assert!(units.warnings().is_empty());
+    }
+
+    #[test]
+    fn timer_splits_day_and_weekday() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_interval(300)
+            .with_start_calendar_intervals(vec![CalendarInterval::default()
+                .with_day(1)
+                .unwrap()
+                .with_weekday(7)
+                .unwrap()
+                .with_hour(9)
+                .unwrap()]);
+        assert_eq!(
+            launchd.to_systemd_units().unwrap().timer(),
+            Some(
+                "[Unit]
+Description=Timer for LABEL
+
+[Timer]
+OnCalendar=*-*-01 09:*:00
+OnCalendar=Sun *-*-* 09:*:00
+OnActiveSec=300
+OnUnitActiveSec=300
+
+[Install]
+WantedBy=timers.target
+"
+            )
+        );
+    }
+
+    #[test]
+    fn socket_and_warnings() {
+        let launchd = Launchd::new("LABEL", "/usr/sbin/daemon")
+            .unwrap()
+            .with_socket(
+                Socket::new(
+                    "Listener",
+                    SocketOptions::new()
+                        .with_service_name("8080")
+                        .with_family(SocketFamily::IPv6),
+                )
+                .into(),
+            )
+            .with_socket(
+                Socket::new("Named", SocketOptions::new().with_service_name("http")).into(),
+            )
+            .with_inetd_compatibility(true)
+            .with_watch_paths(vec!["/etc/daemon.conf".to_string()])
+            .disabled();
+        let units = launchd.to_systemd_units().unwrap();
+        assert_eq!(
+            units.socket(),
+            Some(
+                "[Unit]
+Description=Sockets for LABEL
+
+[Socket]
+ListenStream=[::]:8080
+Accept=no
+
+[Install]
+WantedBy=sockets.target
+"
+            )
+        );
+        let keys: Vec<_> = units.warnings().iter().map(Warning::key).collect();
+        assert_eq!(
+            keys,
+            vec!["Sockets.Named", "Sockets", "Disabled", "WatchPaths"]
+        );
+    }
+
+    #[test]
+    fn no_socket_unit_without_a_listen_directive() {
+        let launchd = Launchd::new("LABEL", "/usr/sbin/sshd")
+            .unwrap()
+            .with_socket(
+                Socket::new("Listener", SocketOptions::new().with_service_name("ssh")).into(),
+            );
+        let units = launchd.to_systemd_units().unwrap();
+        assert_eq!(units.socket(), None);
+        let keys: Vec<_> = units.warnings().iter().map(Warning::key).collect();
+        assert_eq!(keys, vec!["Sockets.Listener"]);
+    }
+
+    #[test]
+    fn no_timer_unit_without_a_schedule() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(vec![]);
+        assert_eq!(launchd.to_systemd_units().unwrap().timer(), None);
+    }
+
+    #[test]
+    fn environment_keeps_dollar_signs() {
+        let launchd = Launchd::new("com.example.agent", "/bin/echo")
+            .unwrap()
+            .with_environment_variables(HashMap::from([
+                ("FOO".to_string(), "$HOME".to_string()),
+                ("RATE".to_string(), "100% of $5".to_string()),
+            ]));
+        let units = launchd.to_systemd_units().unwrap();
+        let environment: Vec<_> = units
+            .service()
+            .lines()
+            .filter(|line| line.starts_with("Environment="))
+            .collect();
+        assert_eq!(
+            environment,
+            vec!["Environment=FOO=$HOME", "Environment=\"RATE=100%% of $5\""]
+        );
+
+        let (imported, _) = Launchd::from_systemd_units("com.example.agent", &units).unwrap();
+        assert_eq!(
+            imported.environment_variables(),
+            launchd.environment_variables()
+        );
+    }
+
+    #[test]
+    fn missing_program_is_an_error() {
+        assert!(matches!(
+            Launchd::default().to_systemd_units(),
+            Err(Error::SystemdConversion(_))
+        ));
+    }
+}
@@ -0,0 +1,559 @@
+// Conversion of systemd units to a launchd job, the reverse of export.rs.
+//
+// Every directive that isn't converted is reported as a warning with the key "Section.Directive",
+// so nothing is dropped silently.
+use super::unit::{split_environment, split_words, UnitFile};
+use super::{SystemdUnits, Warning};
+use crate::keep_alive::{KeepAliveOptions, KeepAliveType};
+use crate::sockets::{SocketFamily, SocketOptions, SocketType, Sockets};
+use crate::{CalendarInterval, Error, InetdCompatibility, Launchd, ResourceLimits};
+use std::collections::HashMap;
+
+impl Launchd {
+    /// Converts systemd units to a launchd job with the given label. The `.timer` and `.socket`
+    /// units of `units` are optional.
+    ///
+    /// Returns the job together with a warning for every directive that was dropped or only
+    /// approximated. Returns an error if a unit can't be parsed or the service has no
+    /// `ExecStart=`.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd, SystemdUnits};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let units = SystemdUnits::new("[Service]\nExecStart=/usr/local/bin/backup --all\n")
+    ///         .with_timer("[Timer]\nOnCalendar=daily\n");
+    ///     let (launchd, warnings) = Launchd::from_systemd_units("com.example.backup", &units)?;
+    ///
+    ///     assert_eq!(launchd.start_calendar_intervals_to_cron()?, vec!["0 0 * * *"]);
+    ///     assert!(warnings.is_empty());
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn from_systemd_units<S: AsRef<str>>(
+        label: S,
+        units: &SystemdUnits,
+    ) -> Result<(Launchd, Vec<Warning>), Error> {
+        let mut importer = Importer {
+            launchd: Launchd {
+                label: label.as_ref().to_string(),
+                ..Default::default()
+            },
+            warnings: Vec::new(),
+        };
+        importer.service(&UnitFile::parse(&units.service)?)?;
+        if let Some(timer) = &units.timer {
+            importer.timer(&UnitFile::parse(timer)?);
+        }
+        if let Some(socket) = &units.socket {
+            importer.socket(&UnitFile::parse(socket)?);
+        }
+        Ok((importer.launchd, importer.warnings))
+    }
+}
+
+struct Importer {
+    launchd: Launchd,
+    warnings: Vec<Warning>,
+}
+
+impl Importer {
+    fn warn(&mut self, section: &str, key: &str, message: &str) {
+        self.warnings
+            .push(Warning::new(format!("{}.{}", section, key), message));
+    }
+
+    fn service(&mut self, unit: &UnitFile) -> Result<(), Error> {
+        let mut soft = ResourceLimits::default();
+        let mut hard = ResourceLimits::default();
+        for (section, key, value) in unit.directives() {
+            match (section, key) {
+                // The description to_systemd_units generates, which has nothing to import.
+                ("Unit", "Description") if value == self.launchd.label => {}
+                ("Service", "ExecStart") => {
+                    if self.launchd.program_arguments.is_some() {
+                        self.warn(section, key, "launchd can only run a single command");
+                    } else {
+                        self.exec_start(value)?;
+                    }
+                }
+                ("Service", "Type") if value == "simple" || value == "exec" => {}
+                ("Service", "WorkingDirectory") if !value.starts_with('~') => {
+                    let directory = value.strip_prefix('-').unwrap_or(value);
+                    self.launchd.working_directory = Some(directory.to_string());
+                }
+                ("Service", "RootDirectory") => {
+                    self.launchd.root_directory = Some(value.to_string());
+                }
+                ("Service", "Environment") => {
+                    let variables = self
+                        .launchd
+                        .environment_variables
+                        .get_or_insert_with(HashMap::new);
+                    for word in split_environment(value)? {
+                        if let Some((name, value)) = word.split_once('=') {
+                            variables.insert(name.to_string(), value.to_string());
+                        }
+                    }
+                }
+                ("Service", "User") => self.launchd.user_name = Some(value.to_string()),
+                ("Service", "Group") => self.launchd.group_name = Some(value.to_string()),
+                ("Service", "Restart") => {
+                    let successful_exit = match value {
+                        "no" => continue,
+                        "always" => {
+                            self.launchd.keep_alive = Some(KeepAliveType::Enabled(true));
+                            continue;
+                        }
+                        "on-success" => true,
+                        "on-failure" => false,
+                        _ => {
+                            self.warn(section, key, "approximated as restarting on failure");
+                            false
+                        }
+                    };
+                    self.launchd.keep_alive = Some(
+                        KeepAliveOptions::new()
+                            .with_successful_exit(successful_exit)
+                            .into(),
+                    );
+                }
+                ("Service", "RestartSec") => match parse_seconds(value) {
+                    Some(seconds) => self.launchd.throttle_interval = Some(seconds),
+                    None => self.warn(section, key, "is not a whole number of seconds"),
+                },
+                ("Service", "TimeoutStopSec") => match parse_seconds(value) {
+                    Some(seconds) => self.launchd.exit_time_out = Some(seconds),
+                    None => self.warn(section, key, "is not a whole number of seconds"),
+                },
+                ("Service", "KillMode") if value == "process" => {
+                    self.launchd.abandon_process_group = Some(true);
+                }
+                ("Service", "KillMode") if value == "control-group" => {}
+                ("Service", "Nice") => match value.parse() {
+                    Ok(nice) => self.launchd.nice = Some(nice),
+                    Err(_) => self.warn(section, key, "is not a number"),
+                },
+                ("Service", "UMask") => match u16::from_str_radix(value, 8) {
+                    Ok(umask) => self.launchd.umask = Some(umask),
+                    Err(_) => self.warn(section, key, "is not an octal number"),
+                },
+                ("Service", "IOSchedulingClass") if value == "idle" => {
+                    self.launchd.low_priority_io = Some(true);
+                }
+                ("Service", "StandardInput") if value.starts_with("file:") => {
+                    self.launchd.standard_in_path = Some(value[5..].to_string());
+                }
+                ("Service", "StandardOutput") | ("Service", "StandardError") => {
+                    let path = value
+                        .strip_prefix("append:")
+                        .or_else(|| value.strip_prefix("file:"));
+                    match path {
+                        Some(path) if key == "StandardOutput" => {
+                            self.launchd.standard_out_path = Some(path.to_string());
+                        }
+                        Some(path) => self.launchd.standard_error_path = Some(path.to_string()),
+                        None => self.warn(section, key, "launchd can only write to a file"),
+                    }
+                }
+                ("Service", limit) if limit.starts_with("Limit") => {
+                    let (soft_value, hard_value) = value.split_once(':').unwrap_or((value, value));
+                    match (
+                        resource_limit(&mut soft, limit),
+                        resource_limit(&mut hard, limit),
+                        parse_limit(soft_value),
+                        parse_limit(hard_value),
+                    ) {
+                        (
+                            Some(soft_field),
+                            Some(hard_field),
+                            Some(soft_value),
+                            Some(hard_value),
+                        ) => {
+                            *soft_field = soft_value;
+                            *hard_field = hard_value;
+                        }
+                        _ => self.warn(section, key, "has no launchd equivalent"),
+                    }
+                }
+                ("Install", "WantedBy")
+                    if value == "default.target" || value == "multi-user.target" =>
+                {
+                    self.launchd.run_at_load = Some(true);
+                }
+                _ => self.warn(section, key, "has no launchd equivalent"),
+            }
+        }
+        if self.launchd.program_arguments.is_none() {
+            return Err(Error::InvalidUnitFile(
+                "the service has no ExecStart=".to_string(),
+            ));
+        }
+        if soft != ResourceLimits::default() {
+            self.launchd.soft_resource_limits = Some(soft);
+        }
+        if hard != ResourceLimits::default() {
+            self.launchd.hard_resource_limits = Some(hard);
+        }
+        Ok(())
+    }
+
+    // The prefixes of ExecStart= change how the command is run. "@" passes the second word as
+    // argv[0], which launchd expresses with Program.
+    fn exec_start(&mut self, value: &str) -> Result<(), Error> {
+        let mut value = value;
+        let mut argv0 = false;
+        while let Some(prefix) = value.chars().next().filter(|c| "@-:+!".contains(*c)) {
+            match prefix {
+                '@' => argv0 = true,
+                '-' => {}
+                _ => self.warn(
+                    "Service",
+                    "ExecStart",
+                    &format!("the \"{}\" prefix has no launchd equivalent", prefix),
+                ),
+            }
+            value = &value[1..];
+        }
+        if expands(value) {
+            self.warn(
+                "Service",
+                "ExecStart",
+                "launchd doesn't expand variables or specifiers",
+            );
+        }
+        let mut words = split_words(value)?;
+        if argv0 && !words.is_empty() {
+            self.launchd.program = Some(words.remove(0));
+        }
+        self.launchd.program_arguments = Some(words);
+        Ok(())
+    }
+
+    fn timer(&mut self, unit: &UnitFile) {
+        let mut intervals = Vec::new();
+        for (section, key, value) in unit.directives() {
+            match (section, key) {
+                ("Unit", "Description") if value == format!("Timer for {}", self.launchd.label) => {
+                }
+                ("Timer", "OnCalendar") => match CalendarInterval::from_on_calendar(value) {
+                    Ok(mut parsed) => intervals.append(&mut parsed),
+                    Err(error) => self.warn(section, key, &error.to_string()),
+                },
+                ("Timer", "OnActiveSec") | ("Timer", "OnUnitActiveSec") => {
+                    match (parse_seconds(value), self.launchd.start_interval) {
+                        (Some(seconds), None) => self.launchd.start_interval = Some(seconds),
+                        (Some(seconds), Some(interval)) if seconds == interval => {}
+                        _ => self.warn(section, key, "launchd only has a single StartInterval"),
+                    }
+                }
+                ("Install", "WantedBy") if value == "timers.target" => {}
+                _ => self.warn(section, key, "has no launchd equivalent"),
+            }
+        }
+        if !intervals.is_empty() {
+            self.launchd.start_calendar_intervals = Some(intervals);
+        }
+    }
+
+    fn socket(&mut self, unit: &UnitFile) {
+        let mut sockets: Vec<SocketOptions> = Vec::new();
+        let mut name = "Listeners".to_string();
+        let mut mode = None;
+        for (section, key, value) in unit.directives() {
+            let sock_type = match (section, key) {
+                ("Unit", "Description")
+                    if value == format!("Sockets for {}", self.launchd.label) =>
+                {
+                    continue
+                }
+                ("Socket", "ListenStream") => SocketType::Stream,
+                ("Socket", "ListenDatagram") => SocketType::Dgram,
+                ("Socket", "ListenSequentialPacket") => SocketType::Seqpacket,
+                ("Socket", "FileDescriptorName") => {
+                    name = value.to_string();
+                    continue;
+                }
+                ("Socket", "SocketMode") => {
+                    match i128::from_str_radix(value, 8) {
+                        Ok(value) => mode = Some(value),
+                        Err(_) => self.warn(section, key, "is not an octal number"),
+                    }
+                    continue;
+                }
+                ("Socket", "Accept") => {
+                    match value {
+                        "yes" | "true" | "1" => {
+                            self.launchd.inetd_compatibility =
+                                Some(HashMap::from([(InetdCompatibility::Wait, false)]))
+                        }
+                        "no" | "false" | "0" => {}
+                        _ => self.warn(section, key, "is not a boolean"),
+                    }
+                    continue;
+                }
+                ("Install", "WantedBy") if value == "sockets.target" => continue,
+                _ => {
+                    self.warn(section, key, "has no launchd equivalent");
+                    continue;
+                }
+            };
+            match listen_address(value) {
+                Some(options) => sockets.push(options.with_type(sock_type)),
+                None => self.warn(section, key, "launchd can't listen on this address"),
+            }
+        }
+
+        let mut dictionary = HashMap::new();
+        for (i, mut options) in sockets.into_iter().enumerate() {
+            if options.sock_path_name.is_some() {
+                options.sock_path_mode = mode;
+            }
+            let name = if i == 0 {
+                name.clone()
+            } else {
+                format!("{}{}", name, i + 1)
+            };
+            dictionary.insert(name, options);
+        }
+        if !dictionary.is_empty() {
+            self.launchd.sockets = Some(Sockets::Dictionary(dictionary));
+        }
+    }
+}
+
+// Returns whether systemd would expand a variable ("$VAR") or a specifier ("%n") in a command.
+fn expands(command: &str) -> bool {
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if (c == '$' || c == '%') && chars.next() != Some(c) {
+            return true;
+        }
+    }
+    false
+}
+
+// Parses "/path", "port", "address:port" or "[address]:port".
+fn listen_address(value: &str) -> Option<SocketOptions> {
+    if value.starts_with('/') {
+        return SocketOptions::new().with_path_name(value).ok();
+    }
+    let (node, port) = match value.rsplit_once(':') {
+        Some((node, port)) => (Some(node), port),
+        None => (None, value),
+    };
+    port.parse::<u16>().ok()?;
+    let options = SocketOptions::new().with_service_name(port);
+    Some(match node {
+        Some(node) if node.starts_with('[') && node.ends_with(']') => options
+            .with_node_name(&node[1..node.len() - 1])
+            .with_family(SocketFamily::IPv6),
+        Some(node) if node.contains(':') => return None,
+        Some(node) => options.with_node_name(node).with_family(SocketFamily::IPv4),
+        None => options,
+    })
+}
+
+// Parses a systemd time span like "90", "5min" or "1h 30s" into whole seconds.
+fn parse_seconds(value: &str) -> Option<u32> {
+    let mut total: u32 = 0;
+    for part in value.split_whitespace() {
+        let split = part
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(part.len());
+        let number: u32 = part[..split].parse().ok()?;
+        let unit = match &part[split..] {
+            "" | "s" | "sec" | "second" | "seconds" => 1,
+            "m" | "min" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(number.checked_mul(unit)?)?;
+    }
+    Some(total)
+}
+
+// Parses a resource limit like "1024", "8M" or "infinity". Unlimited is represented as None.
+fn parse_limit(value: &str) -> Option<Option<u64>> {
+    if value == "infinity" {
+        return Some(None);
+    }
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let number: u64 = value[..split].parse().ok()?;
+    let exponent = ["", "K", "M", "G", "T", "P", "E"]
+        .iter()
+        .position(|suffix| *suffix == &value[split..])?;
+    number.checked_mul(1024u64.pow(exponent as u32)).map(Some)
+}
+
+fn resource_limit<'a>(limits: &'a mut ResourceLimits, key: &str) -> Option<&'a mut Option<u64>> {
+    Some(match key {
+        "LimitCORE" => &mut limits.core,
+        "LimitCPU" => &mut limits.cpu,
+        "LimitDATA" => &mut limits.data,
+        "LimitFSIZE" => &mut limits.file_size,
+        "LimitMEMLOCK" => &mut limits.memory_lock,
+        "LimitNOFILE" => &mut limits.number_of_files,
+        "LimitNPROC" => &mut limits.number_of_processes,
+        "LimitRSS" => &mut limits.resident_set_size,
+        "LimitSTACK" => &mut limits.stack,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::Socket;
+
+    #[test]
+    fn service_directives() {
+        let units = SystemdUnits::new(
+            "[Unit]
+Description=Example agent
+
+[Service]
+Type=simple
+ExecStart=@/usr/bin/env env \"two words\" 100%%
+WorkingDirectory=-/var/lib/agent
+Environment=\"A=1 2\" B=2
+User=agent
+Restart=on-failure
+RestartSec=1min
+UMask=0022
+StandardOutput=append:/var/log/agent.log
+StandardError=journal
+LimitNOFILE=256:1K
+LimitCORE=0
+ProtectSystem=strict
+
+[Install]
+WantedBy=default.target
+",
+        );
+        let (launchd, warnings) = Launchd::from_systemd_units("LABEL", &units).unwrap();
+        let expected = Launchd::new("LABEL", "/usr/bin/env")
+            .unwrap()
+            .with_program_arguments(vec![
+                "env".to_string(),
+                "two words".to_string(),
+                "100%".to_string(),
+            ])
+            .with_working_directory("/var/lib/agent")
+            .unwrap()
+            .with_environment_variables(HashMap::from([
+                ("A".to_string(), "1 2".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ]))
+            .with_user_name("agent")
+            .with_keep_alive(KeepAliveOptions::new().with_successful_exit(false).into())
+            .with_throttle_interval(60)
+            .with_umask(0o22)
+            .with_standard_out_path("/var/log/agent.log")
+            .unwrap()
+            .with_soft_resource_limits(ResourceLimits::new().with_core(0).with_number_of_files(256))
+            .with_hard_resource_limits(
+                ResourceLimits::new()
+                    .with_core(0)
+                    .with_number_of_files(1024),
+            )
+            .run_at_load();
+        assert_eq!(launchd, expected);
+
+        let keys: Vec<_> = warnings.iter().map(Warning::key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "Unit.Description",
+                "Service.StandardError",
+                "Service.ProtectSystem"
+            ]
+        );
+    }
+
+    #[test]
+    fn timer_and_socket() {
+        let units = SystemdUnits::new("[Service]\nExecStart=/usr/sbin/daemon\n")
+            .with_timer(
+                "[Timer]\nOnCalendar=Mon..Fri 09:00\nOnCalendar=*-*-* *:*:30\nOnUnitActiveSec=5m\nOnBootSec=1h\n",
+            )
+            .with_socket(
+                "[Socket]\nListenStream=127.0.0.1:8080\nListenStream=/var/run/daemon.sock\nSocketMode=0600\nAccept=yes\n",
+            );
+        let (launchd, warnings) = Launchd::from_systemd_units("LABEL", &units).unwrap();
+        assert_eq!(
+            launchd.start_calendar_intervals_to_cron().unwrap(),
+            vec!["0 9 * * 1-5"]
+        );
+        assert_eq!(launchd.start_interval, Some(300));
+        assert_eq!(
+            launchd.sockets,
+            Some(Sockets::Dictionary(HashMap::from([
+                (
+                    "Listeners".to_string(),
+                    SocketOptions::new()
+                        .with_service_name("8080")
+                        .with_node_name("127.0.0.1")
+                        .with_family(SocketFamily::IPv4)
+                        .with_type(SocketType::Stream)
+                ),
+                (
+                    "Listeners2".to_string(),
+                    SocketOptions::new()
+                        .with_path_name("/var/run/daemon.sock")
+                        .unwrap()
+                        .with_type(SocketType::Stream)
+                        .with_path_mode(0o600)
+                ),
+            ])))
+        );
+        assert!(launchd.inetd_compatibility.is_some());
+        let keys: Vec<_> = warnings.iter().map(Warning::key).collect();
+        assert_eq!(keys, vec!["Timer.OnCalendar", "Timer.OnBootSec"]);
+    }
+
+    #[test]
+    fn exported_units_round_trip() {
+        let launchd = Launchd::new("LABEL", "/usr/sbin/daemon")
+            .unwrap()
+            .with_program_arguments(vec!["daemon".to_string(), "--foreground".to_string()])
+            .with_keep_alive(true.into())
+            .with_nice(-5)
+            .with_start_calendar_intervals(vec![CalendarInterval::default()
+                .with_minute(0)
+                .unwrap()])
+            .with_socket(
+                Socket::new(
+                    "Listeners",
+                    SocketOptions::new()
+                        .with_service_name("53")
+                        .with_type(SocketType::Dgram),
+                )
+                .into(),
+            );
+        let (imported, warnings) =
+            Launchd::from_systemd_units("LABEL", &launchd.to_systemd_units().unwrap()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(imported, launchd.run_at_load());
+    }
+
+    #[test]
+    fn invalid_units() {
+        for service in [
+            "[Service]\nType=simple\n",
+            "ExecStart=/bin/true\n",
+            "[Service]\nExecStart=\"/bin/true\n",
+        ] {
+            assert!(matches!(
+                Launchd::from_systemd_units("LABEL", &SystemdUnits::new(service)),
+                Err(Error::InvalidUnitFile(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Conversion between launchd and systemd.
+//
+// See https://www.freedesktop.org/software/systemd/man/systemd.time.html for the calendar syntax.
+mod calendar;
+mod export;
+mod import;
+mod unit;
+
+use std::fmt;
+
+/// The systemd units equivalent to a launchd job, created by
+/// [`Launchd::to_systemd_units`](crate::Launchd::to_systemd_units).
+///
+/// The `.timer` and `.socket` units activate the `.service` unit with the same name, so they
+/// should be installed next to each other, e.g. as `LABEL.service` and `LABEL.timer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemdUnits {
+    pub(crate) service: String,
+    pub(crate) timer: Option<String>,
+    pub(crate) socket: Option<String>,
+    pub(crate) warnings: Vec<Warning>,
+}
+
+impl SystemdUnits {
+    /// Creates units from the contents of a `.service` unit, to be converted with
+    /// [`Launchd::from_systemd_units`](crate::Launchd::from_systemd_units).
+    pub fn new<S: AsRef<str>>(service: S) -> Self {
+        Self {
+            service: service.as_ref().to_string(),
+            timer: None,
+            socket: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_timer<S: AsRef<str>>(mut self, timer: S) -> Self {
+        self.timer = Some(timer.as_ref().to_string());
+        self
+    }
+
+    pub fn with_socket<S: AsRef<str>>(mut self, socket: S) -> Self {
+        self.socket = Some(socket.as_ref().to_string());
+        self
+    }
+
+    /// The contents of the `.service` unit.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The contents of the `.timer` unit, if the job has a `StartInterval` or
+    /// `StartCalendarInterval`.
+    pub fn timer(&self) -> Option<&str> {
+        self.timer.as_deref()
+    }
+
+    /// The contents of the `.socket` unit, if the job has `Sockets`.
+    pub fn socket(&self) -> Option<&str> {
+        self.socket.as_deref()
+    }
+
+    /// The keys of the job that couldn't be converted, or only partially.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+}
+
+/// A key or directive that was dropped or only partially converted between launchd and systemd.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    key: String,
+    message: String,
+}
+
+impl Warning {
+    pub(crate) fn new<K: Into<String>, M: Into<String>>(key: K, message: M) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    /// The key the warning is about. This is the launchd key when exporting, e.g. `WatchPaths` or
+    /// `Sockets.Listeners`, and the systemd directive when importing, e.g. `Service.Type`.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
@@ -0,0 +1,251 @@
+// Minimal representation of systemd unit files, see systemd.syntax(7).
+use crate::Error;
+use std::fmt;
+
+// A unit file with named sections of ordered directives. A directive can occur more than once.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct UnitFile {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl UnitFile {
+    pub(crate) fn push<V: Into<String>>(&mut self, section: &str, key: &str, value: V) {
+        let index = match self.sections.iter().position(|(name, _)| name == section) {
+            Some(index) => index,
+            None => {
+                self.sections.push((section.to_string(), Vec::new()));
+                self.sections.len() - 1
+            }
+        };
+        self.sections[index].1.push((key.to_string(), value.into()));
+    }
+
+    // Parses the sections and directives of a unit file. Comments and empty lines are skipped, and
+    // lines ending with a backslash are continued on the next line, with the backslash replaced by
+    // a space.
+    pub(crate) fn parse(contents: &str) -> Result<Self, Error> {
+        let mut unit = UnitFile::default();
+        let mut section: Option<String> = None;
+        let mut lines = contents.lines().enumerate();
+        while let Some((number, line)) = lines.next() {
+            let mut line = line.trim().to_string();
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) if next.starts_with(['#', ';']) => {}
+                    Some((_, next)) => {
+                        line.push(' ');
+                        line.push_str(next.trim());
+                    }
+                    None => break,
+                }
+            }
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = Some(line[1..line.len() - 1].to_string());
+                continue;
+            }
+            let invalid =
+                |reason: &str| Error::InvalidUnitFile(format!("line {}: {}", number + 1, reason));
+            let section = section
+                .as_deref()
+                .ok_or_else(|| invalid("directive outside of a section"))?;
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected a section or KEY=VALUE"))?;
+            unit.push(section, key.trim(), value.trim());
+        }
+        Ok(unit)
+    }
+
+    // Returns every (section, key, value) in order.
+    pub(crate) fn directives(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.sections.iter().flat_map(|(section, directives)| {
+            directives
+                .iter()
+                .map(move |(key, value)| (section.as_str(), key.as_str(), value.as_str()))
+        })
+    }
+}
+
+impl fmt::Display for UnitFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, directives)) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", name)?;
+            for (key, value) in directives {
+                writeln!(f, "{}={}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Quotes a command line argument for ExecStart=, escaping the specifiers and variables systemd
+// would otherwise expand.
+pub(crate) fn quote(argument: &str) -> String {
+    let escaped = escape(argument).replace('$', "$$");
+    if escaped.is_empty() || needs_quotes(argument) || argument.contains(';') {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+// Quotes a variable assignment for Environment=. Specifiers are expanded there too, but `$` is
+// not, so it is kept as is.
+pub(crate) fn quote_environment(assignment: &str) -> String {
+    let escaped = escape(assignment);
+    if needs_quotes(assignment) {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+// Escapes backslashes, quotes, specifiers and the control characters that would split the line.
+fn escape(word: &str) -> String {
+    word.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+fn needs_quotes(word: &str) -> bool {
+    word.contains(|c: char| c.is_whitespace() || c == '\'')
+}
+
+// Splits a command line into words, undoing the quoting and escaping of `quote`.
+pub(crate) fn split_words(value: &str) -> Result<Vec<String>, Error> {
+    split(value, true)
+}
+
+// Splits an Environment= value into assignments, undoing the quoting of `quote_environment`.
+pub(crate) fn split_environment(value: &str) -> Result<Vec<String>, Error> {
+    split(value, false)
+}
+
+fn split(value: &str, variables: bool) -> Result<Vec<String>, Error> {
+    let mut words = Vec::new();
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(words);
+        }
+        let mut word = String::new();
+        let mut quote: Option<char> = None;
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', _) => match chars.next() {
+                    Some('n') => word.push('\n'),
+                    Some('r') => word.push('\r'),
+                    Some('t') => word.push('\t'),
+                    Some(escaped) => word.push(escaped),
+                    None => {
+                        return Err(Error::InvalidUnitFile(format!(
+                            "trailing backslash in {}",
+                            value
+                        )))
+                    }
+                },
+                ('"', None) | ('\'', None) if word.is_empty() => quote = Some(c),
+                (c, Some(q)) if c == q => {
+                    quote = None;
+                    if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                        return Err(Error::InvalidUnitFile(format!(
+                            "a closing quote must be followed by whitespace in {}",
+                            value
+                        )));
+                    }
+                    break;
+                }
+                (c, None) if c.is_whitespace() => break,
+                ('%', _) if chars.next_if_eq(&'%').is_some() => word.push('%'),
+                ('$', _) if variables && chars.next_if_eq(&'$').is_some() => word.push('$'),
+                (c, _) => word.push(c),
+            }
+        }
+        if quote.is_some() {
+            return Err(Error::InvalidUnitFile(format!(
+                "unterminated quote in {}",
+                value
+            )));
+        }
+        words.push(word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_words_round_trip() {
+        let words = [
+            "/bin/echo",
+            "two words",
+            "100%",
+            "$HOME",
+            "say \"hi\"",
+            "a\\b",
+            "line\nbreak",
+            "tab\there",
+            "",
+        ];
+        let line: Vec<_> = words.iter().map(|word| quote(word)).collect();
+        assert_eq!(split_words(&line.join(" ")).unwrap(), words);
+        assert_eq!(
+            split_words("'single quoted' plain").unwrap(),
+            vec!["single quoted", "plain"]
+        );
+        assert!(split_words("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn environment_round_trips() {
+        let assignments = [
+            "HOME=$HOME",
+            "PRICE=$$5",
+            "A=1 2",
+            "P=100%",
+            "Q=\"a\\b\"",
+            "N=one\ntwo",
+        ];
+        let line: Vec<_> = assignments
+            .iter()
+            .map(|assignment| quote_environment(assignment))
+            .collect();
+        assert_eq!(line[0], "HOME=$HOME");
+        assert_eq!(line[5], "\"N=one\\ntwo\"");
+        assert_eq!(split_environment(&line.join(" ")).unwrap(), assignments);
+    }
+
+    #[test]
+    fn parse_sections_and_continuations() {
+        let unit = UnitFile::parse(
+            "# comment\n[Service]\nExecStart=/bin/echo \\\n  hello\n\n; other comment\n[Install]\nWantedBy = default.target\n",
+        )
+        .unwrap();
+        let directives: Vec<_> = unit.directives().collect();
+        assert_eq!(
+            directives,
+            vec![
+                ("Service", "ExecStart", "/bin/echo  hello"),
+                ("Install", "WantedBy", "default.target")
+            ]
+        );
+        let unit = UnitFile::parse("[Service]\nExecStart=/bin/foo\\\n--bar\n").unwrap();
+        assert_eq!(
+            unit.directives().collect::<Vec<_>>(),
+            vec![("Service", "ExecStart", "/bin/foo --bar")]
+        );
+        assert!(UnitFile::parse("ExecStart=/bin/true").is_err());
+    }
+}
@@ -0,0 +1,534 @@
+// Reading and writing of the keys that the Launchd struct doesn't model, with any serde backend.
+//
+// serde(flatten) can't be used to collect them, as the plist crate then expects every Option to be
+// wrapped in a dictionary. So the derived implementations (see serde(remote = "Self")) only handle
+// the modelled keys. Deserialize reads every key as a Value and passes the known ones on to the
+// derived implementation. Serialize turns the derived fields into a Tree, without the unset ones,
+// and writes it as a map together with the unknown keys. The Tree holds Values, so dates and data
+// are written the way Value writes them. The io functions convert the Tree to a plist::Value
+// instead, so that they stay dates and data in a plist.
+#[cfg(feature = "io")]
+use crate::key_order::KeyOrdering;
+use crate::value::{untag, Nullable};
+#[cfg(feature = "io")]
+use crate::Error;
+use crate::{Launchd, Value};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{self, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+
+impl Serialize for Launchd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_tree()
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl Launchd {
+    // The derived fields followed by the unknown keys.
+    fn to_tree(&self) -> Result<Tree, de::value::Error> {
+        let mut entries = match Launchd::serialize(self, TreeSerializer)? {
+            Some(Tree::Dictionary(entries)) => entries,
+            _ => unreachable!("Launchd always serializes to a dictionary"),
+        };
+        let mut unknown: Vec<_> = self.unknown_keys.iter().collect();
+        unknown.sort_by_key(|(key, _)| *key);
+        for (key, value) in unknown {
+            entries.push((key.clone(), Tree::Value(value.clone())));
+        }
+        Ok(Tree::Dictionary(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for Launchd {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeysVisitor;
+
+        impl<'de> Visitor<'de> for KeysVisitor {
+            type Value = Launchd;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a dictionary of launchd keys")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Launchd, A::Error> {
+                let known = known_keys();
+                let mut fields = BTreeMap::new();
+                let mut unknown_keys = HashMap::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    let value = match map.next_value::<Nullable>()? {
+                        Nullable(Some(value)) => value,
+                        Nullable(None) => continue,
+                    };
+                    if known.contains(&key.as_str()) {
+                        fields.insert(key, value);
+                    } else {
+                        unknown_keys.insert(key, value);
+                    }
+                }
+                let mut launchd =
+                    Launchd::deserialize(Value::Dictionary(fields)).map_err(de::Error::custom)?;
+                launchd.unknown_keys = unknown_keys;
+                Ok(launchd)
+            }
+        }
+
+        deserializer.deserialize_map(KeysVisitor)
+    }
+}
+
+#[cfg(feature = "io")]
+impl Launchd {
+    pub(crate) fn from_plist_value(value: plist::Value) -> Result<Self, Error> {
+        let key_order = KeyOrdering::record(&value);
+        let mut launchd: Launchd = plist::from_value(&value).map_err(Error::Read)?;
+        launchd.key_order = key_order;
+        Ok(launchd)
+    }
+
+    pub(crate) fn to_plist_value(&self) -> Result<plist::Value, Error> {
+        let tree = self
+            .to_tree()
+            .map_err(|error| Error::Write(ser::Error::custom(error)))?;
+        let mut value = plist::Value::from(tree);
+        self.key_order.apply(&mut value);
+        Ok(value)
+    }
+}
+
+// Returns the keys (including aliases) that the derived Deserialize implementation accepts, by
+// letting it describe itself to a Deserializer that records the fields and then bails out.
+pub(crate) fn known_keys() -> &'static [&'static str] {
+    struct FieldRecorder<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldRecorder<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("only structs are supported"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields recorded"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = Launchd::deserialize(FieldRecorder(&mut fields));
+    fields
+}
+
+// A serialized value that keeps the order of its dictionaries, e.g. the fields of a struct.
+pub(crate) enum Tree {
+    Value(Value),
+    Array(Vec<Tree>),
+    Dictionary(Vec<(String, Tree)>),
+}
+
+#[cfg(feature = "io")]
+impl From<Tree> for plist::Value {
+    fn from(tree: Tree) -> Self {
+        match tree {
+            Tree::Value(value) => value.into(),
+            Tree::Array(trees) => {
+                plist::Value::Array(trees.into_iter().map(plist::Value::from).collect())
+            }
+            Tree::Dictionary(entries) => plist::Value::Dictionary(
+                entries
+                    .into_iter()
+                    .map(|(key, tree)| (key, plist::Value::from(tree)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for Tree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tree::Value(value) => value.serialize(serializer),
+            Tree::Array(trees) => serializer.collect_seq(trees),
+            Tree::Dictionary(entries) => {
+                serializer.collect_map(entries.iter().map(|(key, tree)| (key, tree)))
+            }
+        }
+    }
+}
+
+// Serializes into a Tree like the plist crate serializes into a plist: unset Options are left out
+// of structs and maps, and enum variants are written as strings or single-key dictionaries.
+pub(crate) struct TreeSerializer;
+
+type TreeResult = Result<Option<Tree>, de::value::Error>;
+
+fn leaf<V: Into<Value>>(value: V) -> TreeResult {
+    Ok(Some(Tree::Value(value.into())))
+}
+
+fn integer(value: i128) -> TreeResult {
+    leaf(Value::Integer(value))
+}
+
+// Returns the tree of a value that can't be unset, e.g. an element of an array.
+fn required<T: Serialize + ?Sized>(value: &T) -> Result<Tree, de::value::Error> {
+    value
+        .serialize(TreeSerializer)?
+        .ok_or_else(|| ser::Error::custom("a plist can't contain None"))
+}
+
+fn wrap(variant: Option<&'static str>, tree: Tree) -> Tree {
+    match variant {
+        Some(variant) => Tree::Dictionary(vec![(variant.to_string(), tree)]),
+        None => tree,
+    }
+}
+
+impl Serializer for TreeSerializer {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+    type SerializeSeq = ArrayBuilder;
+    type SerializeTuple = ArrayBuilder;
+    type SerializeTupleStruct = ArrayBuilder;
+    type SerializeTupleVariant = ArrayBuilder;
+    type SerializeMap = DictionaryBuilder;
+    type SerializeStruct = DictionaryBuilder;
+    type SerializeStructVariant = DictionaryBuilder;
+
+    fn serialize_bool(self, value: bool) -> TreeResult {
+        leaf(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_i16(self, value: i16) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_i32(self, value: i32) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_i64(self, value: i64) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_i128(self, value: i128) -> TreeResult {
+        integer(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_u16(self, value: u16) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_u32(self, value: u32) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_u64(self, value: u64) -> TreeResult {
+        integer(i128::from(value))
+    }
+
+    fn serialize_u128(self, value: u128) -> TreeResult {
+        integer(i128::try_from(value).map_err(ser::Error::custom)?)
+    }
+
+    fn serialize_f32(self, value: f32) -> TreeResult {
+        leaf(f64::from(value))
+    }
+
+    fn serialize_f64(self, value: f64) -> TreeResult {
+        leaf(value)
+    }
+
+    fn serialize_char(self, value: char) -> TreeResult {
+        leaf(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> TreeResult {
+        leaf(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> TreeResult {
+        leaf(Value::Data(value.to_vec()))
+    }
+
+    fn serialize_none(self) -> TreeResult {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> TreeResult {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> TreeResult {
+        leaf("")
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> TreeResult {
+        leaf("")
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> TreeResult {
+        leaf(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> TreeResult {
+        Ok(Some(required(value)?))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> TreeResult {
+        Ok(Some(wrap(Some(variant), required(value)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ArrayBuilder, Self::Error> {
+        Ok(ArrayBuilder {
+            variant: None,
+            trees: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ArrayBuilder, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<ArrayBuilder, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ArrayBuilder, Self::Error> {
+        Ok(ArrayBuilder {
+            variant: Some(variant),
+            trees: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<DictionaryBuilder, Self::Error> {
+        Ok(DictionaryBuilder {
+            variant: None,
+            entries: Vec::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<DictionaryBuilder, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<DictionaryBuilder, Self::Error> {
+        Ok(DictionaryBuilder {
+            variant: Some(variant),
+            entries: Vec::new(),
+            key: None,
+        })
+    }
+}
+
+pub(crate) struct ArrayBuilder {
+    variant: Option<&'static str>,
+    trees: Vec<Tree>,
+}
+
+impl ArrayBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), de::value::Error> {
+        self.trees.push(required(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> TreeResult {
+        Ok(Some(wrap(self.variant, Tree::Array(self.trees))))
+    }
+}
+
+impl ser::SerializeSeq for ArrayBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ArrayBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ArrayBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ArrayBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+pub(crate) struct DictionaryBuilder {
+    variant: Option<&'static str>,
+    entries: Vec<(String, Tree)>,
+    key: Option<String>,
+}
+
+impl DictionaryBuilder {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), de::value::Error> {
+        if let Some(tree) = value.serialize(TreeSerializer)? {
+            self.entries.push((key, tree));
+        }
+        Ok(())
+    }
+
+    // Values write their dates and data as tagged dictionaries, which are turned back into Values
+    // here, so that the io functions can write them as plist dates and data.
+    fn finish(self) -> TreeResult {
+        if let (None, [(tag, Tree::Value(Value::String(value)))]) =
+            (self.variant, self.entries.as_slice())
+        {
+            if let Some(Some(value)) = untag(tag, value) {
+                return Ok(Some(Tree::Value(value)));
+            }
+        }
+        Ok(Some(wrap(self.variant, Tree::Dictionary(self.entries))))
+    }
+}
+
+impl ser::SerializeMap for DictionaryBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        match required(key)? {
+            Tree::Value(Value::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom(
+                "the keys of a dictionary must be strings",
+            )),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for DictionaryBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for DictionaryBuilder {
+    type Ok = Option<Tree>;
+    type Error = de::value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> TreeResult {
+        self.finish()
+    }
+}
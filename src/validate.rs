@@ -0,0 +1,497 @@
+// Validation of launchd jobs against the rules of launchd.plist(5).
+//
+// The builder accepts anything that can be serialized, so a job that launchd rejects (or silently
+// never runs) would otherwise only be noticed when it's bootstrapped on a Mac.
+use crate::key_path::KeyPath;
+use crate::sockets::{SocketFamily, SocketOptions, SocketProtocol, SocketType, Sockets};
+use crate::{CalendarInterval, Launchd, ResourceLimits};
+use std::fmt;
+use std::path::Path;
+
+const DAYS_IN_MONTH: [u8; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// launchd refuses to load the job, or the key has no effect.
+    Error,
+    /// launchd loads the job, but it probably doesn't behave as intended.
+    Warning,
+}
+
+/// A problem found by [`Launchd::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    severity: Severity,
+    path: KeyPath,
+    message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new<S: Into<String>>(severity: Severity, path: KeyPath, message: S) -> Self {
+        Self {
+            severity,
+            path,
+            message: message.into(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// The key the diagnostic is about. It is empty if the diagnostic is about the whole job.
+    pub fn path(&self) -> &KeyPath {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.path.is_empty() {
+            write!(f, "{}: {}", severity, self.message)
+        } else {
+            write!(f, "{}: {}: {}", severity, self.path, self.message)
+        }
+    }
+}
+
+impl Launchd {
+    /// Checks this job against the rules of launchd.plist(5) that the builder doesn't enforce,
+    /// e.g. that `Program` is an absolute path. Returns an empty list if no problems are found.
+    ///
+    /// Usage:
+    /// ```
+    /// use launchd::{Error, Launchd, Severity};
+    ///
+    /// fn example() -> Result<(), Error> {
+    ///     let launchd = Launchd::new("com.example.agent", "./agent")?;
+    ///     let diagnostics = launchd.validate();
+    ///
+    ///     assert_eq!(diagnostics.len(), 1);
+    ///     assert_eq!(diagnostics[0].severity(), Severity::Error);
+    ///     assert_eq!(diagnostics[0].to_string(), "error: Program: must be an absolute path");
+    ///     Ok(())
+    /// }
+    /// example().unwrap();
+    /// ```
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |severity, path: KeyPath, message: &str| {
+            diagnostics.push(Diagnostic::new(severity, path, message))
+        };
+        let root = KeyPath::new();
+
+        if self.label.trim().is_empty() {
+            report(
+                Severity::Error,
+                root.clone().key("Label"),
+                "must not be empty",
+            );
+        }
+
+        match (&self.program, &self.program_arguments) {
+            (None, None) if self.bundle_program.is_none() => report(
+                Severity::Error,
+                root.clone(),
+                "either Program or ProgramArguments must be present",
+            ),
+            (None, Some(arguments)) if arguments.is_empty() => report(
+                Severity::Error,
+                root.clone().key("ProgramArguments"),
+                "must not be empty when Program is absent",
+            ),
+            _ => {}
+        }
+        if let Some(program) = &self.program {
+            if !Path::new(program).is_absolute() {
+                report(
+                    Severity::Error,
+                    root.clone().key("Program"),
+                    "must be an absolute path",
+                );
+            }
+        }
+        for (key, path) in [
+            ("WorkingDirectory", &self.working_directory),
+            ("RootDirectory", &self.root_directory),
+            ("StandardInPath", &self.standard_in_path),
+            ("StandardOutPath", &self.standard_out_path),
+            ("StandardErrorPath", &self.standard_error_path),
+        ] {
+            if let Some(path) = path {
+                if !Path::new(path).is_absolute() {
+                    report(
+                        Severity::Warning,
+                        root.clone().key(key),
+                        "is resolved relative to the root directory, use an absolute path",
+                    );
+                }
+            }
+        }
+
+        if self.start_interval == Some(0) {
+            report(
+                Severity::Error,
+                root.clone().key("StartInterval"),
+                "must be greater than 0",
+            );
+        }
+        if let Some(intervals) = &self.start_calendar_intervals {
+            let path = root.clone().key("StartCalendarInterval");
+            if intervals.is_empty() {
+                report(Severity::Warning, path.clone(), "contains no intervals");
+            }
+            for (i, ci) in intervals.iter().enumerate() {
+                validate_calendar_interval(ci, path.clone().index(i), &mut report);
+            }
+        }
+
+        if let Some(umask) = self.umask {
+            if umask > 0o777 {
+                report(
+                    Severity::Error,
+                    root.clone().key("Umask"),
+                    "must lie between 0 and 0777",
+                );
+            }
+        }
+        if let Some(nice) = self.nice {
+            if !(-20..=20).contains(&nice) {
+                report(
+                    Severity::Error,
+                    root.clone().key("Nice"),
+                    "must lie between -20 and 20",
+                );
+            }
+        }
+        if let (Some(soft), Some(hard)) = (&self.soft_resource_limits, &self.hard_resource_limits) {
+            for (key, soft, hard) in resource_limits(soft)
+                .iter()
+                .zip(resource_limits(hard).iter())
+                .map(|((key, soft), (_, hard))| (key, soft, hard))
+            {
+                if let (Some(soft), Some(hard)) = (soft, hard) {
+                    if soft > hard {
+                        report(
+                            Severity::Error,
+                            root.clone().key("SoftResourceLimits").key(key),
+                            "must not exceed the hard limit",
+                        );
+                    }
+                }
+            }
+        }
+
+        match &self.sockets {
+            Some(Sockets::Dictionary(sockets)) => {
+                let mut names: Vec<_> = sockets.keys().collect();
+                names.sort();
+                for name in names {
+                    let path = root.clone().key("Sockets").key(name);
+                    validate_socket(&sockets[name], path, &mut report);
+                }
+            }
+            Some(Sockets::Array(array)) => {
+                for (i, sockets) in array.iter().enumerate() {
+                    let mut names: Vec<_> = sockets.keys().collect();
+                    names.sort();
+                    for name in names {
+                        let path = root.clone().key("Sockets").index(i).key(name);
+                        validate_socket(&sockets[name], path, &mut report);
+                    }
+                }
+            }
+            None => {}
+        }
+        if self.inetd_compatibility.is_some() && self.sockets.is_none() {
+            report(
+                Severity::Warning,
+                root.key("inetdCompatibility"),
+                "has no effect without Sockets",
+            );
+        }
+
+        diagnostics
+    }
+}
+
+fn validate_calendar_interval(
+    ci: &CalendarInterval,
+    path: KeyPath,
+    report: &mut impl FnMut(Severity, KeyPath, &str),
+) {
+    if *ci == CalendarInterval::default() {
+        report(
+            Severity::Error,
+            path.clone(),
+            "must not be empty, use StartInterval to run every minute",
+        );
+    }
+    for (key, value, bounds) in [
+        ("Minute", ci.minute, 0..=59),
+        ("Hour", ci.hour, 0..=23),
+        ("Day", ci.day, 1..=31),
+        ("Weekday", ci.weekday, 0..=7),
+        ("Month", ci.month, 1..=12),
+    ] {
+        if let Some(value) = value {
+            if !bounds.contains(&value) {
+                let message = format!("must lie between {} and {}", bounds.start(), bounds.end());
+                report(Severity::Error, path.clone().key(key), &message);
+            }
+        }
+    }
+    if let (Some(month @ 1..=12), Some(day)) = (ci.month, ci.day) {
+        if day > DAYS_IN_MONTH[usize::from(month - 1)] && ci.weekday.is_none() {
+            report(
+                Severity::Warning,
+                path.key("Day"),
+                "doesn't occur in this Month, so the interval never fires",
+            );
+        }
+    }
+}
+
+fn validate_socket(
+    options: &SocketOptions,
+    path: KeyPath,
+    report: &mut impl FnMut(Severity, KeyPath, &str),
+) {
+    let unix = options.sock_path_name.is_some() || options.secure_socket_with_key.is_some();
+    let network = options.sock_node_name.is_some() || options.sock_service_name.is_some();
+    if options.sock_path_name.is_some() && options.secure_socket_with_key.is_some() {
+        report(
+            Severity::Error,
+            path.clone().key("SecureSocketWithKey"),
+            "creates its own socket path, so it can't be combined with SockPathName",
+        );
+    }
+    if unix && network {
+        report(
+            Severity::Error,
+            path.clone(),
+            "a socket either has a SockPathName or a SockNodeName and SockServiceName",
+        );
+    }
+    match options.sock_family {
+        Some(SocketFamily::Unix) if !unix => report(
+            Severity::Error,
+            path.clone().key("SockFamily"),
+            "a Unix socket needs a SockPathName",
+        ),
+        Some(SocketFamily::IPv4) | Some(SocketFamily::IPv6) if unix => report(
+            Severity::Error,
+            path.clone().key("SockFamily"),
+            "a socket with a SockPathName must be a Unix socket",
+        ),
+        _ => {}
+    }
+    if options.sock_path_mode.is_some() && options.sock_path_name.is_none() {
+        report(
+            Severity::Error,
+            path.clone().key("SockPathMode"),
+            "has no effect without SockPathName",
+        );
+    }
+    if options.sock_protocol == Some(SocketProtocol::Tcp)
+        && !matches!(options.sock_type, Some(SocketType::Stream) | None)
+    {
+        report(
+            Severity::Error,
+            path.clone().key("SockProtocol"),
+            "TCP requires a stream socket",
+        );
+    }
+    if options.multicast_group.is_some() && options.sock_type != Some(SocketType::Dgram) {
+        report(
+            Severity::Error,
+            path.clone().key("MulticastGroup"),
+            "requires a dgram socket",
+        );
+    }
+    if options.sock_passive == Some(false) && options.sock_node_name.is_none() && !unix {
+        report(
+            Severity::Error,
+            path.clone().key("SockNodeName"),
+            "is required to connect a socket that isn't passive",
+        );
+    }
+    if options.bonjour.is_some() && unix {
+        report(
+            Severity::Warning,
+            path.key("Bonjour"),
+            "only applies to network sockets",
+        );
+    }
+}
+
+fn resource_limits(limits: &ResourceLimits) -> [(&'static str, Option<u64>); 9] {
+    [
+        ("Core", limits.core),
+        ("CPU", limits.cpu),
+        ("Data", limits.data),
+        ("FileSize", limits.file_size),
+        ("MemoryLock", limits.memory_lock),
+        ("NumberOfFiles", limits.number_of_files),
+        ("NumberOfProcesses", limits.number_of_processes),
+        ("ResidentSetSize", limits.resident_set_size),
+        ("Stack", limits.stack),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::Socket;
+
+    fn messages(launchd: &Launchd) -> Vec<String> {
+        launchd
+            .validate()
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_job_has_no_diagnostics() {
+        let launchd = Launchd::new("com.example.agent", "/usr/local/bin/agent")
+            .unwrap()
+            .with_start_calendar_intervals(vec![CalendarInterval::default().with_hour(3).unwrap()])
+            .with_socket(
+                Socket::new(
+                    "Listeners",
+                    SocketOptions::new()
+                        .with_path_name("/var/run/agent.sock")
+                        .unwrap()
+                        .with_path_mode(0o600),
+                )
+                .into(),
+            );
+        assert_eq!(messages(&launchd), Vec::<String>::new());
+    }
+
+    #[test]
+    fn program_and_label() {
+        let launchd = Launchd::default();
+        assert_eq!(
+            messages(&launchd),
+            vec![
+                "error: Label: must not be empty",
+                "error: either Program or ProgramArguments must be present"
+            ]
+        );
+        let launchd = Launchd::default()
+            .with_label("LABEL")
+            .with_program_arguments(vec![])
+            .with_working_directory("relative")
+            .unwrap();
+        assert_eq!(
+            messages(&launchd),
+            vec![
+                "error: ProgramArguments: must not be empty when Program is absent",
+                "warning: WorkingDirectory: is resolved relative to the root directory, use an absolute path"
+            ]
+        );
+    }
+
+    #[test]
+    fn calendar_intervals() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(vec![
+                CalendarInterval::default(),
+                CalendarInterval {
+                    minute: Some(60),
+                    ..Default::default()
+                },
+                CalendarInterval::default()
+                    .with_month(2)
+                    .and_then(|ci| ci.with_day(30))
+                    .unwrap(),
+            ]);
+        assert_eq!(
+            messages(&launchd),
+            vec![
+                "error: StartCalendarInterval[0]: must not be empty, use StartInterval to run every minute",
+                "error: StartCalendarInterval[1].Minute: must lie between 0 and 59",
+                "warning: StartCalendarInterval[2].Day: doesn't occur in this Month, so the interval never fires"
+            ]
+        );
+    }
+
+    #[test]
+    fn converted_schedules_are_valid() {
+        let schedules = vec![
+            CalendarInterval::from_on_calendar("Mon..Fri *-*-* 09:00").unwrap(),
+            CalendarInterval::from_on_calendar("hourly").unwrap(),
+            CalendarInterval::minimize(
+                (0..24)
+                    .map(|hour| {
+                        CalendarInterval::default()
+                            .with_hour(hour)
+                            .and_then(|ci| ci.with_minute(15))
+                            .unwrap()
+                    })
+                    .collect(),
+            ),
+        ];
+        for intervals in schedules {
+            let launchd = Launchd::new("LABEL", "/bin/true")
+                .unwrap()
+                .with_start_calendar_intervals(intervals);
+            assert_eq!(messages(&launchd), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cron")]
+    fn converted_cron_schedules_are_valid() {
+        use std::str::FromStr;
+        let schedule = cron::Schedule::from_str("0 */5 * * * *").unwrap();
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_start_calendar_intervals(CalendarInterval::from_cron_schedule(schedule).unwrap());
+        assert_eq!(messages(&launchd), Vec::<String>::new());
+    }
+
+    #[test]
+    fn inconsistent_sockets() {
+        let launchd = Launchd::new("LABEL", "/bin/true")
+            .unwrap()
+            .with_socket(
+                Socket::new(
+                    "Listeners",
+                    SocketOptions::new()
+                        .with_service_name("8080")
+                        .with_family(SocketFamily::Unix)
+                        .with_path_mode(0o600)
+                        .with_type(SocketType::Stream)
+                        .with_multicast_group("239.0.0.1"),
+                )
+                .into(),
+            )
+            .with_soft_resource_limits(ResourceLimits::new().with_number_of_files(4096))
+            .with_hard_resource_limits(ResourceLimits::new().with_number_of_files(1024));
+        assert_eq!(
+            messages(&launchd),
+            vec![
+                "error: SoftResourceLimits.NumberOfFiles: must not exceed the hard limit",
+                "error: Sockets.Listeners.SockFamily: a Unix socket needs a SockPathName",
+                "error: Sockets.Listeners.SockPathMode: has no effect without SockPathName",
+                "error: Sockets.Listeners.MulticastGroup: requires a dgram socket"
+            ]
+        );
+    }
+}
@@ -0,0 +1,793 @@
+// A dynamic value that covers the plist data model, for the parts of a job that aren't modelled,
+// such as unknown keys and the descriptors of uncommon LaunchEvents streams.
+//
+// It doesn't depend on the plist crate, so these parts can be (de)serialized with any serde
+// backend. serde has no date or data types, so they are written as
+// {"$date": "2021-06-01T12:00:00Z"} and {"$data": "<base64>"}, and such single-key dictionaries
+// are read back as dates and data. The io functions keep the plist types by converting to
+// plist::Value instead of serializing, and the plist crate passes its dates on in the newtypes
+// it uses for plist::Date and plist::Value.
+#[cfg(feature = "serde")]
+use serde::de::{
+    self, value::MapAccessDeserializer, value::MapDeserializer, value::SeqDeserializer, EnumAccess,
+    IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+#[cfg(any(feature = "serde", feature = "plist"))]
+use std::convert::TryFrom;
+#[cfg(feature = "serde")]
+use std::fmt;
+use std::time::SystemTime;
+#[cfg(feature = "serde")]
+use std::time::{Duration, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+pub(crate) const DATE_NEWTYPE_STRUCT_NAME: &str = "PLIST-DATE";
+#[cfg(feature = "serde")]
+const UID_NEWTYPE_STRUCT_NAME: &str = "PLIST-UID";
+#[cfg(feature = "serde")]
+const VALUE_NEWTYPE_STRUCT_NAME: &str = "PLIST-VALUE";
+#[cfg(feature = "serde")]
+const DATE_TAG: &str = "$date";
+#[cfg(feature = "serde")]
+const DATA_TAG: &str = "$data";
+
+#[cfg(feature = "serde")]
+pub(crate) const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A plist value: a string, integer, real, boolean, date, data, array or dictionary.
+///
+/// Usage:
+/// ```
+/// use launchd::Value;
+/// use std::collections::BTreeMap;
+///
+/// let value = Value::from(vec![Value::from("com.example.app"), Value::from(42)]);
+/// assert_eq!(value.as_array().unwrap()[1].as_integer(), Some(42));
+///
+/// let dictionary: BTreeMap<String, Value> = vec![("Enabled".to_string(), true.into())]
+///     .into_iter()
+///     .collect();
+/// assert_eq!(Value::from(dictionary).as_dictionary().unwrap()["Enabled"], Value::Boolean(true));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// Plists store integers in the range of both `i64` and `u64`.
+    Integer(i128),
+    Real(f64),
+    Boolean(bool),
+    /// Plists store dates with a precision of a second.
+    Date(SystemTime),
+    Data(Vec<u8>),
+    Array(Vec<Value>),
+    Dictionary(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_real(&self) -> Option<f64> {
+        match self {
+            Value::Real(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_boolean(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<SystemTime> {
+        match self {
+            Value::Date(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            Value::Data(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Dictionary(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Real(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<SystemTime> for Value {
+    fn from(value: SystemTime) -> Self {
+        Value::Date(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::Array(value)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(value: BTreeMap<String, Value>) -> Self {
+        Value::Dictionary(value)
+    }
+}
+
+#[cfg(feature = "plist")]
+impl From<plist::Value> for Value {
+    fn from(value: plist::Value) -> Self {
+        match value {
+            plist::Value::String(value) => Value::String(value),
+            plist::Value::Integer(value) => Value::Integer(
+                value
+                    .as_signed()
+                    .map(i128::from)
+                    .or_else(|| value.as_unsigned().map(i128::from))
+                    .unwrap_or_default(),
+            ),
+            plist::Value::Real(value) => Value::Real(value),
+            plist::Value::Boolean(value) => Value::Boolean(value),
+            plist::Value::Date(value) => Value::Date(value.into()),
+            plist::Value::Data(value) => Value::Data(value),
+            plist::Value::Array(values) => {
+                Value::Array(values.into_iter().map(Value::from).collect())
+            }
+            plist::Value::Dictionary(dictionary) => Value::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+            // UIDs only occur in NSKeyedArchiver plists, not in launchd jobs.
+            plist::Value::Uid(uid) => Value::Integer(uid.get().into()),
+            // plist::Value is non-exhaustive, but every variant of plist 1.x is handled above.
+            _ => Value::String(String::new()),
+        }
+    }
+}
+
+#[cfg(feature = "plist")]
+impl From<Value> for plist::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::String(value) => plist::Value::String(value),
+            // Integers outside of the range of plists are saturated.
+            Value::Integer(value) if value < 0 => {
+                plist::Value::Integer(i64::try_from(value).unwrap_or(i64::MIN).into())
+            }
+            Value::Integer(value) => {
+                plist::Value::Integer(u64::try_from(value).unwrap_or(u64::MAX).into())
+            }
+            Value::Real(value) => plist::Value::Real(value),
+            Value::Boolean(value) => plist::Value::Boolean(value),
+            Value::Date(value) => plist::Value::Date(value.into()),
+            Value::Data(value) => plist::Value::Data(value),
+            Value::Array(values) => {
+                plist::Value::Array(values.into_iter().map(plist::Value::from).collect())
+            }
+            Value::Dictionary(dictionary) => plist::Value::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, plist::Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Integer(value) => match (i64::try_from(*value), u64::try_from(*value)) {
+                (Ok(value), _) => serializer.serialize_i64(value),
+                (_, Ok(value)) => serializer.serialize_u64(value),
+                _ => serializer.serialize_i128(*value),
+            },
+            Value::Real(value) => serializer.serialize_f64(*value),
+            Value::Boolean(value) => serializer.serialize_bool(*value),
+            Value::Date(value) => tagged(serializer, DATE_TAG, format_date(*value)),
+            Value::Data(value) => tagged(serializer, DATA_TAG, encode_base64(value)),
+            Value::Array(values) => values.serialize(serializer),
+            Value::Dictionary(dictionary) => dictionary.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn tagged<S: Serializer>(serializer: S, tag: &str, value: String) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(tag, &value)?;
+    map.end()
+}
+
+// Reads back the entry of a single-key dictionary written by `tagged`. Returns None if the key
+// isn't a tag, and Some(None) if the tagged string is invalid.
+#[cfg(feature = "serde")]
+pub(crate) fn untag(tag: &str, value: &str) -> Option<Option<Value>> {
+    match tag {
+        DATE_TAG => Some(parse_date(value).map(Value::Date)),
+        DATA_TAG => Some(decode_base64(value).map(Value::Data)),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The plist crate only passes dates on as dates inside of this newtype.
+        deserializer.deserialize_newtype_struct(VALUE_NEWTYPE_STRUCT_NAME, ValueVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct ValueVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a plist value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Boolean(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Integer(value.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<Value, E> {
+        Ok(Value::Integer(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Integer(value.into()))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::Real(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Value, E> {
+        Ok(Value::Data(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Data(value))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut dictionary: BTreeMap<String, Value> = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            dictionary.insert(key, value);
+        }
+        if let (1, Some((tag, Value::String(value)))) = (dictionary.len(), dictionary.iter().next())
+        {
+            match untag(tag, value) {
+                Some(Some(value)) => return Ok(value),
+                Some(None) => {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Str(value),
+                        &"a date or base64 data",
+                    ))
+                }
+                None => {}
+            }
+        }
+        Ok(Value::Dictionary(dictionary))
+    }
+
+    // The plist crate passes dates and UIDs on as single-variant enums.
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        let (name, variant): (String, _) = data.variant()?;
+        match name.as_str() {
+            DATE_NEWTYPE_STRUCT_NAME => {
+                let date: String = variant.newtype_variant()?;
+                parse_date(&date)
+                    .map(Value::Date)
+                    .ok_or_else(|| de::Error::invalid_value(Unexpected::Str(&date), &"a date"))
+            }
+            UID_NEWTYPE_STRUCT_NAME => Ok(Value::Integer(variant.newtype_variant::<u64>()?.into())),
+            _ => Err(de::Error::unknown_variant(
+                &name,
+                &[DATE_NEWTYPE_STRUCT_NAME, UID_NEWTYPE_STRUCT_NAME],
+            )),
+        }
+    }
+}
+
+// A value that may be null, in formats that have one such as JSON. A null key of a job is the same
+// as a missing one.
+#[cfg(feature = "serde")]
+pub(crate) struct Nullable(pub(crate) Option<Value>);
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Nullable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_newtype_struct(VALUE_NEWTYPE_STRUCT_NAME, NullableVisitor)
+            .map(Nullable)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct NullableVisitor;
+
+#[cfg(feature = "serde")]
+macro_rules! forward_to_value_visitor {
+    ($($method:ident($type:ty);)*) => {
+        $(
+            fn $method<E: de::Error>(self, value: $type) -> Result<Self::Value, E> {
+                ValueVisitor.$method(value).map(Some)
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for NullableVisitor {
+    type Value = Option<Value>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a plist value or null")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Value::deserialize(deserializer).map(Some)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    forward_to_value_visitor! {
+        visit_bool(bool);
+        visit_i64(i64);
+        visit_i128(i128);
+        visit_u64(u64);
+        visit_f64(f64);
+        visit_str(&str);
+        visit_string(String);
+        visit_bytes(&[u8]);
+        visit_byte_buf(Vec<u8>);
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        ValueVisitor.visit_seq(seq).map(Some)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        ValueVisitor.visit_map(map).map(Some)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        ValueVisitor.visit_enum(data).map(Some)
+    }
+}
+
+// A Value can be deserialized into a type, e.g. to check whether it fits a model.
+#[cfg(feature = "serde")]
+impl<'de> Deserializer<'de> for Value {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::String(value) => visitor.visit_string(value),
+            Value::Integer(value) => match (i64::try_from(value), u64::try_from(value)) {
+                (Ok(value), _) => visitor.visit_i64(value),
+                (_, Ok(value)) => visitor.visit_u64(value),
+                _ => visitor.visit_i128(value),
+            },
+            Value::Real(value) => visitor.visit_f64(value),
+            Value::Boolean(value) => visitor.visit_bool(value),
+            Value::Date(value) => visitor.visit_string(format_date(value)),
+            Value::Data(value) => visitor.visit_byte_buf(value),
+            Value::Array(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+            Value::Dictionary(dictionary) => {
+                visitor.visit_map(MapDeserializer::new(dictionary.into_iter()))
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    // Like the plist crate, dates are passed on as dates when a Value is deserialized.
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Date(date) if name == VALUE_NEWTYPE_STRUCT_NAME => {
+                visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(
+                    std::iter::once((DATE_NEWTYPE_STRUCT_NAME, format_date(date))),
+                )))
+            }
+            value => visitor.visit_newtype_struct(value),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::String(variant) => variant
+                .into_deserializer()
+                .deserialize_enum(name, variants, visitor),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(feature = "serde")]
+impl IntoDeserializer<'_, de::value::Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// Dates are written like in XML plists: "2011-01-01T00:00:00Z".
+#[cfg(feature = "serde")]
+pub(crate) fn format_date(date: SystemTime) -> String {
+    let seconds = match date.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(error) => {
+            let duration = error.duration();
+            -(duration.as_secs() as i64) - i64::from(duration.subsec_nanos() > 0)
+        }
+    };
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn parse_date(date: &str) -> Option<SystemTime> {
+    let bytes = date.as_bytes();
+    let separators = [
+        (4, b'-'),
+        (7, b'-'),
+        (10, b'T'),
+        (13, b':'),
+        (16, b':'),
+        (19, b'Z'),
+    ];
+    if bytes.len() != 20 || separators.iter().any(|(i, c)| bytes[*i] != *c) {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = date.get(range)?;
+        if digits.bytes().all(|c| c.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    Some(if seconds < 0 {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    } else {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    })
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn encode_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Whitespace is ignored, so long data can be wrapped over multiple lines.
+#[cfg(feature = "serde")]
+pub(crate) fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = encoded
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut data = Vec::with_capacity(digits.len() / 4 * 3);
+    for chunk in digits.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && data.len() + 3 < digits.len() / 4 * 3) {
+            return None;
+        }
+        let mut bits = 0u32;
+        for digit in &chunk[..4 - padding] {
+            let index = BASE64.iter().position(|c| c == digit)?;
+            bits = bits << 6 | index as u32;
+        }
+        bits <<= 6 * padding;
+        data.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+    Some(data)
+}
+
+// Conversions between days since 1970-01-01 and dates in the proleptic Gregorian calendar, from
+// http://howardhinnant.github.io/date_algorithms.html
+#[cfg(feature = "serde")]
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(feature = "serde")]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_formatted_like_plists() {
+        for date in [
+            "1970-01-01T00:00:00Z",
+            "2000-02-29T23:59:59Z",
+            "2011-01-01T00:00:00Z",
+            "1969-12-31T23:59:59Z",
+            "1601-06-15T12:30:00Z",
+        ] {
+            assert_eq!(format_date(parse_date(date).unwrap()), date);
+        }
+        assert_eq!(
+            parse_date("2001-01-01T00:00:00Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(978_307_200))
+        );
+        assert_eq!(parse_date("2001-13-01T00:00:00Z"), None);
+        assert_eq!(parse_date("2001-01-01 00:00:00"), None);
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            let encoded = encode_base64(data);
+            assert_eq!(decode_base64(&encoded).as_deref(), Some(data));
+        }
+        assert_eq!(encode_base64(&[0xde, 0xad, 0xbe, 0xef]), "3q2+7w==");
+        assert_eq!(
+            decode_base64("3q2+\n7w=="),
+            Some(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(decode_base64("3q2"), None);
+        assert_eq!(decode_base64("3q==7w=="), None);
+        assert_eq!(decode_base64("3q2*"), None);
+    }
+
+    #[test]
+    fn other_serializers_get_tagged_dates_and_data() {
+        let value = Value::Array(vec![
+            Value::Date(parse_date("2021-06-01T12:00:00Z").unwrap()),
+            Value::Data(vec![0xde, 0xad, 0xbe, 0xef]),
+        ]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"$date":"2021-06-01T12:00:00Z"},{"$data":"3q2+7w=="}]"#
+        );
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+        assert!(serde_json::from_str::<Value>(r#"{"$date":"yesterday"}"#).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"$data":"3q2"}"#).is_err());
+        assert!(matches!(
+            serde_json::from_str::<Value>(r#"{"$date":"yesterday","Extra":1}"#),
+            Ok(Value::Dictionary(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn plist_dates_and_data_keep_their_type() {
+        let plist = br#"<plist version="1.0"><dict>
+            <key>Created</key><date>2011-01-01T00:00:00Z</date>
+            <key>Fingerprint</key><data>3q2+7w==</data>
+            <key>Count</key><integer>18446744073709551615</integer>
+        </dict></plist>"#;
+        let value: Value = plist::from_bytes(plist).unwrap();
+        let dictionary = value.as_dictionary().unwrap();
+        assert_eq!(
+            dictionary["Created"].as_date(),
+            parse_date("2011-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            dictionary["Fingerprint"].as_data(),
+            Some(&[0xde, 0xad, 0xbe, 0xef][..])
+        );
+        assert_eq!(dictionary["Count"].as_integer(), Some(u64::MAX.into()));
+
+        let mut buffer = Vec::new();
+        plist::to_writer_xml(&mut buffer, &value).unwrap();
+        assert!(String::from_utf8(buffer.clone())
+            .unwrap()
+            .contains("<key>$date</key>"));
+        assert_eq!(plist::from_bytes::<Value>(&buffer).unwrap(), value);
+        assert_eq!(
+            plist::Value::from(value.clone()),
+            plist::Value::from_reader_xml(&plist[..]).unwrap()
+        );
+        assert_eq!(
+            Value::from(plist::Value::from_reader_xml(&plist[..]).unwrap()),
+            value
+        );
+    }
+}
@@ -0,0 +1,263 @@
+// A small reader for XML plists, over quick-xml's events. Unlike plist's reader, it doesn't copy
+// the text it reads unless it contains an escape, and it can skip a value without decoding it.
+use crate::value::decode_base64;
+use crate::{Error, KeyPath, Value};
+use quick_xml::escape::resolve_xml_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::SystemTime;
+
+// Deeper values are rejected, so a crafted plist can't overflow the stack.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+pub(crate) struct Parser<'a> {
+    reader: Reader<&'a [u8]>,
+}
+
+enum Tag<'a> {
+    Start(BytesStart<'a>),
+    End,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(xml: &'a str) -> Self {
+        let mut reader = Reader::from_str(xml);
+        let config = reader.config_mut();
+        config.check_end_names = true;
+        config.expand_empty_elements = true;
+        Parser { reader }
+    }
+
+    fn error<M: fmt::Display>(&self, message: M) -> Error {
+        Error::InvalidXml(format!(
+            "{} at byte {}",
+            message,
+            self.reader.buffer_position()
+        ))
+    }
+
+    // Returns the next opening or closing tag, skipping whitespace, comments and declarations.
+    fn tag(&mut self) -> Result<Tag<'a>, Error> {
+        loop {
+            match self
+                .reader
+                .read_event()
+                .map_err(|error| self.error(error))?
+            {
+                Event::Start(start) => return Ok(Tag::Start(start)),
+                Event::End(_) => return Ok(Tag::End),
+                Event::Text(text) if text.trim().is_empty() => {}
+                Event::Decl(_) | Event::DocType(_) | Event::Comment(_) | Event::PI(_) => {}
+                Event::Eof => return Err(self.error("unexpected end of the plist")),
+                _ => return Err(self.error("unexpected text")),
+            }
+        }
+    }
+
+    pub(crate) fn open_root(&mut self) -> Result<(), Error> {
+        for name in ["plist", "dict"] {
+            match self.tag()? {
+                Tag::Start(start) if is(&start, name) => {}
+                _ => return Err(self.error(format_args!("expected <{}>", name))),
+            }
+        }
+        Ok(())
+    }
+
+    // Opens the value of a key, which should be a `name` element.
+    fn open(&mut self, key: &str, name: &str, expected: &str) -> Result<(), Error> {
+        match self.tag()? {
+            Tag::Start(start) if is(&start, name) => Ok(()),
+            Tag::Start(_) => Err(Error::InvalidValueAt(
+                KeyPath::new().key(key),
+                format!("expected {}", expected),
+            )),
+            Tag::End => Err(self.error(format_args!("missing the value of {}", key))),
+        }
+    }
+
+    // Reads the next key of a dictionary, or None at the end of the dictionary.
+    pub(crate) fn key(&mut self) -> Result<Option<Cow<'a, str>>, Error> {
+        match self.tag()? {
+            Tag::Start(start) if is(&start, "key") => self.text().map(Some),
+            Tag::Start(_) => Err(self.error("expected <key>")),
+            Tag::End => Ok(None),
+        }
+    }
+
+    // Reads the text up to the closing tag.
+    fn text(&mut self) -> Result<Cow<'a, str>, Error> {
+        let mut text: Option<Cow<'a, str>> = None;
+        loop {
+            let part = match self
+                .reader
+                .read_event()
+                .map_err(|error| self.error(error))?
+            {
+                Event::Text(part) => part.into_inner(),
+                Event::CData(part) => part.into_inner(),
+                Event::GeneralRef(reference) => {
+                    match reference
+                        .resolve_char_ref()
+                        .map_err(|error| self.error(error))?
+                    {
+                        Some(c) => Cow::Owned(c.to_string()),
+                        None => Cow::Borrowed(resolve_xml_entity(&reference).ok_or_else(|| {
+                            self.error(format_args!("unknown entity &{};", &*reference))
+                        })?),
+                    }
+                }
+                Event::Comment(_) | Event::PI(_) => continue,
+                Event::End(_) => return Ok(text.unwrap_or_default()),
+                _ => return Err(self.error("unexpected element in a string")),
+            };
+            // Only text with an escape in it is copied.
+            text = Some(match text {
+                None => part,
+                Some(mut text) => {
+                    text.to_mut().push_str(&part);
+                    text
+                }
+            });
+        }
+    }
+
+    pub(crate) fn string(&mut self, key: &str) -> Result<Cow<'a, str>, Error> {
+        self.open(key, "string", "a string")?;
+        self.text()
+    }
+
+    pub(crate) fn strings(&mut self, key: &str) -> Result<Vec<Cow<'a, str>>, Error> {
+        self.open(key, "array", "an array of strings")?;
+        let mut strings = Vec::new();
+        loop {
+            match self.tag()? {
+                Tag::Start(start) if is(&start, "string") => strings.push(self.text()?),
+                Tag::Start(_) => {
+                    return Err(Error::InvalidValueAt(
+                        KeyPath::new().key(key).index(strings.len()),
+                        "expected a string".to_string(),
+                    ))
+                }
+                Tag::End => return Ok(strings),
+            }
+        }
+    }
+
+    pub(crate) fn dictionary(
+        &mut self,
+        key: &str,
+    ) -> Result<HashMap<Cow<'a, str>, Cow<'a, str>>, Error> {
+        self.open(key, "dict", "a dictionary of strings")?;
+        let mut dictionary = HashMap::new();
+        while let Some(name) = self.key()? {
+            match self.tag()? {
+                Tag::Start(start) if is(&start, "string") => {
+                    let value = self.text()?;
+                    dictionary.insert(name, value);
+                }
+                Tag::Start(_) => {
+                    return Err(Error::InvalidValueAt(
+                        KeyPath::new().key(key).key(&name),
+                        "expected a string".to_string(),
+                    ))
+                }
+                Tag::End => return Err(self.error(format_args!("missing the value of {}", name))),
+            }
+        }
+        Ok(dictionary)
+    }
+
+    // Reads the value of a key.
+    pub(crate) fn value(&mut self) -> Result<Value, Error> {
+        self.nested_value(1)
+    }
+
+    fn nested_value(&mut self, depth: usize) -> Result<Value, Error> {
+        match self.tag()? {
+            Tag::Start(start) => self.element(&start, depth),
+            Tag::End => Err(self.error("missing a value")),
+        }
+    }
+
+    // Reads the rest of a value, after its opening tag.
+    fn element(&mut self, start: &BytesStart, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("the values are nested too deeply"));
+        }
+        let name = start.name().into_inner();
+        let value = match name {
+            "dict" => {
+                let mut dictionary = BTreeMap::new();
+                while let Some(key) = self.key()? {
+                    let value = self.nested_value(depth + 1)?;
+                    dictionary.insert(key.into_owned(), value);
+                }
+                Value::Dictionary(dictionary)
+            }
+            "array" => {
+                let mut array = Vec::new();
+                while let Tag::Start(start) = self.tag()? {
+                    array.push(self.element(&start, depth + 1)?);
+                }
+                Value::Array(array)
+            }
+            "string" => Value::String(self.text()?.into_owned()),
+            "true" | "false" => {
+                self.text()?;
+                Value::Boolean(name == "true")
+            }
+            "integer" => {
+                let text = self.text()?;
+                let text = text.trim();
+                let integer = match text.strip_prefix("0x") {
+                    Some(hex) => i128::from_str_radix(hex, 16),
+                    None => text.parse(),
+                };
+                Value::Integer(
+                    integer.map_err(|_| self.error(format_args!("invalid integer {:?}", text)))?,
+                )
+            }
+            "real" => {
+                let text = self.text()?;
+                Value::Real(
+                    text.trim()
+                        .parse()
+                        .map_err(|_| self.error(format_args!("invalid real {:?}", text)))?,
+                )
+            }
+            "date" => {
+                let text = self.text()?;
+                let date = plist::Date::from_xml_format(text.trim())
+                    .map_err(|_| self.error(format_args!("invalid date {:?}", text)))?;
+                Value::Date(SystemTime::from(date))
+            }
+            "data" => {
+                let text = self.text()?;
+                Value::Data(decode_base64(&text).ok_or_else(|| self.error("invalid base64 data"))?)
+            }
+            _ => return Err(self.error(format_args!("unknown element <{}>", name))),
+        };
+        Ok(value)
+    }
+
+    // Skips a value, without decoding it.
+    pub(crate) fn skip(&mut self) -> Result<(), Error> {
+        match self.tag()? {
+            Tag::Start(start) => {
+                self.reader
+                    .read_to_end(start.name())
+                    .map_err(|error| self.error(error))?;
+                Ok(())
+            }
+            Tag::End => Err(self.error("missing a value")),
+        }
+    }
+}
+
+fn is(start: &BytesStart, name: &str) -> bool {
+    start.name().into_inner() == name
+}